@@ -0,0 +1,123 @@
+use cgmath::{ElementWise, EuclideanSpace, Matrix4, Point3, Transform, Vector3};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    /// A box containing nothing, the identity for `union`.
+    pub fn empty() -> Self {
+        Self {
+            min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn grow(&mut self, point: Vector3<f32>) {
+        *self = self.union(&Aabb::new(point, point));
+    }
+
+    pub fn centroid(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Smallest box containing this one after it was transformed.
+    pub fn transform(&self, transform: &Matrix4<f32>) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let mut bounds = Aabb::empty();
+        for corner in 0..8 {
+            let point = Point3::new(
+                if corner & 1 == 0 {
+                    self.min.x
+                } else {
+                    self.max.x
+                },
+                if corner & 2 == 0 {
+                    self.min.y
+                } else {
+                    self.max.y
+                },
+                if corner & 4 == 0 {
+                    self.min.z
+                } else {
+                    self.max.z
+                },
+            );
+            bounds.grow(transform.transform_point(point).to_vec());
+        }
+        bounds
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let extent = self.extent();
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    /// Distance along the ray at which it enters the box, if it does so
+    /// before `max_distance`. `inverse_direction` is `1 / direction`.
+    pub fn intersect(
+        &self,
+        origin: Vector3<f32>,
+        inverse_direction: Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<f32> {
+        let t1 = (self.min - origin).mul_element_wise(inverse_direction);
+        let t2 = (self.max - origin).mul_element_wise(inverse_direction);
+
+        // Where the ray runs in the plane of a face, 0 * inf is NaN, but the
+        // ray never leaves that slab
+        let slab = |t1: f32, t2: f32| {
+            if t1.is_nan() || t2.is_nan() {
+                (f32::NEG_INFINITY, f32::INFINITY)
+            } else {
+                (t1.min(t2), t1.max(t2))
+            }
+        };
+        let (x, y, z) = (slab(t1.x, t2.x), slab(t1.y, t2.y), slab(t1.z, t2.z));
+
+        let t_near = x.0.max(y.0).max(z.0);
+        let t_far = x.1.min(y.1).min(z.1);
+
+        if t_near <= t_far && t_far >= 0.0 && t_near < max_distance {
+            Some(t_near.max(0.0))
+        } else {
+            None
+        }
+    }
+}
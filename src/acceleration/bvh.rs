@@ -0,0 +1,331 @@
+use cgmath::Vector3;
+use wgpu::util::DeviceExt;
+
+use crate::acceleration::aabb::Aabb;
+
+/// Maximum depth of a BVH. The shader traverses with a stack of this size.
+pub const MAX_BVH_DEPTH: usize = 32;
+
+/// Number of buckets the surface area heuristic evaluates per axis.
+const SAH_BINS: usize = 12;
+
+/// Relative cost of traversing a node compared to intersecting a primitive.
+const TRAVERSAL_COST: f32 = 1.0;
+
+/// Leaves with more primitives than this are split even if the SAH prefers a
+/// leaf, so the shader never has to loop over huge leaves.
+const MAX_LEAF_SIZE: usize = 8;
+
+/// Flattened BVH node as laid out on the GPU.
+///
+/// Interior nodes have `count == 0` and their children at `left_or_first`
+/// and `left_or_first + 1`. Leaves reference `count` primitives starting at
+/// `left_or_first` in the reordered primitive list.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BvhNode {
+    pub min: [f32; 3],
+    pub left_or_first: u32,
+    pub max: [f32; 3],
+    pub count: u32,
+}
+
+impl BvhNode {
+    pub fn bounds(&self) -> Aabb {
+        Aabb::new(self.min.into(), self.max.into())
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// Bounding volume hierarchy over a list of primitive bounds, built with the
+/// binned surface area heuristic.
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    /// Original index of every primitive, in the order the leaves reference
+    /// them. Primitive buffers are reordered this way before uploading.
+    pub primitive_indices: Vec<u32>,
+}
+
+struct BuildPrimitive {
+    bounds: Aabb,
+    centroid: Vector3<f32>,
+    index: u32,
+}
+
+#[derive(Copy, Clone)]
+struct Bin {
+    bounds: Aabb,
+    count: usize,
+}
+
+impl Bvh {
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let mut primitives: Vec<BuildPrimitive> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, bounds)| BuildPrimitive {
+                bounds: *bounds,
+                centroid: bounds.centroid(),
+                index: index as u32,
+            })
+            .collect();
+
+        let mut nodes: Vec<BvhNode> = Vec::with_capacity(2 * primitives.len().max(1));
+        nodes.push(bytemuck::Zeroable::zeroed());
+
+        if primitives.is_empty() {
+            // A root that can't be hit, so there is always a node to bind
+            let empty = Aabb::empty();
+            nodes[0].min = empty.min.into();
+            nodes[0].max = empty.max.into();
+        } else {
+            let count = primitives.len();
+            Self::build_node(&mut nodes, 0, &mut primitives, 0, count, 0);
+        }
+
+        Self {
+            nodes,
+            primitive_indices: primitives.iter().map(|primitive| primitive.index).collect(),
+        }
+    }
+
+    fn build_node(
+        nodes: &mut Vec<BvhNode>,
+        node_index: usize,
+        primitives: &mut [BuildPrimitive],
+        first: usize,
+        count: usize,
+        depth: usize,
+    ) {
+        let node_primitives = &mut primitives[first..first + count];
+
+        let bounds = node_primitives
+            .iter()
+            .fold(Aabb::empty(), |bounds, primitive| {
+                bounds.union(&primitive.bounds)
+            });
+        nodes[node_index].min = bounds.min.into();
+        nodes[node_index].max = bounds.max.into();
+
+        let split = if count > 1 && depth + 1 < MAX_BVH_DEPTH {
+            Self::find_split(node_primitives, &bounds)
+        } else {
+            None
+        };
+
+        let split = match split {
+            Some(split) => split,
+            None => {
+                nodes[node_index].left_or_first = first as u32;
+                nodes[node_index].count = count as u32;
+                return;
+            }
+        };
+
+        let left_count = split;
+        let left_index = nodes.len();
+        nodes[node_index].left_or_first = left_index as u32;
+        nodes[node_index].count = 0;
+        nodes.push(bytemuck::Zeroable::zeroed());
+        nodes.push(bytemuck::Zeroable::zeroed());
+
+        Self::build_node(nodes, left_index, primitives, first, left_count, depth + 1);
+        Self::build_node(
+            nodes,
+            left_index + 1,
+            primitives,
+            first + left_count,
+            count - left_count,
+            depth + 1,
+        );
+    }
+
+    /// Partitions `primitives` along the cheapest SAH split and returns the
+    /// number of primitives on the left side, or `None` if a leaf is cheaper.
+    fn find_split(primitives: &mut [BuildPrimitive], bounds: &Aabb) -> Option<usize> {
+        let centroid_bounds = primitives.iter().fold(Aabb::empty(), |bounds, primitive| {
+            bounds.union(&Aabb::new(primitive.centroid, primitive.centroid))
+        });
+        let extent = centroid_bounds.extent();
+
+        let leaf_cost = primitives.len() as f32;
+        let mut best: Option<(usize, usize, f32)> = None;
+
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 {
+                continue;
+            }
+            let bin_of = |primitive: &BuildPrimitive| -> usize {
+                let relative =
+                    (primitive.centroid[axis] - centroid_bounds.min[axis]) / extent[axis];
+                ((relative * SAH_BINS as f32) as usize).min(SAH_BINS - 1)
+            };
+
+            let mut bins = [Bin {
+                bounds: Aabb::empty(),
+                count: 0,
+            }; SAH_BINS];
+            for primitive in primitives.iter() {
+                let bin = &mut bins[bin_of(primitive)];
+                bin.bounds = bin.bounds.union(&primitive.bounds);
+                bin.count += 1;
+            }
+
+            // Sweep from both sides to get the cost of every split plane
+            let mut right_areas = [0.0; SAH_BINS];
+            let mut right_counts = [0; SAH_BINS];
+            let mut right = Bin {
+                bounds: Aabb::empty(),
+                count: 0,
+            };
+            for bin in (1..SAH_BINS).rev() {
+                right.bounds = right.bounds.union(&bins[bin].bounds);
+                right.count += bins[bin].count;
+                right_areas[bin] = right.bounds.surface_area();
+                right_counts[bin] = right.count;
+            }
+
+            let mut left = Bin {
+                bounds: Aabb::empty(),
+                count: 0,
+            };
+            for split in 1..SAH_BINS {
+                left.bounds = left.bounds.union(&bins[split - 1].bounds);
+                left.count += bins[split - 1].count;
+                if left.count == 0 || right_counts[split] == 0 {
+                    continue;
+                }
+
+                let cost = TRAVERSAL_COST
+                    + (left.bounds.surface_area() * left.count as f32
+                        + right_areas[split] * right_counts[split] as f32)
+                        / bounds.surface_area().max(f32::MIN_POSITIVE);
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best = Some((axis, split, cost));
+                }
+            }
+        }
+
+        let (axis, split, cost) = match best {
+            Some(best) => best,
+            // All centroids coincide, only an arbitrary split is possible
+            None if primitives.len() > MAX_LEAF_SIZE => return Some(primitives.len() / 2),
+            None => return None,
+        };
+        if cost >= leaf_cost && primitives.len() <= MAX_LEAF_SIZE {
+            return None;
+        }
+
+        // Partition the primitives around the chosen bin boundary
+        let split_position =
+            centroid_bounds.min[axis] + extent[axis] * split as f32 / SAH_BINS as f32;
+        let mut left_count = 0;
+        for index in 0..primitives.len() {
+            if primitives[index].centroid[axis] < split_position {
+                primitives.swap(index, left_count);
+                left_count += 1;
+            }
+        }
+
+        if left_count == 0 || left_count == primitives.len() {
+            // Floating point put everything on one side, split in the middle
+            left_count = primitives.len() / 2;
+        }
+        Some(left_count)
+    }
+
+    /// Finds the closest primitive hit by the ray. `intersect` is called with
+    /// the original index of each candidate primitive and returns its hit
+    /// distance, if any.
+    pub fn closest_hit<F>(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        mut intersect: F,
+    ) -> Option<(u32, f32)>
+    where
+        F: FnMut(u32) -> Option<f32>,
+    {
+        let inverse_direction =
+            Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut closest: Option<(u32, f32)> = None;
+        let closest_distance =
+            |closest: &Option<(u32, f32)>| closest.map_or(f32::INFINITY, |hit| hit.1);
+
+        if self.primitive_indices.is_empty() {
+            return None;
+        }
+        self.nodes[0]
+            .bounds()
+            .intersect(origin, inverse_direction, f32::INFINITY)?;
+
+        let mut stack = Vec::with_capacity(MAX_BVH_DEPTH);
+        stack.push(0);
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if node.is_leaf() {
+                let first = node.left_or_first as usize;
+                for primitive in &self.primitive_indices[first..first + node.count as usize] {
+                    if let Some(distance) = intersect(*primitive) {
+                        if distance < closest_distance(&closest) {
+                            closest = Some((*primitive, distance));
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let left = node.left_or_first as usize;
+            let max_distance = closest_distance(&closest);
+            let mut children = [
+                (
+                    left,
+                    self.nodes[left]
+                        .bounds()
+                        .intersect(origin, inverse_direction, max_distance),
+                ),
+                (
+                    left + 1,
+                    self.nodes[left + 1].bounds().intersect(
+                        origin,
+                        inverse_direction,
+                        max_distance,
+                    ),
+                ),
+            ];
+            // push the far child first so the near one is visited next
+            if children[0].1.unwrap_or(f32::INFINITY) < children[1].1.unwrap_or(f32::INFINITY) {
+                children.swap(0, 1);
+            }
+            for (child, distance) in children {
+                if distance.is_some() {
+                    stack.push(child);
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+pub struct BvhState {
+    pub buffer: wgpu::Buffer,
+}
+
+impl BvhState {
+    /// Uploads the nodes of one or more BVHs laid out one after another.
+    pub fn new(nodes: &[BvhNode], label: &str, device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(nodes),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self { buffer }
+    }
+}
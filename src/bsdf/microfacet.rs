@@ -0,0 +1,110 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+pub fn luminance(color: Vector3<f32>) -> f32 {
+    color.dot(Vector3::new(0.2126, 0.7152, 0.0722))
+}
+
+pub fn fresnel_schlick(f0: Vector3<f32>, cos_theta: f32) -> Vector3<f32> {
+    let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 + one_minus(f0) * weight
+}
+
+/// Exact Fresnel reflectance of unpolarized light at a dielectric boundary,
+/// `eta` is the index of refraction of the far side over the one of the near
+/// side. Total internal reflection reflects everything.
+pub fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+/// Anisotropic GGX normal distribution of the half vector `h`.
+pub fn ggx_d(alpha: Vector2<f32>, h: Vector3<f32>) -> f32 {
+    let stretched = Vector3::new(h.x / alpha.x, h.y / alpha.y, h.z);
+    let length2 = stretched.magnitude2();
+    1.0 / (PI * alpha.x * alpha.y * length2 * length2)
+}
+
+/// Smith masking lambda of anisotropic GGX for direction `w`.
+pub fn ggx_lambda(alpha: Vector2<f32>, w: Vector3<f32>) -> f32 {
+    let projected = alpha.x * alpha.x * w.x * w.x + alpha.y * alpha.y * w.y * w.y;
+    ((1.0 + projected / (w.z * w.z).max(1e-8)).sqrt() - 1.0) * 0.5
+}
+
+pub fn ggx_g1(alpha: Vector2<f32>, w: Vector3<f32>) -> f32 {
+    1.0 / (1.0 + ggx_lambda(alpha, w))
+}
+
+/// Height correlated masking and shadowing.
+pub fn ggx_g2(alpha: Vector2<f32>, wo: Vector3<f32>, wi: Vector3<f32>) -> f32 {
+    1.0 / (1.0 + ggx_lambda(alpha, wo) + ggx_lambda(alpha, wi))
+}
+
+/// Heitz 2018, "Sampling the GGX Distribution of Visible Normals".
+pub fn sample_ggx_visible_normal(
+    alpha: Vector2<f32>,
+    wo: Vector3<f32>,
+    u: Vector2<f32>,
+) -> Vector3<f32> {
+    // Stretch the view direction to the hemisphere configuration
+    let v = Vector3::new(alpha.x * wo.x, alpha.y * wo.y, wo.z).normalize();
+
+    let length2 = v.x * v.x + v.y * v.y;
+    let t1 = if length2 > 0.0 {
+        Vector3::new(-v.y, v.x, 0.0) / length2.sqrt()
+    } else {
+        Vector3::new(1.0, 0.0, 0.0)
+    };
+    let t2 = v.cross(t1);
+
+    // Point on the projected disc, compressed towards the visible half
+    let r = u.x.sqrt();
+    let phi = 2.0 * PI * u.y;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + v.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+    let n = t1 * p1 + t2 * p2 + v * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+    Vector3::new(alpha.x * n.x, alpha.y * n.y, n.z.max(0.0)).normalize()
+}
+
+pub fn sample_cosine_hemisphere(u: Vector2<f32>) -> Vector3<f32> {
+    let r = u.x.sqrt();
+    let phi = 2.0 * PI * u.y;
+    Vector3::new(r * phi.cos(), r * phi.sin(), (1.0 - u.x).max(0.0).sqrt())
+}
+
+/// Mirrors `incident` at the plane with unit `normal`, as WGSL's `reflect`.
+pub fn reflect(incident: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    incident - normal * (2.0 * normal.dot(incident))
+}
+
+/// Refracts `incident` at the plane with unit `normal`, `eta` is the index of
+/// refraction of the near side over the far side. Zero on total internal
+/// reflection, as WGSL's `refract`.
+pub fn refract(incident: Vector3<f32>, normal: Vector3<f32>, eta: f32) -> Vector3<f32> {
+    let cos_i = normal.dot(incident);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+    incident * eta - normal * (eta * cos_i + k.sqrt())
+}
+
+/// Component wise `a * (1 - t) + b * t`.
+pub fn mix(a: Vector3<f32>, b: Vector3<f32>, t: f32) -> Vector3<f32> {
+    a * (1.0 - t) + b * t
+}
+
+/// Component wise `1 - color`.
+pub fn one_minus(color: Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(1.0, 1.0, 1.0) - color
+}
@@ -0,0 +1,267 @@
+//! CPU implementation of the principled BSDF in `src/shader/bsdf.wgsl`.
+//!
+//! Mirrors the shader function by function so the BSDF can be tested without
+//! a GPU. Directions are in the local frame of the shading normal, which is
+//! along +z on the side of `wo`, with the tangent along x.
+
+pub mod microfacet;
+
+use std::f32::consts::PI;
+
+use cgmath::{ElementWise, InnerSpace, Vector2, Vector3, Vector4};
+
+use crate::bsdf::microfacet::{
+    fresnel_dielectric, fresnel_schlick, ggx_d, ggx_g1, ggx_g2, luminance, mix, one_minus, reflect,
+    refract, sample_cosine_hemisphere, sample_ggx_visible_normal,
+};
+use crate::types::material::Material;
+
+/// Smallest GGX alpha, keeps mirrors numerically stable.
+pub const MIN_ALPHA: f32 = 0.001;
+/// Reflectance of the clearcoat at normal incidence.
+pub const CLEARCOAT_F0: f32 = 0.04;
+
+/// Value and solid angle density of the BSDF for a pair of directions.
+#[derive(Debug, Copy, Clone)]
+pub struct BsdfEval {
+    /// BSDF times the cosine of the incoming direction.
+    pub value: Vector3<f32>,
+    pub pdf: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BsdfSample {
+    pub direction: Vector3<f32>,
+    /// BSDF times cosine divided by pdf.
+    pub weight: Vector3<f32>,
+    pub pdf: f32,
+}
+
+/// Lobe parameters derived from a material.
+#[derive(Debug, Copy, Clone)]
+pub struct BsdfLobes {
+    pub diffuse_color: Vector3<f32>,
+    /// Reflectance at normal incidence.
+    pub f0: Vector3<f32>,
+    /// GGX alpha along the tangent and the bitangent.
+    pub alpha: Vector2<f32>,
+    pub sheen: f32,
+    pub sheen_color: Vector3<f32>,
+    pub clearcoat: f32,
+    pub clearcoat_alpha: f32,
+    /// Weight of the dielectric lobe, the opaque ones get the rest.
+    pub transmission: f32,
+    /// Index of refraction of the side `wi` refracts to over the one of `wo`.
+    pub eta: f32,
+    pub transmission_color: Vector3<f32>,
+}
+
+impl BsdfLobes {
+    /// `front_face` tells whether `wo` is outside the material.
+    pub fn new(material: &Material, front_face: bool) -> Self {
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let color = Vector3::from(material.color);
+        let tint = tint_of(color);
+
+        let dielectric_f0 = mix(white, tint, material.specular_tint) * (0.08 * material.specular);
+
+        let aspect = (1.0 - 0.9 * material.anisotropic).sqrt();
+        let alpha = material.roughness * material.roughness;
+
+        Self {
+            diffuse_color: color * (1.0 - material.metallic),
+            f0: mix(dielectric_f0, color, material.metallic),
+            alpha: Vector2::new(
+                (alpha / aspect).max(MIN_ALPHA),
+                (alpha * aspect).max(MIN_ALPHA),
+            ),
+            sheen: material.sheen * (1.0 - material.metallic),
+            sheen_color: mix(white, tint, material.sheen_tint),
+            clearcoat: material.clearcoat,
+            clearcoat_alpha: (material.clearcoat_roughness * material.clearcoat_roughness)
+                .max(MIN_ALPHA),
+            transmission: material.transmission * (1.0 - material.metallic),
+            eta: if front_face {
+                material.ior
+            } else {
+                1.0 / material.ior
+            },
+            transmission_color: color,
+        }
+    }
+
+    /// Energy left for the base after passing the clearcoat at `cos_theta` to the normal.
+    fn clearcoat_transmittance(&self, cos_theta: f32) -> f32 {
+        1.0 - self.clearcoat * fresnel_schlick(Vector3::new(CLEARCOAT_F0, 0.0, 0.0), cos_theta).x
+    }
+
+    /// Probabilities of sampling the clearcoat, specular, diffuse and dielectric lobes.
+    pub fn lobe_probabilities(&self, wo: Vector3<f32>) -> Vector4<f32> {
+        let clearcoat = 1.0 - self.clearcoat_transmittance(wo.z);
+        let base = 1.0 - clearcoat;
+        let opaque = base * (1.0 - self.transmission);
+        let specular = opaque * luminance(fresnel_schlick(self.f0, wo.z));
+        let diffuse = opaque
+            * (luminance(self.diffuse_color.mul_element_wise(one_minus(self.f0)))
+                + self.sheen * luminance(self.sheen_color));
+        let probabilities = Vector4::new(clearcoat, specular, diffuse, base * self.transmission);
+
+        let total = probabilities.x + probabilities.y + probabilities.z + probabilities.w;
+        if total <= 0.0 {
+            return Vector4::new(0.0, 1.0, 0.0, 0.0);
+        }
+        probabilities / total
+    }
+
+    pub fn eval(&self, wo: Vector3<f32>, wi: Vector3<f32>) -> BsdfEval {
+        let mut result = BsdfEval {
+            value: Vector3::new(0.0, 0.0, 0.0),
+            pdf: 0.0,
+        };
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return result;
+        }
+
+        let probabilities = self.lobe_probabilities(wo);
+        let base = self.clearcoat_transmittance(wo.z) * self.clearcoat_transmittance(wi.z.abs());
+
+        if wi.z > 0.0 {
+            let h = (wo + wi).normalize();
+            let cos_d = wi.dot(h);
+
+            let d = ggx_d(self.alpha, h);
+            let specular =
+                fresnel_schlick(self.f0, cos_d) * (d * ggx_g2(self.alpha, wo, wi) / (4.0 * wo.z));
+            let specular_pdf = ggx_g1(self.alpha, wo) * d / (4.0 * wo.z);
+
+            // Ashikhmin and Shirley 2000, "An Anisotropic Phong BRDF Model"
+            let ashikhmin_shirley = self.diffuse_color.mul_element_wise(one_minus(self.f0))
+                * (28.0 / (23.0 * PI)
+                    * (1.0 - (1.0 - 0.5 * wi.z).powi(5))
+                    * (1.0 - (1.0 - 0.5 * wo.z).powi(5)));
+            let sheen = self.sheen_color.mul_element_wise(one_minus(self.f0)) / PI;
+            let diffuse = mix(ashikhmin_shirley, sheen, self.sheen * (1.0 - cos_d).powi(5)) * wi.z;
+
+            let clearcoat_alpha = Vector2::new(self.clearcoat_alpha, self.clearcoat_alpha);
+            let clearcoat_d = ggx_d(clearcoat_alpha, h);
+            let clearcoat = self.clearcoat
+                * fresnel_schlick(Vector3::new(CLEARCOAT_F0, 0.0, 0.0), cos_d).x
+                * clearcoat_d
+                * ggx_g2(clearcoat_alpha, wo, wi)
+                / (4.0 * wo.z);
+            let clearcoat_pdf = ggx_g1(clearcoat_alpha, wo) * clearcoat_d / (4.0 * wo.z);
+
+            result.value = Vector3::new(clearcoat, clearcoat, clearcoat)
+                + (specular + diffuse) * (base * (1.0 - self.transmission));
+            result.pdf = probabilities.x * clearcoat_pdf
+                + probabilities.y * specular_pdf
+                + probabilities.z * wi.z / PI;
+        }
+
+        if self.transmission > 0.0 {
+            let dielectric = self.dielectric_eval(wo, wi);
+            result.value += dielectric.value * (base * self.transmission);
+            result.pdf += probabilities.w * dielectric.pdf;
+        }
+        result
+    }
+
+    /// Picks one of the lobes with `u_lobe` and samples a direction from it with `u`.
+    pub fn sample(&self, wo: Vector3<f32>, u_lobe: f32, u: Vector2<f32>) -> Option<BsdfSample> {
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let probabilities = self.lobe_probabilities(wo);
+        let clearcoat_end = probabilities.x;
+        let specular_end = clearcoat_end + probabilities.y;
+        let diffuse_end = specular_end + probabilities.z;
+
+        let mut reflected = true;
+        let wi = if u_lobe < clearcoat_end {
+            let clearcoat_alpha = Vector2::new(self.clearcoat_alpha, self.clearcoat_alpha);
+            reflect(-wo, sample_ggx_visible_normal(clearcoat_alpha, wo, u))
+        } else if u_lobe < specular_end {
+            reflect(-wo, sample_ggx_visible_normal(self.alpha, wo, u))
+        } else if u_lobe < diffuse_end {
+            sample_cosine_hemisphere(u)
+        } else {
+            let u_reflect = (u_lobe - diffuse_end) / probabilities.w;
+            let h = sample_ggx_visible_normal(self.alpha, wo, u);
+            if u_reflect < fresnel_dielectric(wo.dot(h), self.eta) {
+                reflect(-wo, h)
+            } else {
+                reflected = false;
+                refract(-wo, h, 1.0 / self.eta)
+            }
+        };
+
+        // Reflections off microfacets can point below the surface and
+        // refractions above it, eval would count them towards other lobes
+        if (wi.z > 0.0) != reflected {
+            return None;
+        }
+
+        let evaluation = self.eval(wo, wi);
+        if evaluation.pdf <= 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            direction: wi,
+            weight: evaluation.value / evaluation.pdf,
+            pdf: evaluation.pdf,
+        })
+    }
+
+    /// Rough dielectric (Walter et al. 2007, "Microfacet Models for Refraction
+    /// through Rough Surfaces").
+    fn dielectric_eval(&self, wo: Vector3<f32>, wi: Vector3<f32>) -> BsdfEval {
+        let none = BsdfEval {
+            value: Vector3::new(0.0, 0.0, 0.0),
+            pdf: 0.0,
+        };
+
+        if wi.z > 0.0 {
+            let h = (wo + wi).normalize();
+            let fresnel = fresnel_dielectric(wo.dot(h), self.eta);
+            let d = ggx_d(self.alpha, h);
+            let value = fresnel * d * ggx_g2(self.alpha, wo, wi) / (4.0 * wo.z);
+            return BsdfEval {
+                value: Vector3::new(value, value, value),
+                pdf: fresnel * ggx_g1(self.alpha, wo) * d / (4.0 * wo.z),
+            };
+        }
+
+        let mut h = (wo + wi * self.eta).normalize();
+        if h.z < 0.0 {
+            h = -h;
+        }
+        let cos_o = wo.dot(h);
+        let cos_i = wi.dot(h);
+        if cos_o <= 0.0 || cos_i >= 0.0 {
+            return none;
+        }
+
+        let transmitted = 1.0 - fresnel_dielectric(cos_o, self.eta);
+        let d = ggx_d(self.alpha, h);
+        let denominator = cos_o + self.eta * cos_i;
+        let jacobian = -cos_i / (denominator * denominator);
+        let value = transmitted * d * ggx_g2(self.alpha, wo, wi) * cos_o * jacobian / wo.z;
+        let pdf = transmitted * ggx_g1(self.alpha, wo) * d * cos_o * jacobian * self.eta * self.eta
+            / wo.z;
+        BsdfEval {
+            value: self.transmission_color * value,
+            pdf,
+        }
+    }
+}
+
+/// Hue and saturation of a color at unit luminance.
+pub fn tint_of(color: Vector3<f32>) -> Vector3<f32> {
+    let color_luminance = luminance(color);
+    if color_luminance <= 0.0 {
+        return Vector3::new(1.0, 1.0, 1.0);
+    }
+    color / color_luminance
+}
@@ -0,0 +1,84 @@
+use cgmath::{InnerSpace, Vector3};
+use serde::Deserialize;
+
+use crate::scene::scene_file::CameraDescription;
+
+/// Movement of the camera over the course of an animation.
+#[derive(Debug, Clone, Deserialize)]
+pub enum CameraPath {
+    /// Circles around `target` at the given radius and height.
+    Turntable {
+        target: [f32; 3],
+        radius: f32,
+        height: f32,
+        #[serde(default = "default_revolutions")]
+        revolutions: f32,
+    },
+    /// Moves linearly through the given cameras, evenly spaced in time.
+    Keyframes(Vec<CameraDescription>),
+}
+
+fn default_revolutions() -> f32 {
+    1.0
+}
+
+impl CameraPath {
+    /// Time of `frame` out of `frames`. Turntables loop, so the last frame
+    /// stops one step short of the first one; keyframes end on the last key.
+    pub fn frame_time(&self, frame: u32, frames: u32) -> f32 {
+        match self {
+            CameraPath::Turntable { .. } => frame as f32 / frames.max(1) as f32,
+            CameraPath::Keyframes(_) => frame as f32 / (frames.max(2) - 1) as f32,
+        }
+    }
+
+    /// Returns the camera at time `t` in `[0, 1)`. Parameters the path does
+    /// not control (fovy, znear, ...) are taken from `base`.
+    pub fn camera_at(&self, t: f32, base: &CameraDescription) -> CameraDescription {
+        match self {
+            CameraPath::Turntable {
+                target,
+                radius,
+                height,
+                revolutions,
+            } => {
+                let angle = t * revolutions * 2.0 * std::f32::consts::PI;
+                // angle 0 looks along +z, like the default camera
+                let offset = Vector3::new(-angle.sin(), 0.0, -angle.cos()) * *radius;
+                CameraDescription {
+                    eye: [
+                        target[0] + offset.x,
+                        target[1] + height,
+                        target[2] + offset.z,
+                    ],
+                    target: *target,
+                    ..base.clone()
+                }
+            }
+            CameraPath::Keyframes(keyframes) => match keyframes.len() {
+                0 => base.clone(),
+                1 => keyframes[0].clone(),
+                count => {
+                    let position = t.clamp(0.0, 1.0) * (count - 1) as f32;
+                    let index = (position.floor() as usize).min(count - 2);
+                    let fraction = position - index as f32;
+                    lerp_camera(&keyframes[index], &keyframes[index + 1], fraction)
+                }
+            },
+        }
+    }
+}
+
+fn lerp_camera(a: &CameraDescription, b: &CameraDescription, t: f32) -> CameraDescription {
+    let lerp = |a: [f32; 3], b: [f32; 3]| -> [f32; 3] {
+        (Vector3::from(a) + (Vector3::from(b) - Vector3::from(a)) * t).into()
+    };
+
+    CameraDescription {
+        eye: lerp(a.eye, b.eye),
+        target: lerp(a.target, b.target),
+        up: Vector3::from(lerp(a.up, b.up)).normalize().into(),
+        fovy: a.fovy + (b.fovy - a.fovy) * t,
+        znear: a.znear + (b.znear - a.znear) * t,
+    }
+}
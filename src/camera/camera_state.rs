@@ -8,24 +8,15 @@ pub struct CameraState {
     pub(crate) object: Camera,
     pub(crate) uniform: CameraUniform,
     pub(crate) buffer: Buffer,
+    /// Camera of the frame before, to find where points were on screen.
+    pub(crate) previous_buffer: Buffer,
     pub(crate) controller: CameraController,
+    /// Whether the last update moved the camera.
+    previous_moved: bool,
 }
 
 impl CameraState {
-    pub(crate) fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
-        let object = Camera {
-            // position the camera one unit up and 2 units back
-            // +z is out of the screen
-            eye: (0.0, 0.0, -5.0).into(),
-            // have it look at the origin
-            target: (0.0, 0.0, 0.0).into(),
-            // which way is "up"
-            up: cgmath::Vector3::unit_y(),
-            aspect: config.width as f32 / config.height as f32,
-            fovy: 70.0,
-            znear: 0.01,
-        };
-
+    pub(crate) fn new(device: &wgpu::Device, object: Camera) -> Self {
         let mut uniform = CameraUniform::new();
         uniform.update(&object);
 
@@ -34,21 +25,42 @@ impl CameraState {
             contents: bytemuck::cast_slice(&[uniform]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        let previous_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Previous Camera Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
 
         let controller = CameraController::new(0.2);
 
         Self {
             controller,
             buffer,
+            previous_buffer,
             uniform,
             object,
+            previous_moved: false,
         }
     }
 
-    pub(crate) fn update(&mut self, queue: &wgpu::Queue) {
+    /// Applies the controller input and uploads the camera, and the one it
+    /// replaces as the previous camera. Returns whether the camera changed
+    /// since the last update.
+    pub(crate) fn update(&mut self, queue: &wgpu::Queue) -> bool {
+        let previous = self.uniform;
+
         self.controller.update_camera(&mut self.object);
         self.uniform.update(&self.object);
 
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+        let moved = bytemuck::bytes_of(&previous) != bytemuck::bytes_of(&self.uniform);
+        if moved {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+        }
+        // The previous camera is one frame behind, so it catches up the frame after a move
+        if moved || self.previous_moved {
+            queue.write_buffer(&self.previous_buffer, 0, bytemuck::cast_slice(&[previous]));
+        }
+        self.previous_moved = moved;
+        moved
     }
 }
@@ -15,10 +15,9 @@ impl Camera {
         // let transform = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up) * cgmath::Matrix4::from_translation(cgmath::Vector3::from([self.eye.x, self.eye.y, self.eye.z]));
         // let transform = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
         // TODO: Add translation to matrix instead of using 'eye' attribute
-        let transform = cgmath::Matrix4::look_at_lh(self.eye, self.target, self.up);
         // let translation = cgmath::Matrix4::from_translation(cgmath::Vector3::from([self.eye.x, self.eye.y, self.eye.z]));
         // let transform = transform * translation;
-        return transform;
+        cgmath::Matrix4::look_at_lh(self.eye, self.target, self.up)
     }
 
     // pub fn change_fovy(&mut self, fovy: f32) {
@@ -40,6 +39,12 @@ pub struct CameraUniform {
     pub _padding2: u32,
 }
 
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CameraUniform {
     pub fn new() -> Self {
         use cgmath::SquareMatrix;
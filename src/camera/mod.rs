@@ -1,3 +1,4 @@
 pub mod camera_controller;
+pub mod camera_path;
 pub mod camera_state;
 pub mod main_camera;
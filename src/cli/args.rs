@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use crate::sampler::sampler_kind::SamplerKind;
+use crate::types::globals::RenderSettings;
+
+const USAGE: &str = "Usage: wgpu_raytracer [options]
+
+Options:
+    --scene <path>       scene file or .obj/.gltf/.glb model to render instead of the default scene
+    --output <path>      render a single frame without a window and save it
+    --gif <path>         render the scene's camera animation to a GIF
+    --width <pixels>     width of the headless render (default 800)
+    --height <pixels>    height of the headless render (default 600)
+    --samples <count>    passes averaged into each headless frame
+    --sampler <name>     random, sobol or blue-noise (Tab cycles them in the window)
+    --spp <count>        paths traced per pixel in every pass (default 10, at most 256, - and + in the window)
+    --depth <count>      bounces per path at most (default 5, at most 64, [ and ] in the window)
+    --roulette <count>   bounces before Russian roulette may end a path (default 3)
+    --frames <count>     number of GIF frames
+    --delay <1/100 s>    delay between GIF frames
+    --fallback-adapter   force a software adapter for headless rendering";
+
+/// Command line options of the raytracer binary.
+#[derive(Debug)]
+pub struct Args {
+    /// Scene file or model to load instead of the built-in default scene.
+    pub scene: Option<PathBuf>,
+    /// Image to render to without opening a window.
+    pub output: Option<PathBuf>,
+    /// GIF to render the camera animation to.
+    pub gif: Option<PathBuf>,
+    pub width: u32,
+    pub height: u32,
+    pub samples: Option<u32>,
+    pub frames: Option<u32>,
+    pub delay: Option<u16>,
+    pub sampler_kind: SamplerKind,
+    pub render_settings: RenderSettings,
+    pub force_fallback_adapter: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            scene: None,
+            output: None,
+            gif: None,
+            width: 800,
+            height: 600,
+            samples: None,
+            frames: None,
+            delay: None,
+            sampler_kind: SamplerKind::default(),
+            render_settings: RenderSettings::default(),
+            force_fallback_adapter: false,
+        }
+    }
+}
+
+impl Args {
+    /// Parses the arguments of the current process, exiting with a usage
+    /// message if they are invalid.
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(args) => args,
+            Err(error) => {
+                eprintln!("{}\n\n{}", error, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut parsed = Self::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scene" => parsed.scene = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--output" => parsed.output = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--gif" => parsed.gif = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--width" => parsed.width = size(&arg, args.next())?,
+                "--height" => parsed.height = size(&arg, args.next())?,
+                "--samples" => parsed.samples = Some(size(&arg, args.next())?),
+                "--frames" => parsed.frames = Some(size(&arg, args.next())?),
+                "--delay" => parsed.delay = Some(number(&arg, args.next())?),
+                "--sampler" => parsed.sampler_kind = value(&arg, args.next())?.parse()?,
+                "--spp" => parsed.render_settings.samples_per_frame = size(&arg, args.next())?,
+                "--depth" => parsed.render_settings.max_bounces = size(&arg, args.next())?,
+                "--roulette" => parsed.render_settings.roulette_depth = number(&arg, args.next())?,
+                "--fallback-adapter" => parsed.force_fallback_adapter = true,
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        parsed.render_settings = parsed.render_settings.clamped();
+        Ok(parsed)
+    }
+}
+
+fn value(arg: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("{} requires a value", arg))
+}
+
+fn size(arg: &str, value: Option<String>) -> Result<u32, String> {
+    match number(arg, value)? {
+        0 => Err(format!("{} must be greater than 0", arg)),
+        size => Ok(size),
+    }
+}
+
+fn number<T: std::str::FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+    let value = self::value(arg, value)?;
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got '{}'", arg, value))
+}
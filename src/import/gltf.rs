@@ -0,0 +1,249 @@
+use std::path::Path;
+
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Transform, Vector2,
+    Vector3,
+};
+use gltf::camera::Projection;
+use gltf::mesh::Mode;
+
+use crate::import::import_error::ImportError;
+use crate::scene::scene_file::CameraDescription;
+use crate::types::material::Material;
+use crate::types::mesh::Mesh;
+
+/// Name of the material used by primitives that don't reference one.
+pub const DEFAULT_MATERIAL_NAME: &str = "default";
+
+/// Meshes, materials and camera of a glTF scene with the node hierarchy
+/// flattened into world space.
+pub struct GltfModel {
+    /// Every triangle primitive merged into one mesh, the material ids index `materials`.
+    pub mesh: Mesh,
+    pub materials: Vec<Material>,
+    pub material_names: Vec<String>,
+    /// The first camera of the document that is placed by a node.
+    pub camera: Option<CameraDescription>,
+}
+
+/// Loads a `.gltf` or `.glb` file and the buffers it references. The default
+/// scene is imported, or the first one if there is no default.
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<GltfModel, ImportError> {
+    let path = path.as_ref();
+    let (document, buffers, _images) = gltf::import(path).map_err(|error| ImportError::Gltf {
+        path: path.display().to_string(),
+        error,
+    })?;
+
+    let mut model = GltfModel {
+        mesh: Mesh::default(),
+        materials: document
+            .materials()
+            .map(|material| convert_material(&material))
+            .collect(),
+        material_names: document
+            .materials()
+            .map(|material| match material.name() {
+                Some(name) => name.to_string(),
+                None => format!("material {}", material.index().unwrap_or_default()),
+            })
+            .collect(),
+        camera: None,
+    };
+    let mut default_material: Option<u32> = None;
+    // The first camera by document order, not by traversal order
+    let mut first_camera: Option<(usize, CameraDescription)> = None;
+
+    let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    else {
+        return Ok(model);
+    };
+
+    // Depth first in document order, the stack holds the nodes in reverse
+    let mut nodes: Vec<(gltf::Node, Matrix4<f32>)> = scene
+        .nodes()
+        .map(|node| (node, Matrix4::identity()))
+        .collect();
+    nodes.reverse();
+    while let Some((node, parent_transform)) = nodes.pop() {
+        let transform = parent_transform * Matrix4::from(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if primitive.mode() != Mode::Triangles {
+                    log::warn!(
+                        "{}: skipping {:?} primitive of mesh {}",
+                        path.display(),
+                        primitive.mode(),
+                        mesh.index()
+                    );
+                    continue;
+                }
+
+                let material_id = match primitive.material().index() {
+                    Some(index) => index as u32,
+                    None => *default_material.get_or_insert_with(|| {
+                        model.materials.push(Material::default());
+                        model.material_names.push(DEFAULT_MATERIAL_NAME.to_string());
+                        model.materials.len() as u32 - 1
+                    }),
+                };
+
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()].0[..]));
+                let Some(positions) = reader.read_positions() else {
+                    log::warn!(
+                        "{}: skipping primitive of mesh {} without positions",
+                        path.display(),
+                        mesh.index()
+                    );
+                    continue;
+                };
+
+                let mut primitive_mesh = Mesh {
+                    positions: positions.map(Vector3::from).collect(),
+                    ..Default::default()
+                };
+                let vertex_count = primitive_mesh.positions.len();
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..vertex_count as u32).collect(),
+                };
+                primitive_mesh.triangles = indices
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                    .collect();
+                primitive_mesh.material_ids = vec![material_id; primitive_mesh.triangles.len()];
+                primitive_mesh.uvs = match reader.read_tex_coords(0) {
+                    Some(uvs) => uvs.into_f32().map(Vector2::from).collect(),
+                    None => vec![Vector2::new(0.0, 0.0); vertex_count],
+                };
+                match reader.read_normals() {
+                    Some(normals) => primitive_mesh.normals = normals.map(Vector3::from).collect(),
+                    None => primitive_mesh.compute_normals(),
+                }
+
+                if let Err(reason) = primitive_mesh.validate() {
+                    log::warn!(
+                        "{}: skipping invalid primitive of mesh {}: {}",
+                        path.display(),
+                        mesh.index(),
+                        reason
+                    );
+                    continue;
+                }
+
+                primitive_mesh.find_closed();
+                append_transformed(&mut model.mesh, primitive_mesh, &transform);
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            let is_first = first_camera
+                .as_ref()
+                .is_none_or(|(index, _)| camera.index() < *index);
+            if is_first {
+                match convert_camera(&camera, &transform) {
+                    Some(description) => first_camera = Some((camera.index(), description)),
+                    None => log::warn!(
+                        "{}: skipping orthographic camera {}",
+                        path.display(),
+                        camera.index()
+                    ),
+                }
+            }
+        }
+
+        let first_child = nodes.len();
+        nodes.extend(node.children().map(|child| (child, transform)));
+        nodes[first_child..].reverse();
+    }
+
+    model.camera = first_camera.map(|(_, camera)| camera);
+    Ok(model)
+}
+
+fn convert_material(material: &gltf::Material) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [red, green, blue, _alpha] = pbr.base_color_factor();
+    let emission_color = material.emissive_factor();
+    let emission_strength = if emission_color.iter().any(|channel| *channel > 0.0) {
+        material.emissive_strength().unwrap_or(1.0)
+    } else {
+        0.0
+    };
+
+    Material {
+        color: [red, green, blue],
+        emission_color,
+        emission_strength,
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        // Dielectrics in glTF reflect 4% at normal incidence, as with the default specular
+        ..Default::default()
+    }
+}
+
+/// glTF cameras look down their local -Z axis with +Y up.
+fn convert_camera(camera: &gltf::Camera, transform: &Matrix4<f32>) -> Option<CameraDescription> {
+    let Projection::Perspective(perspective) = camera.projection() else {
+        return None;
+    };
+
+    let eye = transform.transform_point(Point3::new(0.0, 0.0, 0.0));
+    let forward = transform
+        .transform_vector(Vector3::new(0.0, 0.0, -1.0))
+        .normalize();
+    let up = transform.transform_vector(Vector3::unit_y()).normalize();
+
+    Some(CameraDescription {
+        eye: eye.into(),
+        target: (eye + forward).into(),
+        up: up.into(),
+        fovy: perspective.yfov().to_degrees(),
+        znear: perspective.znear(),
+    })
+}
+
+/// Appends `primitive` to `mesh` with its positions and normals moved into world space.
+fn append_transformed(mesh: &mut Mesh, primitive: Mesh, transform: &Matrix4<f32>) {
+    let linear = Matrix3::from_cols(
+        transform.x.truncate(),
+        transform.y.truncate(),
+        transform.z.truncate(),
+    );
+    let normal_matrix = linear
+        .invert()
+        .map(|inverse| inverse.transpose())
+        .unwrap_or(linear);
+    // Mirroring transforms flip the winding, keep it counter-clockwise
+    let mirrored = linear.determinant() < 0.0;
+
+    let first_vertex = mesh.positions.len() as u32;
+    mesh.positions
+        .extend(primitive.positions.iter().map(|position| {
+            transform
+                .transform_point(Point3::from_vec(*position))
+                .to_vec()
+        }));
+    mesh.normals.extend(
+        primitive
+            .normals
+            .iter()
+            .map(|normal| (normal_matrix * normal).normalize()),
+    );
+    mesh.uvs.extend(primitive.uvs);
+    mesh.closed.resize(mesh.triangles.len(), false);
+    mesh.closed.extend(primitive.closed);
+    mesh.triangles
+        .extend(primitive.triangles.iter().map(|[a, b, c]| {
+            let [a, b, c] = [a, b, c].map(|index| index + first_vertex);
+            if mirrored {
+                [a, c, b]
+            } else {
+                [a, b, c]
+            }
+        }));
+    mesh.material_ids.extend(primitive.material_ids);
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+
+/// Errors that can occur while importing a model file.
+#[derive(Debug)]
+pub enum ImportError {
+    Io {
+        path: String,
+        error: std::io::Error,
+    },
+    /// Malformed content at a 1-based line of `path`.
+    Parse {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    Gltf {
+        path: String,
+        error: gltf::Error,
+    },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io { path, error } => {
+                write!(f, "could not read '{}': {}", path, error)
+            }
+            ImportError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path, line, message),
+            ImportError::Gltf { path, error } => write!(f, "'{}': {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io { error, .. } => Some(error),
+            ImportError::Gltf { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
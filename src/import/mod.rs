@@ -0,0 +1,3 @@
+pub mod gltf;
+pub mod import_error;
+pub mod obj;
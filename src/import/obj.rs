@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use cgmath::{Vector2, Vector3};
+
+use crate::import::import_error::ImportError;
+use crate::types::material::Material;
+use crate::types::mesh::Mesh;
+
+/// Name of the material used by faces that come before any `usemtl`.
+pub const DEFAULT_MATERIAL_NAME: &str = "default";
+
+/// Geometry and materials of a Wavefront OBJ file. The material ids of the
+/// mesh index `materials`, which only holds the materials that are used.
+pub struct ObjModel {
+    pub mesh: Mesh,
+    pub materials: Vec<Material>,
+    pub material_names: Vec<String>,
+}
+
+/// Loads an OBJ file and the MTL libraries it references, which are looked up
+/// next to it.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ObjModel, ImportError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    parse_obj(&source, &path.display().to_string(), |library| {
+        load_mtl(directory.join(library))
+    })
+}
+
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<Vec<(String, Material)>, ImportError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    parse_mtl(&source, &path.display().to_string())
+}
+
+fn read_file(path: &Path) -> Result<String, ImportError> {
+    std::fs::read_to_string(path).map_err(|error| ImportError::Io {
+        path: path.display().to_string(),
+        error,
+    })
+}
+
+/// Parses OBJ source. `path` is only used in error messages, `load_library`
+/// is called with the file name of every `mtllib`.
+///
+/// Polygons are triangulated as fans. Vertices without a normal make the
+/// whole mesh fall back to computed smooth normals.
+pub fn parse_obj<F>(source: &str, path: &str, mut load_library: F) -> Result<ObjModel, ImportError>
+where
+    F: FnMut(&str) -> Result<Vec<(String, Material)>, ImportError>,
+{
+    let error = |line: usize, message: String| ImportError::Parse {
+        path: path.to_string(),
+        line,
+        message,
+    };
+
+    let mut positions: Vec<Vector3<f32>> = vec![];
+    let mut normals: Vec<Vector3<f32>> = vec![];
+    let mut uvs: Vec<Vector2<f32>> = vec![];
+
+    let mut library: Vec<(String, Material)> = vec![];
+    let mut materials: Vec<Material> = vec![];
+    let mut material_names: Vec<String> = vec![];
+    let mut current_material: Option<u32> = None;
+
+    // Unique (position, uv, normal) combinations become the mesh vertices
+    let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut vertex_uvs: Vec<Option<Vector2<f32>>> = vec![];
+    let mut vertex_normals: Vec<Option<Vector3<f32>>> = vec![];
+    let mut mesh = Mesh::default();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let values = parse_floats(&arguments, 3, 4)
+                    .map_err(|message| error(line_number, message))?;
+                positions.push(Vector3::new(values[0], values[1], values[2]));
+            }
+            "vn" => {
+                let values = parse_floats(&arguments, 3, 3)
+                    .map_err(|message| error(line_number, message))?;
+                normals.push(Vector3::new(values[0], values[1], values[2]));
+            }
+            "vt" => {
+                let values = parse_floats(&arguments, 1, 3)
+                    .map_err(|message| error(line_number, message))?;
+                uvs.push(Vector2::new(
+                    values[0],
+                    values.get(1).copied().unwrap_or(0.0),
+                ));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error(
+                        line_number,
+                        format!("face needs at least 3 vertices, found {}", arguments.len()),
+                    ));
+                }
+
+                let mut face: Vec<u32> = vec![];
+                for vertex in &arguments {
+                    let key = parse_face_vertex(vertex, positions.len(), uvs.len(), normals.len())
+                        .map_err(|message| error(line_number, message))?;
+                    let vertex_index = *vertex_indices.entry(key).or_insert_with(|| {
+                        let (position, uv, normal) = key;
+                        mesh.positions.push(positions[position]);
+                        vertex_uvs.push(uv.map(|uv| uvs[uv]));
+                        vertex_normals.push(normal.map(|normal| normals[normal]));
+                        mesh.positions.len() as u32 - 1
+                    });
+                    face.push(vertex_index);
+                }
+
+                let material_id = *current_material.get_or_insert_with(|| {
+                    materials.push(Material::default());
+                    material_names.push(DEFAULT_MATERIAL_NAME.to_string());
+                    materials.len() as u32 - 1
+                });
+                for corner in 1..face.len() - 1 {
+                    mesh.triangles
+                        .push([face[0], face[corner], face[corner + 1]]);
+                    mesh.material_ids.push(material_id);
+                }
+            }
+            "mtllib" => {
+                if arguments.is_empty() {
+                    return Err(error(line_number, "expected a file name".to_string()));
+                }
+                for name in &arguments {
+                    let loaded = load_library(name).map_err(|load_error| {
+                        error(
+                            line_number,
+                            format!("could not load material library '{}': {}", name, load_error),
+                        )
+                    })?;
+                    library.extend(loaded);
+                }
+            }
+            "usemtl" => {
+                let name = arguments.join(" ");
+                if let Some(index) = material_names.iter().position(|used| *used == name) {
+                    current_material = Some(index as u32);
+                    continue;
+                }
+
+                let material = library
+                    .iter()
+                    .find(|(library_name, _)| *library_name == name)
+                    .map(|(_, material)| material.clone())
+                    .ok_or_else(|| error(line_number, format!("unknown material '{}'", name)))?;
+                materials.push(material);
+                material_names.push(name);
+                current_material = Some(materials.len() as u32 - 1);
+            }
+            // Grouping and smoothing don't change the geometry
+            "o" | "g" | "s" => {}
+            _ => log::debug!("{}:{}: ignoring '{}'", path, line_number, keyword),
+        }
+    }
+
+    if vertex_normals.iter().all(Option::is_some) {
+        mesh.normals = vertex_normals.into_iter().flatten().collect();
+    }
+    if vertex_uvs.iter().any(Option::is_some) {
+        mesh.uvs = vertex_uvs
+            .into_iter()
+            .map(|uv| uv.unwrap_or(Vector2::new(0.0, 0.0)))
+            .collect();
+    }
+    mesh.find_closed();
+
+    Ok(ObjModel {
+        mesh,
+        materials,
+        material_names,
+    })
+}
+
+/// Parses MTL source into named materials. `Kd` becomes the color and `Ke`
+/// the emission, everything else is ignored.
+pub fn parse_mtl(source: &str, path: &str) -> Result<Vec<(String, Material)>, ImportError> {
+    let error = |line: usize, message: String| ImportError::Parse {
+        path: path.to_string(),
+        line,
+        message,
+    };
+
+    let mut materials: Vec<(String, Material)> = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "newmtl" => {
+                if arguments.is_empty() {
+                    return Err(error(line_number, "expected a material name".to_string()));
+                }
+                materials.push((arguments.join(" "), Material::default()));
+            }
+            "Kd" | "Ke" => {
+                let color =
+                    parse_color(&arguments).map_err(|message| error(line_number, message))?;
+                let (_, material) = materials.last_mut().ok_or_else(|| {
+                    error(line_number, format!("'{}' before any 'newmtl'", keyword))
+                })?;
+
+                if keyword == "Kd" {
+                    material.color = color;
+                } else {
+                    material.emission_color = color;
+                    material.emission_strength = if color.iter().any(|channel| *channel > 0.0) {
+                        1.0
+                    } else {
+                        0.0
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn parse_floats(arguments: &[&str], min: usize, max: usize) -> Result<Vec<f32>, String> {
+    if arguments.len() < min || arguments.len() > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(format!(
+            "expected {} numbers, found {}",
+            expected,
+            arguments.len()
+        ));
+    }
+
+    arguments
+        .iter()
+        .map(|argument| {
+            argument
+                .parse::<f32>()
+                .map_err(|_| format!("invalid number '{}'", argument))
+        })
+        .collect()
+}
+
+/// A color is either three channels or a single gray value.
+fn parse_color(arguments: &[&str]) -> Result<[f32; 3], String> {
+    let values = parse_floats(arguments, 1, 3)?;
+    match values.as_slice() {
+        [gray] => Ok([*gray; 3]),
+        [r, g, b] => Ok([*r, *g, *b]),
+        _ => Err(format!(
+            "expected 1 or 3 color channels, found {}",
+            values.len()
+        )),
+    }
+}
+
+/// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` face vertex into zero based indices.
+fn parse_face_vertex(
+    vertex: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let parts: Vec<&str> = vertex.split('/').collect();
+    if parts.len() > 3 {
+        return Err(format!("invalid face vertex '{}'", vertex));
+    }
+
+    let position = resolve_index(parts[0], position_count, "position")?;
+    let uv = match parts.get(1) {
+        Some(part) if !part.is_empty() => {
+            Some(resolve_index(part, uv_count, "texture coordinate")?)
+        }
+        _ => None,
+    };
+    let normal = match parts.get(2) {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, normal_count, "normal")?),
+        _ => None,
+    };
+
+    Ok((position, uv, normal))
+}
+
+/// OBJ indices start at 1, negative ones count back from the last element.
+fn resolve_index(index: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let value: i64 = index
+        .parse()
+        .map_err(|_| format!("invalid {} index '{}'", kind, index))?;
+
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{} index {} is out of range, there are {} so far",
+            kind, value, count
+        ));
+    }
+    Ok(resolved as usize)
+}
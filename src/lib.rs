@@ -1,26 +1,33 @@
-use crate::camera::camera_state::CameraState;
-use crate::pipelines::compute_pipeline::create_compute_pipeline;
+use crate::cli::args::Args;
 use crate::pipelines::render_pipeline::create_render_pipeline;
-use crate::types::globals::GlobalState;
-use crate::types::material::{Material, MaterialState};
-use std::thread::sleep;
+use crate::renderer::animation::{default_animation, render_animation};
+use crate::renderer::headless::HeadlessRenderer;
+use crate::renderer::raytracer::Raytracer;
+use crate::renderer::request_device;
+use crate::scene::scene_file::Scene;
+use crate::utils::save_gif;
 use types::vertex;
 use wgpu::util::DeviceExt;
-use wgpu::{
-    BindGroup, Buffer, Device, Gles3MinorVersion, RenderPipeline, Sampler, TextureFormat,
-    TextureView,
-};
-use winit::dpi::PhysicalSize;
+use wgpu::{BindGroup, Buffer, Device, Gles3MinorVersion, RenderPipeline, Sampler, TextureView};
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
 
-mod camera;
-mod pipelines;
-mod types;
-mod utils;
+pub mod acceleration;
+pub mod bsdf;
+pub mod camera;
+pub mod cli;
+pub mod import;
+pub mod pipelines;
+pub mod procedural;
+pub mod renderer;
+pub mod sampler;
+pub mod scene;
+pub mod sky;
+pub mod types;
+pub mod utils;
 
 struct State {
     surface: wgpu::Surface,
@@ -29,26 +36,17 @@ struct State {
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     window: Window,
-    // Globals
-    global_state: GlobalState,
-    // Materials
-    material_state: MaterialState,
-    // Spheres
-    sphere_state: types::sphere::SphereState,
-    // Camera
-    camera_state: CameraState,
     // Texture and Sampler
     vertex_buffer: Buffer,
     // Raytracing
-    rt_pipeline: wgpu::ComputePipeline,
-    rt_bind_group: wgpu::BindGroup,
+    raytracer: Raytracer,
     // Rendering
     render_pipeline: wgpu::RenderPipeline,
     render_bind_group: wgpu::BindGroup,
 }
 
 impl State {
-    async fn new(window: Window) -> Self {
+    async fn new(window: Window, scene: &Scene) -> Result<Self, failure::Error> {
         // Basic config
 
         let size = window.inner_size();
@@ -80,20 +78,7 @@ impl State {
             .await
             .unwrap();
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::union(
-                        wgpu::Features::default(),
-                        wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES, // | wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY,
-                    ),
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let (device, queue) = request_device(&adapter).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -115,87 +100,19 @@ impl State {
         };
         surface.configure(&device, &config);
 
-        // Texture
-        let rt_texture_view = Self::create_rt_texture_view(&size, &device);
-
         let sampler = Self::create_sampler(&device);
 
         // Vertex buffer for quad surface
         let vertex_buffer = Self::create_vertex_buffer(&device);
 
-        // Globals
-        let global_state = GlobalState::new(&device);
-
-        // Materials
-        let materials = vec![
-            Material {
-                color: [0.8, 0.0, 0.0],
-                emission_color: [0.0, 0.0, 0.0],
-                emission_strength: 0.0,
-            },
-            Material {
-                color: [1.0, 1.0, 1.0],
-                emission_color: [1.0, 1.0, 1.0],
-                emission_strength: 1.0,
-            },
-            Material {
-                color: [0.0, 0.0, 0.8],
-                emission_color: [0.0, 0.0, 0.0],
-                emission_strength: 0.0,
-            },
-            // ground
-            Material {
-                color: [0.4, 0.4, 0.4],
-                emission_color: [0.0, 0.0, 0.0],
-                emission_strength: 0.0,
-            },
-        ];
-        let material_state = MaterialState::new(&materials, &device);
-
-        // Spheres
-        let spheres = vec![
-            types::sphere::Sphere {
-                position: cgmath::Vector3::new(-3.0, 0.0, 0.0),
-                radius: 1.0,
-                material_id: 0,
-            },
-            types::sphere::Sphere {
-                position: cgmath::Vector3::new(0.0, 150.0, 100.0),
-                radius: 100.0,
-                material_id: 1,
-            },
-            types::sphere::Sphere {
-                position: cgmath::Vector3::new(3.0, 0.0, 0.0),
-                radius: 1.0,
-                material_id: 2,
-            },
-            types::sphere::Sphere {
-                position: cgmath::Vector3::new(0.0, -51.0, 3.0),
-                radius: 50.0,
-                material_id: 3,
-            },
-        ];
-
-        let sphere_state = types::sphere::SphereState::new(&spheres, &device);
-
-        // Camera
-        let camera_state = CameraState::new(&device, &config);
-
         // Raytracing
-        let (rt_pipeline, rt_bind_group) = create_compute_pipeline(
-            &device,
-            &rt_texture_view,
-            &global_state,
-            &camera_state,
-            &sphere_state,
-            &material_state,
-        );
+        let raytracer = Raytracer::new(&device, &queue, scene, size.width, size.height)?;
 
         // Rendering
         let (render_bind_group, render_pipeline) =
-            Self::create_render_pipeline(&device, &config, &rt_texture_view, &sampler);
+            Self::create_render_pipeline(&device, &config, &raytracer.rt_texture_view, &sampler);
 
-        Self {
+        Ok(Self {
             window,
             surface,
             device,
@@ -203,15 +120,10 @@ impl State {
             config,
             size,
             vertex_buffer,
-            global_state,
-            material_state,
-            sphere_state,
-            camera_state,
-            rt_pipeline,
-            rt_bind_group,
+            raytracer,
             render_pipeline,
             render_bind_group,
-        }
+        })
     }
 
     fn create_render_pipeline(
@@ -249,16 +161,16 @@ impl State {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&rt_texture_view),
+                    resource: wgpu::BindingResource::TextureView(rt_texture_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
             ],
         });
 
-        let render_pipeline = create_render_pipeline(&config, &device, &render_bind_group_layout);
+        let render_pipeline = create_render_pipeline(config, device, &render_bind_group_layout);
         (render_bind_group, render_pipeline)
     }
 
@@ -278,36 +190,7 @@ impl State {
             border_color: None,
         };
 
-        let sampler = device.create_sampler(&sampler_desc);
-        sampler
-    }
-
-    fn create_rt_texture_view(size: &PhysicalSize<u32>, device: &Device) -> TextureView {
-        // Texture and Sampler
-        let mut texture = device.create_texture(&wgpu::TextureDescriptor {
-            size: wgpu::Extent3d {
-                width: size.width,
-                height: size.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1, // We'll talk about this a little later
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            // Most images are stored using sRGB so we need to reflect that here.
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::STORAGE_BINDING,
-            // | wgpu::TextureUsages::RENDER_ATTACHMENT,
-            label: Some("diffuse_texture"),
-            view_formats: &[],
-        });
-        let rt_texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
-            format: Some(wgpu::TextureFormat::Rgba8Unorm),
-            ..Default::default()
-        });
-
-        rt_texture_view
+        device.create_sampler(&sampler_desc)
     }
 
     fn create_vertex_buffer(device: &Device) -> Buffer {
@@ -340,12 +223,11 @@ impl State {
             },
         ];
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
             contents: bytemuck::cast_slice(&vertices),
             usage: wgpu::BufferUsages::VERTEX,
-        });
-        vertex_buffer
+        })
     }
 
     pub fn window(&self) -> &Window {
@@ -359,29 +241,17 @@ impl State {
             self.config.width = new_size.width;
             self.surface.configure(&self.device, &self.config);
 
-            // camera
-            self.camera_state.object.aspect = self.config.width as f32 / self.config.height as f32;
-
             /* recreate pipelines because of the new size */
             // Compute
-            let rt_texture_view = Self::create_rt_texture_view(&new_size, &self.device);
-            let (rt_pipeline, rt_bind_group) = create_compute_pipeline(
-                &self.device,
-                &rt_texture_view,
-                &self.global_state,
-                &self.camera_state,
-                &self.sphere_state,
-                &self.material_state,
-            );
-            self.rt_pipeline = rt_pipeline;
-            self.rt_bind_group = rt_bind_group;
+            self.raytracer
+                .resize(&self.device, new_size.width, new_size.height);
 
             // Rendering
             let sampler = Self::create_sampler(&self.device);
             let (render_bind_group, render_pipeline) = Self::create_render_pipeline(
                 &self.device,
                 &self.config,
-                &rt_texture_view,
+                &self.raytracer.rt_texture_view,
                 &sampler,
             );
             self.render_bind_group = render_bind_group;
@@ -390,14 +260,79 @@ impl State {
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
-        self.camera_state.controller.process_events(event)
+        // Tab cycles through the samplers to compare their convergence
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Tab),
+                    ..
+                },
+            ..
+        } = event
+        {
+            let global_state = &mut self.raytracer.global_state;
+            global_state.set_sampler_kind(global_state.sampler_kind().next());
+            log::info!("Sampler: {}", global_state.sampler_kind());
+            return true;
+        }
+        // R turns reservoir resampling of direct light on and off
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::R),
+                    ..
+                },
+            ..
+        } = event
+        {
+            let global_state = &mut self.raytracer.global_state;
+            global_state.set_restir(!global_state.restir());
+            log::info!("ReSTIR: {}", global_state.restir());
+            return true;
+        }
+        // - and + change the paths traced per pixel and frame, [ and ] how often they bounce
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode:
+                        Some(
+                            key @ (VirtualKeyCode::Minus
+                            | VirtualKeyCode::Equals
+                            | VirtualKeyCode::LBracket
+                            | VirtualKeyCode::RBracket),
+                        ),
+                    ..
+                },
+            ..
+        } = event
+        {
+            let global_state = &mut self.raytracer.global_state;
+            let mut settings = global_state.render_settings();
+            match key {
+                VirtualKeyCode::Minus => settings.samples_per_frame -= 1,
+                VirtualKeyCode::Equals => settings.samples_per_frame += 1,
+                VirtualKeyCode::LBracket => settings.max_bounces -= 1,
+                VirtualKeyCode::RBracket => settings.max_bounces += 1,
+                _ => unreachable!(),
+            }
+            global_state.set_render_settings(settings);
+            let settings = global_state.render_settings();
+            log::info!(
+                "Samples per frame: {}, bounces: {}",
+                settings.samples_per_frame,
+                settings.max_bounces
+            );
+            return true;
+        }
+
+        self.raytracer.camera_state.controller.process_events(event)
     }
 
     fn update(&mut self) {
-        // update global state
-        self.global_state.update(&self.queue);
-        // update camera
-        self.camera_state.update(&self.queue);
+        self.raytracer.update(&self.queue);
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -408,16 +343,7 @@ impl State {
             });
 
         // compute
-        {
-            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("My fancy compute pass"),
-                timestamp_writes: None,
-            });
-
-            cpass.set_pipeline(&self.rt_pipeline);
-            cpass.set_bind_group(0, &self.rt_bind_group, &[]);
-            cpass.dispatch_workgroups(self.config.width, self.config.height, 1);
-        }
+        self.raytracer.encode(&mut encoder);
 
         // render
         let output = self.surface.get_current_texture()?;
@@ -458,58 +384,125 @@ impl State {
 
         Ok(())
     }
+}
 
-    fn render_to_gif(&mut self) -> Result<(), wgpu::SurfaceError> {
-        // TODO: bullshit
-        Ok(())
+/// Renders the still image and/or animation requested on the command line.
+async fn render_offline(args: &Args, scene: &Scene) -> Result<(), failure::Error> {
+    let mut renderer =
+        HeadlessRenderer::new(scene, args.width, args.height, args.force_fallback_adapter).await?;
+    renderer
+        .raytracer
+        .global_state
+        .set_sampler_kind(args.sampler_kind);
+    renderer
+        .raytracer
+        .global_state
+        .set_render_settings(args.render_settings);
+
+    if let Some(output) = &args.output {
+        let samples = args.samples.unwrap_or(1);
+        renderer.render_samples(samples)?.save(output)?;
+    }
+
+    if let Some(gif) = &args.gif {
+        let mut animation = scene
+            .animation
+            .clone()
+            .unwrap_or_else(|| default_animation(&scene.camera()));
+        if let Some(frames) = args.frames {
+            animation.frames = frames;
+        }
+        if let Some(samples) = args.samples {
+            animation.samples = samples;
+        }
+        if let Some(delay) = args.delay {
+            animation.delay = delay;
+        }
+
+        let mut frames = render_animation(&mut renderer, scene, &animation)?;
+        save_gif(&gif.to_string_lossy(), &mut frames, 10, animation.delay)?;
     }
+
+    Ok(())
 }
 
 pub async fn run() {
-    // if GIF defined, render to GIF and exit
-    if std::env::var("GIF").is_ok() {
-        let event_loop = EventLoop::new();
-        let window = WindowBuilder::new().build(&event_loop).unwrap();
+    env_logger::init();
 
-        let mut state = State::new(window).await;
-        state.render_to_gif().unwrap();
-        return;
+    let mut args = Args::from_env();
+    // GIF=<path> is kept as a shorthand for --gif <path>
+    if let (None, Ok(path)) = (&args.gif, std::env::var("GIF")) {
+        args.gif = Some(if path.is_empty() {
+            "render.gif".into()
+        } else {
+            path.into()
+        });
     }
 
-    env_logger::init();
+    let scene = match &args.scene {
+        Some(path) => match Scene::load_any(path) {
+            Ok(scene) => scene,
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        },
+        None => Scene::default(),
+    };
+
+    // if an output image or GIF is requested, render without a window and exit
+    if args.output.is_some() || args.gif.is_some() {
+        if let Err(error) = render_offline(&args, &scene).await {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
 
-    let mut state = State::new(window).await;
+    let mut state = match State::new(window, &scene).await {
+        Ok(state) => state,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    state
+        .raytracer
+        .global_state
+        .set_sampler_kind(args.sampler_kind);
+    state
+        .raytracer
+        .global_state
+        .set_render_settings(args.render_settings);
+    // the preview reuses light samples across pixels and frames, offline renders don't
+    state.raytracer.global_state.set_restir(true);
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == state.window.id() => {
-            if !state.input(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
+        } if window_id == state.window.id() && !state.input(event) => match event {
+            WindowEvent::CloseRequested
+            | WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Escape),
                         ..
-                    } => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(physical_size) => {
-                        state.resize(*physical_size);
-                    }
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        state.resize(**new_inner_size);
-                    }
-                    _ => {}
-                }
+                    },
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            WindowEvent::Resized(physical_size) => {
+                state.resize(*physical_size);
             }
-        }
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                state.resize(**new_inner_size);
+            }
+            _ => {}
+        },
         Event::RedrawRequested(window_id) if window_id == state.window().id() => {
             state.update();
             match state.render() {
@@ -1,29 +1,48 @@
 use wgpu::{BindGroup, ComputePipeline};
 
 use crate::camera::camera_state::CameraState;
+use crate::sampler::blue_noise::BlueNoiseState;
+use crate::sampler::reservoir::ReservoirState;
+use crate::types::environment::EnvironmentState;
 use crate::types::globals::GlobalState;
+use crate::types::intersectable::{intersectable_wgsl, IntersectableState};
 use crate::types::material::MaterialState;
-use crate::types::sphere::SphereState;
+use crate::types::texture::TextureState;
 
-/// Creates a compute pipeline for the raytracer using the given device, raytracer bind group layout, and camera bind group layout.
+/// Resources bound to the raytracer compute shader.
+pub struct ComputeBindings<'a> {
+    pub rt_texture_view: &'a wgpu::TextureView,
+    /// Two accumulation textures, the previous average is read from one
+    /// while the new one is written to the other.
+    pub accumulation_texture_views: [&'a wgpu::TextureView; 2],
+    pub global_state: &'a GlobalState,
+    pub environment_state: &'a EnvironmentState,
+    pub camera_state: &'a CameraState,
+    pub intersectable_state: &'a IntersectableState,
+    pub material_state: &'a MaterialState,
+    pub blue_noise_state: &'a BlueNoiseState,
+    /// Reservoirs of the last frame are read from one half of the buffer
+    /// while the new ones are written to the other, like the accumulation
+    /// textures.
+    pub reservoir_state: &'a ReservoirState,
+    pub texture_state: &'a TextureState,
+}
+
+/// Creates the compute pipeline of the raytracer and the bind groups holding all of its resources.
 ///
 /// # Arguments
 ///
 /// * `device` - A reference to the `wgpu::Device` to use for creating the pipeline.
-/// * `rt_bind_group_layout` - A reference to the `wgpu::BindGroupLayout` for the raytracer.
-/// * `camera_bind_group_layout` - A reference to the `wgpu::BindGroupLayout` for the camera.
+/// * `bindings` - The textures and scene buffers to bind to the shader.
 ///
 /// # Returns
 ///
-/// The created `wgpu::ComputePipeline`.
+/// The created `wgpu::ComputePipeline` and two `wgpu::BindGroup`s. Bind group `i` reads the
+/// accumulated image from accumulation texture `i` and writes the new average to the other one.
 pub fn create_compute_pipeline(
     device: &wgpu::Device,
-    rt_texture_view: &wgpu::TextureView,
-    global_state: &GlobalState,
-    camera_state: &CameraState,
-    sphere_state: &SphereState,
-    material_state: &MaterialState,
-) -> (ComputePipeline, BindGroup) {
+    bindings: &ComputeBindings,
+) -> (ComputePipeline, [BindGroup; 2]) {
     // Bind Group
     let rt_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("My fancy compute bindings"),
@@ -61,7 +80,7 @@ pub fn create_compute_pipeline(
                 },
                 count: None,
             },
-            // Spheres
+            // Primitives
             wgpu::BindGroupLayoutEntry {
                 binding: 3,
                 visibility: wgpu::ShaderStages::COMPUTE,
@@ -103,51 +122,338 @@ pub fn create_compute_pipeline(
                 },
                 count: None,
             },
-        ],
-    });
-
-    let rt_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("My fancy compute bind group"),
-        layout: &rt_bind_group_layout,
-        entries: &[
-            // Binding 0: raytracer output
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(rt_texture_view),
+            // Environment
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
             },
-            // Binding 1: globals
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: global_state.buffer.as_entire_binding(),
+            // Accumulation textures, averaging samples over several frames
+            wgpu::BindGroupLayoutEntry {
+                binding: 8,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
             },
-            // Binding 2: camera
-            wgpu::BindGroupEntry {
-                binding: 2,
-                resource: camera_state.buffer.as_entire_binding(),
+            wgpu::BindGroupLayoutEntry {
+                binding: 9,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    format: wgpu::TextureFormat::Rgba32Float,
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                },
+                count: None,
             },
-            // Binding 3: sphere metadata
-            wgpu::BindGroupEntry {
-                binding: 3,
-                resource: sphere_state.metadata_buffer.as_entire_binding(),
+            // Blue noise
+            wgpu::BindGroupLayoutEntry {
+                binding: 10,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
             },
-            // Binding 4: sphere buffer
-            wgpu::BindGroupEntry {
-                binding: 4,
-                resource: sphere_state.buffer.as_entire_binding(),
+            // Primitive BVH
+            wgpu::BindGroupLayoutEntry {
+                binding: 11,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
             },
-            // Binding 5: material metadata
-            wgpu::BindGroupEntry {
-                binding: 5,
-                resource: material_state.metadata_buffer.as_entire_binding(),
+            // Primitive data, mesh vertices and the light table
+            wgpu::BindGroupLayoutEntry {
+                binding: 12,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
             },
-            // Binding 6: material buffer
-            wgpu::BindGroupEntry {
-                binding: 6,
-                resource: material_state.buffer.as_entire_binding(),
+            // Instances
+            wgpu::BindGroupLayoutEntry {
+                binding: 14,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // Material textures of up to 256 pixels, the larger ones are at 25 to 27
+            wgpu::BindGroupLayoutEntry {
+                binding: 15,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 16,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            // Lights
+            wgpu::BindGroupLayoutEntry {
+                binding: 17,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // Environment map
+            wgpu::BindGroupLayoutEntry {
+                binding: 18,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            // Environment map distribution
+            wgpu::BindGroupLayoutEntry {
+                binding: 19,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // Reservoirs of the previous frame and of this one
+            wgpu::BindGroupLayoutEntry {
+                binding: 22,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // Previous camera
+            wgpu::BindGroupLayoutEntry {
+                binding: 24,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // Material textures of up to 512, 1024 and 2048 pixels
+            wgpu::BindGroupLayoutEntry {
+                binding: 25,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 26,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 27,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
             },
         ],
     });
 
+    let create_bind_group = |index: usize| {
+        let previous = bindings.accumulation_texture_views[index];
+        let current = bindings.accumulation_texture_views[1 - index];
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("My fancy compute bind group"),
+            layout: &rt_bind_group_layout,
+            entries: &[
+                // Binding 0: raytracer output
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(bindings.rt_texture_view),
+                },
+                // Binding 1: globals
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: bindings.global_state.buffer.as_entire_binding(),
+                },
+                // Binding 2: camera
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: bindings.camera_state.buffer.as_entire_binding(),
+                },
+                // Binding 3: primitive metadata
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: bindings
+                        .intersectable_state
+                        .metadata_buffer
+                        .as_entire_binding(),
+                },
+                // Binding 4: primitive buffer
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: bindings.intersectable_state.buffer.as_entire_binding(),
+                },
+                // Binding 5: material metadata
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: bindings.material_state.metadata_buffer.as_entire_binding(),
+                },
+                // Binding 6: material buffer
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: bindings.material_state.buffer.as_entire_binding(),
+                },
+                // Binding 7: environment
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: bindings.environment_state.buffer.as_entire_binding(),
+                },
+                // Binding 8: previous accumulated image
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(previous),
+                },
+                // Binding 9: new accumulated image
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::TextureView(current),
+                },
+                // Binding 10: blue noise
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: wgpu::BindingResource::TextureView(&bindings.blue_noise_state.view),
+                },
+                // Binding 11: primitive BVH nodes
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: bindings
+                        .intersectable_state
+                        .bvh_state
+                        .buffer
+                        .as_entire_binding(),
+                },
+                // Binding 12: primitive data, mesh vertices and the light table
+                wgpu::BindGroupEntry {
+                    binding: 12,
+                    resource: bindings.intersectable_state.data_buffer.as_entire_binding(),
+                },
+                // Binding 14: instances
+                wgpu::BindGroupEntry {
+                    binding: 14,
+                    resource: bindings
+                        .intersectable_state
+                        .instance_buffer
+                        .as_entire_binding(),
+                },
+                // Binding 15: material textures of up to 256 pixels
+                wgpu::BindGroupEntry {
+                    binding: 15,
+                    resource: wgpu::BindingResource::TextureView(&bindings.texture_state.views[0]),
+                },
+                // Binding 16: material texture sampler
+                wgpu::BindGroupEntry {
+                    binding: 16,
+                    resource: wgpu::BindingResource::Sampler(&bindings.texture_state.sampler),
+                },
+                // Binding 17: lights
+                wgpu::BindGroupEntry {
+                    binding: 17,
+                    resource: bindings
+                        .intersectable_state
+                        .light_buffer
+                        .as_entire_binding(),
+                },
+                // Binding 18: environment map
+                wgpu::BindGroupEntry {
+                    binding: 18,
+                    resource: wgpu::BindingResource::TextureView(
+                        &bindings.environment_state.map_view,
+                    ),
+                },
+                // Binding 19: environment map distribution
+                wgpu::BindGroupEntry {
+                    binding: 19,
+                    resource: bindings
+                        .environment_state
+                        .distribution_buffer
+                        .as_entire_binding(),
+                },
+                // Binding 22: reservoirs of the previous frame and of this one
+                wgpu::BindGroupEntry {
+                    binding: 22,
+                    resource: bindings.reservoir_state.buffer.as_entire_binding(),
+                },
+                // Binding 24: previous camera
+                wgpu::BindGroupEntry {
+                    binding: 24,
+                    resource: bindings.camera_state.previous_buffer.as_entire_binding(),
+                },
+                // Binding 25: material textures of up to 512 pixels
+                wgpu::BindGroupEntry {
+                    binding: 25,
+                    resource: wgpu::BindingResource::TextureView(&bindings.texture_state.views[1]),
+                },
+                // Binding 26: material textures of up to 1024 pixels
+                wgpu::BindGroupEntry {
+                    binding: 26,
+                    resource: wgpu::BindingResource::TextureView(&bindings.texture_state.views[2]),
+                },
+                // Binding 27: material textures of up to 2048 pixels
+                wgpu::BindGroupEntry {
+                    binding: 27,
+                    resource: wgpu::BindingResource::TextureView(&bindings.texture_state.views[3]),
+                },
+            ],
+        })
+    };
+    let rt_bind_groups = [create_bind_group(0), create_bind_group(1)];
+
     let rt_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("My fancy compute pipeline layout"),
         bind_group_layouts: &[&rt_bind_group_layout],
@@ -159,10 +465,27 @@ pub fn create_compute_pipeline(
         layout: Some(&rt_pipeline_layout),
         module: &device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("My fancy compute shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shader/raytracer.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(raytracer_shader_source().into()),
         }),
         entry_point: "main",
     });
 
-    (rt_pipeline, rt_bind_group)
+    (rt_pipeline, rt_bind_groups)
+}
+
+/// The raytracer shader with the material BSDF, the procedural textures, light
+/// and environment sampling, light resampling, the physical sky and the
+/// intersection code of all primitive types appended.
+pub fn raytracer_shader_source() -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+        include_str!("../shader/raytracer.wgsl"),
+        include_str!("../shader/bsdf.wgsl"),
+        include_str!("../shader/procedural.wgsl"),
+        include_str!("../shader/lights.wgsl"),
+        include_str!("../shader/restir.wgsl"),
+        include_str!("../shader/environment.wgsl"),
+        include_str!("../shader/sky.wgsl"),
+        intersectable_wgsl()
+    )
 }
@@ -14,7 +14,7 @@ use crate::types::vertex;
 ///
 /// # Example
 ///
-/// ```
+/// ```ignore
 /// let render_pipeline = create_render_pipeline(&config, &device, &render_bind_group_layout);
 /// ```
 pub fn create_render_pipeline(
@@ -29,11 +29,11 @@ pub fn create_render_pipeline(
 
     let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Render Pipeline Layout"),
-        bind_group_layouts: &[&render_bind_group_layout],
+        bind_group_layouts: &[render_bind_group_layout],
         push_constant_ranges: &[],
     });
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
         layout: Some(&render_pipeline_layout),
         vertex: wgpu::VertexState {
@@ -66,7 +66,5 @@ pub fn create_render_pipeline(
             alpha_to_coverage_enabled: false,
         },
         multiview: None,
-    });
-
-    render_pipeline
+    })
 }
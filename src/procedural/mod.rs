@@ -0,0 +1,166 @@
+//! Procedural textures, and a CPU implementation of `src/shader/procedural.wgsl`.
+//!
+//! A pattern maps a point on a surface to a value between 0 and 1 that blends
+//! the color of the material into the color of the texture. Checkers and
+//! gradients use the uvs of the surface, the others are solid textures of the
+//! position in object space, so they look carved out of the material.
+
+pub mod noise;
+
+use cgmath::{InnerSpace, Vector2, Vector3};
+use image::RgbaImage;
+use serde::Deserialize;
+
+use crate::bsdf::microfacet::mix;
+use crate::procedural::noise::{fbm, turbulence};
+
+/// Pattern id of materials without a procedural texture.
+pub const NO_PATTERN: u32 = 0;
+
+/// Largest number of noise octaves, bounds the loop in the shader.
+pub const MAX_OCTAVES: u32 = 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum Pattern {
+    /// Squares alternating along u and v.
+    Checker,
+    /// Cubes alternating in space.
+    SolidChecker,
+    /// Ramp from 0 to 1 along v, repeating.
+    Gradient,
+    /// Fractal Perlin noise.
+    Noise,
+    /// Veins along x, distorted by turbulence.
+    Marble,
+    /// Rings around the y axis, distorted by noise.
+    Wood,
+}
+
+impl Pattern {
+    /// Identifier of the pattern in the shader, `NO_PATTERN` is none.
+    pub fn id(self) -> u32 {
+        match self {
+            Pattern::Checker => 1,
+            Pattern::SolidChecker => 2,
+            Pattern::Gradient => 3,
+            Pattern::Noise => 4,
+            Pattern::Marble => 5,
+            Pattern::Wood => 6,
+        }
+    }
+}
+
+/// A pattern blending the color of a material into `color`.
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProceduralTexture {
+    pub pattern: Pattern,
+    pub color: [f32; 3],
+    /// Frequency of the pattern, checkers have `scale` squares per unit.
+    pub scale: f32,
+    /// Noise octaves, each adding detail at twice the frequency.
+    pub octaves: u32,
+    /// How much noise distorts marble veins and wood rings.
+    pub distortion: f32,
+}
+
+impl Default for ProceduralTexture {
+    fn default() -> Self {
+        Self {
+            pattern: Pattern::Checker,
+            color: [0.0, 0.0, 0.0],
+            scale: 1.0,
+            octaves: 4,
+            distortion: 1.0,
+        }
+    }
+}
+
+impl ProceduralTexture {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.scale.is_finite() || self.scale <= 0.0 {
+            return Err(format!(
+                "pattern scale {} must be finite and greater than 0",
+                self.scale
+            ));
+        }
+        if !(1..=MAX_OCTAVES).contains(&self.octaves) {
+            return Err(format!(
+                "pattern octaves {} must be between 1 and {}",
+                self.octaves, MAX_OCTAVES
+            ));
+        }
+        if !self.distortion.is_finite() {
+            return Err(format!(
+                "pattern distortion {} must be finite",
+                self.distortion
+            ));
+        }
+        Ok(())
+    }
+
+    /// Blend factor between the material color and `color` at a point with
+    /// object space `position` and `uv`, between 0 and 1.
+    pub fn value(&self, position: Vector3<f32>, uv: Vector2<f32>) -> f32 {
+        let p = position * self.scale;
+        let value = match self.pattern {
+            Pattern::Checker => {
+                let cell = (uv * self.scale).map(f32::floor);
+                checker(cell.x + cell.y)
+            }
+            Pattern::SolidChecker => checker(p.x.floor() + p.y.floor() + p.z.floor()),
+            Pattern::Gradient => (uv.y * self.scale).rem_euclid(1.0),
+            Pattern::Noise => 0.5 + 0.5 * fbm(p, self.octaves),
+            Pattern::Marble => {
+                let phase = p.x + self.distortion * 4.0 * turbulence(p, self.octaves);
+                0.5 + 0.5 * phase.sin()
+            }
+            Pattern::Wood => {
+                let rings = Vector2::new(p.x, p.z).magnitude()
+                    + self.distortion * 0.5 * fbm(p, self.octaves);
+                rings.rem_euclid(1.0)
+            }
+        };
+        value.clamp(0.0, 1.0)
+    }
+
+    /// Color of a material of `base_color` with this texture.
+    pub fn color(
+        &self,
+        base_color: Vector3<f32>,
+        position: Vector3<f32>,
+        uv: Vector2<f32>,
+    ) -> Vector3<f32> {
+        mix(base_color, self.color.into(), self.value(position, uv))
+    }
+
+    /// The texture on a unit square in the xz plane around the origin, with u
+    /// along x and v along z, in sRGB.
+    pub fn preview(&self, base_color: [f32; 3], width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let uv = Vector2::new(
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            );
+            let position = Vector3::new(uv.x - 0.5, 0.0, uv.y - 0.5);
+            let color = self.color(base_color.into(), position, uv);
+            let [r, g, b] = [color.x, color.y, color.z].map(linear_to_srgb);
+            image::Rgba([r, g, b, 255])
+        })
+    }
+}
+
+/// 0 for even and 1 for odd sums of cell coordinates.
+fn checker(sum: f32) -> f32 {
+    sum.rem_euclid(2.0)
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
@@ -0,0 +1,100 @@
+use cgmath::Vector3;
+
+/// Hash of a lattice point (Jarzynski and Olano 2020, "Hash Functions for
+/// GPU Rendering"). Wrapping like WGSL's u32 arithmetic.
+pub fn pcg3d(x: i32, y: i32, z: i32) -> u32 {
+    let mut v = [x as u32, y as u32, z as u32]
+        .map(|value| value.wrapping_mul(1664525).wrapping_add(1013904223));
+
+    v[0] = v[0].wrapping_add(v[1].wrapping_mul(v[2]));
+    v[1] = v[1].wrapping_add(v[2].wrapping_mul(v[0]));
+    v[2] = v[2].wrapping_add(v[0].wrapping_mul(v[1]));
+    v = v.map(|value| value ^ (value >> 16));
+    v[0] = v[0].wrapping_add(v[1].wrapping_mul(v[2]));
+    v[0]
+}
+
+/// Dot product of the offset to a lattice point with one of the twelve edge
+/// directions of a cube picked by `hash`, as in Perlin's improved noise.
+fn gradient(hash: u32, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Perlin's improved gradient noise (Perlin 2002, "Improving Noise"), about
+/// between -1 and 1 and zero on the integer lattice.
+pub fn perlin(p: Vector3<f32>) -> f32 {
+    let cell = Vector3::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let (x, y, z) = (p.x - cell.x, p.y - cell.y, p.z - cell.z);
+    let (i, j, k) = (cell.x as i32, cell.y as i32, cell.z as i32);
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        gradient(
+            pcg3d(i + dx, j + dy, k + dz),
+            x - dx as f32,
+            y - dy as f32,
+            z - dz as f32,
+        )
+    };
+
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        ),
+        w,
+    )
+}
+
+/// Fractal Brownian motion, octaves of noise with doubling frequency and
+/// halving amplitude, normalized to the range of a single octave.
+pub fn fbm(p: Vector3<f32>, octaves: u32) -> f32 {
+    octaves_sum(p, octaves, perlin)
+}
+
+/// Like `fbm` but adds up the absolute values, between 0 and about 1.
+pub fn turbulence(p: Vector3<f32>, octaves: u32) -> f32 {
+    octaves_sum(p, octaves, |p| perlin(p).abs())
+}
+
+fn octaves_sum(p: Vector3<f32>, octaves: u32, noise: impl Fn(Vector3<f32>) -> f32) -> f32 {
+    let mut sum = 0.0;
+    let mut total_amplitude = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    for _ in 0..octaves {
+        sum += amplitude * noise(p * frequency);
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    if total_amplitude == 0.0 {
+        return 0.0;
+    }
+    sum / total_amplitude
+}
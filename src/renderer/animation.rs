@@ -0,0 +1,54 @@
+use image::RgbaImage;
+
+use crate::camera::camera_path::CameraPath;
+use crate::renderer::headless::HeadlessRenderer;
+use crate::scene::scene_file::{AnimationDescription, CameraDescription, Scene};
+
+/// Turntable around the target of `camera`, keeping its distance and height.
+pub fn default_animation(camera: &CameraDescription) -> AnimationDescription {
+    let offset = [
+        camera.eye[0] - camera.target[0],
+        camera.eye[1] - camera.target[1],
+        camera.eye[2] - camera.target[2],
+    ];
+
+    AnimationDescription {
+        path: CameraPath::Turntable {
+            target: camera.target,
+            radius: (offset[0] * offset[0] + offset[2] * offset[2]).sqrt(),
+            height: offset[1],
+            revolutions: 1.0,
+        },
+        frames: 36,
+        samples: 1,
+        delay: 4,
+        time_of_day: None,
+    }
+}
+
+/// Renders every frame of `animation` with the renderer's resolution, stopping
+/// at the first frame that can't be read back.
+pub fn render_animation(
+    renderer: &mut HeadlessRenderer,
+    scene: &Scene,
+    animation: &AnimationDescription,
+) -> Result<Vec<RgbaImage>, failure::Error> {
+    let base = scene.camera();
+    let aspect = renderer.raytracer.width as f32 / renderer.raytracer.height as f32;
+
+    (0..animation.frames)
+        .map(|frame| {
+            let t = animation.path.frame_time(frame, animation.frames);
+            let camera = animation.path.camera_at(t, &base).to_camera(aspect);
+            renderer.set_camera(camera);
+            if let Some((start, end)) = animation.time_of_day {
+                // Ends on the last hour like keyframes
+                let t = frame as f32 / (animation.frames.max(2) - 1) as f32;
+                renderer.set_time_of_day(start + (end - start) * t);
+            }
+
+            log::info!("Rendering frame {}/{}", frame + 1, animation.frames);
+            renderer.render_samples(animation.samples)
+        })
+        .collect()
+}
@@ -0,0 +1,160 @@
+use image::RgbaImage;
+use wgpu::{Device, Queue};
+
+use crate::camera::main_camera::Camera;
+use crate::renderer::raytracer::Raytracer;
+use crate::renderer::request_device;
+use crate::scene::scene_file::Scene;
+
+/// Bytes per pixel of `RT_TEXTURE_FORMAT`.
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Renders scenes into images without a window or surface.
+///
+/// With `force_fallback_adapter` set this also works on machines without a
+/// GPU, using a software adapter such as llvmpipe or lavapipe.
+pub struct HeadlessRenderer {
+    pub device: Device,
+    pub queue: Queue,
+    pub raytracer: Raytracer,
+}
+
+impl HeadlessRenderer {
+    pub async fn new(
+        scene: &Scene,
+        width: u32,
+        height: u32,
+        force_fallback_adapter: bool,
+    ) -> Result<Self, failure::Error> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter,
+            })
+            .await
+            .ok_or_else(|| failure::err_msg("no suitable graphics adapter found"))?;
+
+        let (device, queue) = request_device(&adapter).await?;
+
+        let raytracer = Raytracer::new(&device, &queue, scene, width, height)?;
+
+        Ok(Self {
+            device,
+            queue,
+            raytracer,
+        })
+    }
+
+    /// Moves the camera for the following renders.
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.raytracer.camera_state.object = camera;
+    }
+
+    /// Moves the sun of skies that follow the time of day to `hours` after
+    /// midnight for the following renders.
+    pub fn set_time_of_day(&mut self, hours: f32) {
+        self.raytracer.set_time_of_day(&self.queue, hours);
+    }
+
+    /// Traces `samples` frames, accumulated on the GPU, and reads back their
+    /// average. Fails if the image can't be read back, e.g. after the device
+    /// was lost.
+    pub fn render_samples(&mut self, samples: u32) -> Result<RgbaImage, failure::Error> {
+        self.raytracer.reset_accumulation();
+
+        for _ in 0..samples.max(1) {
+            self.raytracer.update(&self.queue);
+
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Headless encoder"),
+                });
+            self.raytracer.encode(&mut encoder);
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+
+        self.read_image()
+    }
+
+    /// Traces one frame and reads it back from the GPU.
+    pub fn render(&mut self) -> Result<RgbaImage, failure::Error> {
+        self.render_samples(1)
+    }
+
+    /// Copies the output texture into a mappable buffer and reads it back.
+    fn read_image(&self) -> Result<RgbaImage, failure::Error> {
+        let width = self.raytracer.width;
+        let height = self.raytracer.height;
+
+        // Rows of a texture copy have to be aligned to COPY_BYTES_PER_ROW_ALIGNMENT
+        let unpadded_bytes_per_row = width * BYTES_PER_PIXEL;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Output Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Headless readback encoder"),
+            });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.raytracer.rt_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &output_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = output_buffer.slice(..);
+        // The callback runs inside wgpu, so errors are passed out instead of panicking there
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|_| failure::err_msg("the output buffer was never mapped"))??;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let data = buffer_slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        output_buffer.unmap();
+
+        Ok(RgbaImage::from_raw(width, height, pixels).expect("buffer matches image size"))
+    }
+}
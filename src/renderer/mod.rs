@@ -0,0 +1,23 @@
+pub mod animation;
+pub mod headless;
+pub mod raytracer;
+
+/// Requests a device with the features the raytracer needs from `adapter`.
+/// The default limits hold the raytracer's 8 storage buffers per shader stage.
+pub async fn request_device(
+    adapter: &wgpu::Adapter,
+) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::union(
+                    wgpu::Features::default(),
+                    wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES, // | wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY,
+                ),
+                limits: wgpu::Limits::default(),
+                label: None,
+            },
+            None,
+        )
+        .await
+}
@@ -0,0 +1,279 @@
+use wgpu::{Device, Queue, Texture, TextureView};
+
+use crate::camera::camera_state::CameraState;
+use crate::pipelines::compute_pipeline::{create_compute_pipeline, ComputeBindings};
+use crate::sampler::blue_noise::BlueNoiseState;
+use crate::sampler::reservoir::ReservoirState;
+use crate::scene::scene_error::SceneError;
+use crate::scene::scene_file::Scene;
+use crate::types::environment::EnvironmentState;
+use crate::types::globals::GlobalState;
+use crate::types::intersectable::IntersectableState;
+use crate::types::material::MaterialState;
+use crate::types::texture::TextureState;
+
+/// Format of the texture the compute shader writes the final image to.
+pub const RT_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Format of the textures that average samples over several frames.
+pub const ACCUMULATION_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+/// Scene buffers, output texture and compute pipeline of the raytracer.
+///
+/// This is independent of any window or surface, so it is shared by the
+/// interactive `State` and the `HeadlessRenderer`.
+pub struct Raytracer {
+    pub width: u32,
+    pub height: u32,
+    // Globals
+    pub global_state: GlobalState,
+    // Environment
+    pub environment_state: EnvironmentState,
+    // Materials
+    pub material_state: MaterialState,
+    pub texture_state: TextureState,
+    // Primitives
+    pub intersectable_state: IntersectableState,
+    // Camera
+    pub camera_state: CameraState,
+    // Sampling
+    pub blue_noise_state: BlueNoiseState,
+    pub reservoir_state: ReservoirState,
+    // Output
+    pub rt_texture: Texture,
+    pub rt_texture_view: TextureView,
+    pub accumulation_textures: [Texture; 2],
+    pub accumulation_texture_views: [TextureView; 2],
+    // Raytracing
+    pub rt_pipeline: wgpu::ComputePipeline,
+    /// Bind group `i` reads accumulation texture `i` and writes the other one,
+    /// they alternate every frame.
+    pub rt_bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl Raytracer {
+    /// Fails when the scene needs more of a resource than the device has.
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        scene: &Scene,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, SceneError> {
+        // Texture
+        let (rt_texture, rt_texture_view) = Self::create_rt_texture(device, width, height);
+        let (accumulation_textures, accumulation_texture_views) =
+            Self::create_accumulation_textures(device, width, height);
+
+        // Globals
+        let mut global_state = GlobalState::new(device);
+        if let Some(sky) = &scene.environment.sky {
+            global_state.set_time_of_day(sky.hours());
+        }
+
+        // Environment
+        let environment_state = EnvironmentState::new(
+            &scene.environment,
+            scene.environment_map.as_ref(),
+            device,
+            queue,
+        );
+
+        // Materials
+        let material_state = MaterialState::new(&scene.materials, device);
+        let texture_state = TextureState::new(&scene.textures, device, queue)?;
+
+        // Primitives
+        let intersectable_state = IntersectableState::new(&scene.intersectables(), device);
+
+        // Camera
+        let camera = scene.camera().to_camera(width as f32 / height as f32);
+        let camera_state = CameraState::new(device, camera);
+
+        // Sampling
+        let blue_noise_state = BlueNoiseState::new(device, queue);
+        let reservoir_state = ReservoirState::new(device, width, height);
+
+        // Raytracing
+        let (rt_pipeline, rt_bind_groups) = create_compute_pipeline(
+            device,
+            &ComputeBindings {
+                rt_texture_view: &rt_texture_view,
+                accumulation_texture_views: [
+                    &accumulation_texture_views[0],
+                    &accumulation_texture_views[1],
+                ],
+                global_state: &global_state,
+                environment_state: &environment_state,
+                camera_state: &camera_state,
+                intersectable_state: &intersectable_state,
+                material_state: &material_state,
+                blue_noise_state: &blue_noise_state,
+                reservoir_state: &reservoir_state,
+                texture_state: &texture_state,
+            },
+        );
+
+        Ok(Self {
+            width,
+            height,
+            global_state,
+            environment_state,
+            material_state,
+            texture_state,
+            intersectable_state,
+            camera_state,
+            blue_noise_state,
+            reservoir_state,
+            rt_texture,
+            rt_texture_view,
+            accumulation_textures,
+            accumulation_texture_views,
+            rt_pipeline,
+            rt_bind_groups,
+        })
+    }
+
+    fn create_rt_texture(device: &Device, width: u32, height: u32) -> (Texture, TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: RT_TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::STORAGE_BINDING,
+            label: Some("diffuse_texture"),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(RT_TEXTURE_FORMAT),
+            ..Default::default()
+        });
+
+        (texture, view)
+    }
+
+    fn create_accumulation_textures(
+        device: &Device,
+        width: u32,
+        height: u32,
+    ) -> ([Texture; 2], [TextureView; 2]) {
+        let create_texture = || {
+            device.create_texture(&wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: ACCUMULATION_TEXTURE_FORMAT,
+                usage: wgpu::TextureUsages::STORAGE_BINDING
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::COPY_DST,
+                label: Some("accumulation_texture"),
+                view_formats: &[],
+            })
+        };
+        let textures = [create_texture(), create_texture()];
+        let views = [
+            textures[0].create_view(&wgpu::TextureViewDescriptor::default()),
+            textures[1].create_view(&wgpu::TextureViewDescriptor::default()),
+        ];
+
+        (textures, views)
+    }
+
+    /// Recreates the output texture and the pipeline for a new size.
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+
+        // camera
+        self.camera_state.object.aspect = width as f32 / height as f32;
+
+        let (rt_texture, rt_texture_view) = Self::create_rt_texture(device, width, height);
+        let (accumulation_textures, accumulation_texture_views) =
+            Self::create_accumulation_textures(device, width, height);
+        // reservoirs are per pixel, the old ones are dropped
+        let reservoir_state = ReservoirState::new(device, width, height);
+        let (rt_pipeline, rt_bind_groups) = create_compute_pipeline(
+            device,
+            &ComputeBindings {
+                rt_texture_view: &rt_texture_view,
+                accumulation_texture_views: [
+                    &accumulation_texture_views[0],
+                    &accumulation_texture_views[1],
+                ],
+                global_state: &self.global_state,
+                environment_state: &self.environment_state,
+                camera_state: &self.camera_state,
+                intersectable_state: &self.intersectable_state,
+                material_state: &self.material_state,
+                blue_noise_state: &self.blue_noise_state,
+                reservoir_state: &reservoir_state,
+                texture_state: &self.texture_state,
+            },
+        );
+        self.rt_texture = rt_texture;
+        self.rt_texture_view = rt_texture_view;
+        self.accumulation_textures = accumulation_textures;
+        self.accumulation_texture_views = accumulation_texture_views;
+        self.reservoir_state = reservoir_state;
+        self.rt_pipeline = rt_pipeline;
+        self.rt_bind_groups = rt_bind_groups;
+
+        // the new accumulation texture holds no samples yet
+        self.reset_accumulation();
+    }
+
+    /// Moves the sun of skies that follow the time of day to `hours` after
+    /// midnight, and picks the sky as a light as often as it is bright then.
+    pub fn set_time_of_day(&mut self, queue: &Queue, hours: f32) {
+        self.global_state.set_time_of_day(hours);
+        let hours = self.global_state.uniform.time_of_day;
+        let environment = &self.environment_state.environment;
+        if environment.sky.is_some() {
+            let power = environment.power(None, self.intersectable_state.scene_radius, hours);
+            self.intersectable_state
+                .write_light_table(queue, Some(power));
+        }
+    }
+
+    /// Restarts progressive accumulation. Has to be called whenever the
+    /// camera or any of the scene buffers change.
+    pub fn reset_accumulation(&mut self) {
+        self.global_state.reset_accumulation();
+    }
+
+    pub fn update(&mut self, queue: &Queue) {
+        // update camera
+        if self.camera_state.update(queue) {
+            self.reset_accumulation();
+        }
+        // update global state
+        self.global_state.update(queue);
+    }
+
+    /// Records the compute pass that traces the image into `rt_texture`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("My fancy compute pass"),
+            timestamp_writes: None,
+        });
+
+        cpass.set_pipeline(&self.rt_pipeline);
+        let accumulation_index = (self.global_state.uniform.timestamp % 2) as usize;
+        cpass.set_bind_group(0, &self.rt_bind_groups[accumulation_index], &[]);
+        cpass.dispatch_workgroups(self.width, self.height, 1);
+    }
+}
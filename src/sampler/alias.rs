@@ -0,0 +1,97 @@
+/// One slot of an alias table. A slot is picked uniformly, then keeps its own
+/// item with `probability` and switches to item `alias` otherwise.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct AliasEntry {
+    pub probability: f32,
+    pub alias: u32,
+    /// Probability of picking the item of the same index in the end.
+    pub pmf: f32,
+}
+
+/// Discrete distribution over items proportional to their weights, sampled
+/// in constant time with Walker's alias method, built as in Vose 1991, "A
+/// Linear Algorithm for Generating Random Numbers with a Given Distribution".
+/// The same lookup is done in `lights.wgsl`.
+#[derive(Debug, Clone, Default)]
+pub struct AliasTable {
+    pub entries: Vec<AliasEntry>,
+}
+
+impl AliasTable {
+    /// Table proportional to `weights`, uniform if they are all zero.
+    /// Negative and non-finite weights count as zero.
+    pub fn new(weights: &[f32]) -> Self {
+        let count = weights.len();
+        let weights: Vec<f64> = weights
+            .iter()
+            .map(|weight| {
+                if weight.is_finite() {
+                    weight.max(0.0) as f64
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let pmf: Vec<f64> = if total > 0.0 {
+            weights.iter().map(|weight| weight / total).collect()
+        } else {
+            vec![1.0 / count as f64; count]
+        };
+
+        let mut entries: Vec<AliasEntry> = pmf
+            .iter()
+            .enumerate()
+            .map(|(index, pmf)| AliasEntry {
+                probability: 1.0,
+                alias: index as u32,
+                pmf: *pmf as f32,
+            })
+            .collect();
+
+        // Scaled to an average of 1, every slot below it is filled up by one above it
+        let mut scaled: Vec<f64> = pmf.iter().map(|pmf| pmf * count as f64).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..count).partition(|index| scaled[*index] < 1.0);
+        while let (Some(&less), Some(&more)) = (small.last(), large.last()) {
+            small.pop();
+            entries[less].probability = scaled[less] as f32;
+            entries[less].alias = more as u32;
+            scaled[more] -= 1.0 - scaled[less];
+            if scaled[more] < 1.0 {
+                large.pop();
+                small.push(more);
+            }
+        }
+        // The slots left over are full up to rounding and keep their item
+
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Item for a uniform `u` in [0, 1). The integer part of `u` times the
+    /// number of slots picks the slot, the fraction decides between its item
+    /// and the alias.
+    pub fn sample(&self, u: f32) -> usize {
+        let scaled = u * self.len() as f32;
+        let slot = (scaled as usize).min(self.len() - 1);
+        let entry = self.entries[slot];
+        if scaled - (slot as f32) < entry.probability {
+            slot
+        } else {
+            entry.alias as usize
+        }
+    }
+
+    pub fn pmf(&self, index: usize) -> f32 {
+        self.entries[index].pmf
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::OnceLock;
+
+use wgpu::util::DeviceExt;
+
+/// Width and height of the tiling blue noise texture.
+pub const BLUE_NOISE_SIZE: usize = 64;
+
+/// Standard deviation of the gaussian used to measure clustering.
+const SIGMA: f32 = 1.5;
+
+/// The tile the shader samples from. Generating it takes a while, so it is
+/// generated once per process and shared by every renderer.
+pub fn blue_noise_tile() -> &'static [f32] {
+    static TILE: OnceLock<Vec<f32>> = OnceLock::new();
+    TILE.get_or_init(|| generate_blue_noise(BLUE_NOISE_SIZE, 0x9e3779b9))
+}
+
+/// Generates a tileable blue noise texture with the void-and-cluster method.
+///
+/// Every value in `[0, 1)` appears exactly once, pixels with similar values are
+/// spread as far apart as possible.
+pub fn generate_blue_noise(size: usize, seed: u32) -> Vec<f32> {
+    let count = size * size;
+    let kernel = gaussian_kernel(size);
+
+    // Initial binary pattern with ~10% of the pixels set, at least one so
+    // that small tiles have a cluster to start from
+    let initial = if count == 0 { 0 } else { (count / 10).max(1) };
+    let mut rng = seed.max(1);
+    let mut pattern = vec![false; count];
+    let mut ones = 0;
+    while ones < initial {
+        let index = xorshift(&mut rng) as usize % count;
+        if !pattern[index] {
+            pattern[index] = true;
+            ones += 1;
+        }
+    }
+
+    let mut energy = vec![0.0; count];
+    for (index, _) in pattern.iter().enumerate().filter(|(_, set)| **set) {
+        splat(&mut energy, &kernel, size, index, 1.0);
+    }
+
+    // Move points from the tightest cluster to the largest void until stable
+    for _ in 0..count {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        splat(&mut energy, &kernel, size, cluster, -1.0);
+
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, &kernel, size, void, 1.0);
+
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+
+    // Phase 1: rank the initial points by removing the tightest cluster
+    {
+        let mut pattern = pattern.clone();
+        let mut energy = energy.clone();
+        for rank in (0..ones).rev() {
+            let cluster = tightest_cluster(&pattern, &energy);
+            pattern[cluster] = false;
+            splat(&mut energy, &kernel, size, cluster, -1.0);
+            ranks[cluster] = rank;
+        }
+    }
+
+    // Phase 2 and 3: fill the largest void until every pixel is ranked
+    for rank in ones..count {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, &kernel, size, void, 1.0);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .iter()
+        .map(|rank| (*rank as f32 + 0.5) / count as f32)
+        .collect()
+}
+
+/// Toroidally wrapped gaussian, indexed by the offset between two pixels.
+fn gaussian_kernel(size: usize) -> Vec<f32> {
+    let mut kernel = vec![0.0; size * size];
+    for y in 0..size {
+        for x in 0..size {
+            let dx = x.min(size - x) as f32;
+            let dy = y.min(size - y) as f32;
+            kernel[y * size + x] = (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp();
+        }
+    }
+    kernel
+}
+
+/// Adds the kernel centered at `index` to the energy of every pixel.
+fn splat(energy: &mut [f32], kernel: &[f32], size: usize, index: usize, sign: f32) {
+    let (cx, cy) = (index % size, index / size);
+    for y in 0..size {
+        let ky = (y + size - cy) % size;
+        for x in 0..size {
+            let kx = (x + size - cx) % size;
+            energy[y * size + x] += sign * kernel[ky * size + kx];
+        }
+    }
+}
+
+fn tightest_cluster(pattern: &[bool], energy: &[f32]) -> usize {
+    (0..pattern.len())
+        .filter(|index| pattern[*index])
+        .max_by(|a, b| energy[*a].total_cmp(&energy[*b]))
+        .expect("pattern has points")
+}
+
+fn largest_void(pattern: &[bool], energy: &[f32]) -> usize {
+    (0..pattern.len())
+        .filter(|index| !pattern[*index])
+        .min_by(|a, b| energy[*a].total_cmp(&energy[*b]))
+        .expect("pattern has voids")
+}
+
+fn xorshift(state: &mut u32) -> u32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state
+}
+
+/// The blue noise tile as an `R32Float` texture, read without filtering.
+pub struct BlueNoiseState {
+    pub view: wgpu::TextureView,
+}
+
+impl BlueNoiseState {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Blue Noise Texture"),
+                size: wgpu::Extent3d {
+                    width: BLUE_NOISE_SIZE as u32,
+                    height: BLUE_NOISE_SIZE as u32,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            bytemuck::cast_slice(blue_noise_tile()),
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { view }
+    }
+}
@@ -0,0 +1,140 @@
+/// Piecewise constant distribution over [0, 1) with one bin per value of
+/// the function it is built from, sampled by inverting its cdf. The same
+/// inversion is done in `environment.wgsl`.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    /// `cdf[i]` is the probability of the bins before `i`, it has one more
+    /// entry than there are bins and ends with 1.
+    pub cdf: Vec<f32>,
+    /// Integral of the function over [0, 1).
+    pub integral: f32,
+}
+
+impl Distribution1D {
+    /// Distribution proportional to `function`, uniform if it is zero
+    /// everywhere. Negative and non-finite values count as zero.
+    pub fn new(function: &[f32]) -> Self {
+        let count = function.len() as f32;
+        let mut cdf = Vec::with_capacity(function.len() + 1);
+        let mut sum = 0.0;
+        cdf.push(0.0);
+        for value in function {
+            let value = if value.is_finite() {
+                value.max(0.0)
+            } else {
+                0.0
+            };
+            sum += value / count;
+            cdf.push(sum);
+        }
+
+        let integral = sum;
+        if integral > 0.0 {
+            for value in &mut cdf {
+                *value /= integral;
+            }
+        } else {
+            for (index, value) in cdf.iter_mut().enumerate() {
+                *value = index as f32 / count;
+            }
+        }
+        // Rounding may leave the last entry just below 1
+        *cdf.last_mut().unwrap() = 1.0;
+
+        Self { cdf, integral }
+    }
+
+    pub fn count(&self) -> usize {
+        self.cdf.len() - 1
+    }
+
+    /// Bin containing `u`, the last one whose cdf entry isn't above it.
+    /// Bins with zero probability are never picked.
+    pub fn find(&self, u: f32) -> usize {
+        let count = self.count();
+        self.cdf[..count]
+            .partition_point(|value| *value <= u)
+            .saturating_sub(1)
+    }
+
+    /// Point in [0, 1) for a uniform `u` and its density.
+    pub fn sample(&self, u: f32) -> (f32, f32) {
+        let bin = self.find(u);
+        let width = self.cdf[bin + 1] - self.cdf[bin];
+        let offset = if width > 0.0 {
+            ((u - self.cdf[bin]) / width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let count = self.count() as f32;
+        let x = ((bin as f32 + offset) / count).min(1.0 - f32::EPSILON);
+        (x, width * count)
+    }
+
+    /// Density of sampling `x`.
+    pub fn pdf(&self, x: f32) -> f32 {
+        let count = self.count();
+        let bin = ((x * count as f32) as usize).min(count - 1);
+        (self.cdf[bin + 1] - self.cdf[bin]) * count as f32
+    }
+}
+
+/// Piecewise constant distribution over [0, 1)² for an image, sampled by
+/// picking a row from the marginal distribution and then a column from the
+/// conditional distribution of that row (Pharr et al., "Physically Based
+/// Rendering", 13.6.7).
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    pub marginal: Distribution1D,
+    /// One distribution per row, from top to bottom.
+    pub conditionals: Vec<Distribution1D>,
+}
+
+impl Distribution2D {
+    /// Distribution proportional to the `width` by `height` values of
+    /// `function`, stored row by row.
+    pub fn new(function: &[f32], width: usize, height: usize) -> Self {
+        assert_eq!(function.len(), width * height);
+        let conditionals: Vec<Distribution1D> = function
+            .chunks_exact(width)
+            .map(Distribution1D::new)
+            .collect();
+        let row_integrals: Vec<f32> = conditionals.iter().map(|row| row.integral).collect();
+        Self {
+            marginal: Distribution1D::new(&row_integrals),
+            conditionals,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.conditionals[0].count()
+    }
+
+    pub fn height(&self) -> usize {
+        self.marginal.count()
+    }
+
+    /// Point in [0, 1)² for two uniform numbers and its density.
+    pub fn sample(&self, u: [f32; 2]) -> ([f32; 2], f32) {
+        let (y, row_pdf) = self.marginal.sample(u[1]);
+        let row = self.marginal.find(u[1]);
+        let (x, column_pdf) = self.conditionals[row].sample(u[0]);
+        ([x, y], row_pdf * column_pdf)
+    }
+
+    /// Density of sampling `point`.
+    pub fn pdf(&self, point: [f32; 2]) -> f32 {
+        let row = ((point[1] * self.height() as f32) as usize).min(self.height() - 1);
+        self.marginal.pdf(point[1]) * self.conditionals[row].pdf(point[0])
+    }
+
+    /// The cdfs as the shader reads them: the marginal cdf, followed by the
+    /// conditional cdf of every row.
+    pub fn to_buffer(&self) -> Vec<f32> {
+        let mut buffer = self.marginal.cdf.clone();
+        for row in &self.conditionals {
+            buffer.extend_from_slice(&row.cdf);
+        }
+        buffer
+    }
+}
@@ -0,0 +1,5 @@
+pub mod alias;
+pub mod blue_noise;
+pub mod distribution;
+pub mod reservoir;
+pub mod sampler_kind;
@@ -0,0 +1,44 @@
+/// Reservoir of one pixel for reservoir-based spatiotemporal importance
+/// resampling of direct light (Bitterli et al. 2020, "Spatiotemporal
+/// Reservoir Resampling for Real-Time Ray Tracing with Dynamic Direct
+/// Lighting"), see `restir.wgsl`. Empty reservoirs have a `count` of zero.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ReservoirUniform {
+    /// Point on the light kept, or the direction to it for directional
+    /// lights and the environment.
+    pub light_point: [f32; 3],
+    pub light_id: u32,
+    /// Primary hit the reservoir belongs to, reuse is only allowed between
+    /// hits on similar surfaces.
+    pub position: [f32; 3],
+    /// Number of candidates the reservoir has seen.
+    pub count: f32,
+    pub normal: [f32; 3],
+    /// Unbiased contribution weight of the sample.
+    pub weight: f32,
+}
+
+/// Reservoirs of every pixel of the last frame and the current one in two
+/// halves of one buffer, they swap every frame like the accumulation textures.
+pub struct ReservoirState {
+    pub buffer: wgpu::Buffer,
+}
+
+impl ReservoirState {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let size = 2
+            * width.max(1) as wgpu::BufferAddress
+            * height.max(1) as wgpu::BufferAddress
+            * std::mem::size_of::<ReservoirUniform>() as wgpu::BufferAddress;
+        // New buffers are zeroed, so every reservoir starts out empty
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reservoir Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        Self { buffer }
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Sequence the compute shader draws its samples from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[repr(u32)]
+pub enum SamplerKind {
+    /// Independent PCG random numbers with stratified pixel jitter.
+    #[default]
+    Random = 0,
+    /// Owen-scrambled Sobol sequence, padded per pair of dimensions.
+    Sobol = 1,
+    /// Blue noise tile, rotated over time with a golden ratio sequence.
+    BlueNoise = 2,
+}
+
+impl SamplerKind {
+    pub const ALL: [SamplerKind; 3] = [
+        SamplerKind::Random,
+        SamplerKind::Sobol,
+        SamplerKind::BlueNoise,
+    ];
+
+    /// The sampler after this one, wrapping around.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for SamplerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SamplerKind::Random => "random",
+            SamplerKind::Sobol => "sobol",
+            SamplerKind::BlueNoise => "blue-noise",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.to_string() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown sampler '{}', expected one of random, sobol, blue-noise",
+                    s
+                )
+            })
+    }
+}
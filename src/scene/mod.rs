@@ -0,0 +1,2 @@
+pub mod scene_error;
+pub mod scene_file;
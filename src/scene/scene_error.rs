@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::import::import_error::ImportError;
+
+/// Errors that can occur while loading a scene description.
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: String,
+        error: std::io::Error,
+    },
+    Parse(ron::error::SpannedError),
+    Import(ImportError),
+    UnsupportedModel {
+        path: String,
+    },
+    /// `object` names what references the material, e.g. `sphere 3`.
+    UnknownMaterial {
+        object: String,
+        material: String,
+    },
+    InvalidMaterial {
+        material: String,
+        reason: String,
+    },
+    Texture {
+        path: String,
+        error: image::ImageError,
+    },
+    /// More textures of up to `size` pixels than fit in a texture array.
+    TooManyTextures {
+        size: u32,
+        count: usize,
+        limit: u32,
+    },
+    EnvironmentMap {
+        path: String,
+        error: image::ImageError,
+    },
+    InvalidEnvironment {
+        reason: String,
+    },
+    InvalidRadius {
+        sphere: usize,
+        radius: f32,
+    },
+    InvalidMesh {
+        mesh: usize,
+        reason: String,
+    },
+    /// `object` names the shape, e.g. `quad 2`.
+    InvalidShape {
+        object: String,
+        reason: String,
+    },
+    /// An error in the primitives of an instanced object.
+    InObject {
+        object: String,
+        error: Box<SceneError>,
+    },
+    UnknownObject {
+        instance: usize,
+        object: String,
+    },
+    InvalidInstance {
+        instance: usize,
+        reason: String,
+    },
+    InvalidLight {
+        light: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, error } => {
+                write!(f, "could not read scene file '{}': {}", path, error)
+            }
+            SceneError::Parse(error) => write!(f, "could not parse scene: {}", error),
+            SceneError::Import(error) => write!(f, "could not import model: {}", error),
+            SceneError::UnsupportedModel { path } => {
+                write!(f, "model '{}' is not an .obj, .gltf or .glb file", path)
+            }
+            SceneError::UnknownMaterial { object, material } => {
+                write!(f, "{} references unknown material '{}'", object, material)
+            }
+            SceneError::InvalidRadius { sphere, radius } => write!(
+                f,
+                "sphere {} has invalid radius {} (must be finite and greater than 0)",
+                sphere, radius
+            ),
+            SceneError::InvalidMaterial { material, reason } => {
+                write!(f, "material '{}' is invalid: {}", material, reason)
+            }
+            SceneError::Texture { path, error } => {
+                write!(f, "could not load texture '{}': {}", path, error)
+            }
+            SceneError::TooManyTextures { size, count, limit } => write!(
+                f,
+                "scene has {} textures of up to {}x{} pixels, the graphics adapter supports {}",
+                count, size, size, limit
+            ),
+            SceneError::EnvironmentMap { path, error } => {
+                write!(f, "could not load environment map '{}': {}", path, error)
+            }
+            SceneError::InvalidEnvironment { reason } => {
+                write!(f, "environment is invalid: {}", reason)
+            }
+            SceneError::InvalidMesh { mesh, reason } => {
+                write!(f, "mesh {} is invalid: {}", mesh, reason)
+            }
+            SceneError::InvalidShape { object, reason } => {
+                write!(f, "{} is invalid: {}", object, reason)
+            }
+            SceneError::InObject { object, error } => write!(f, "object '{}': {}", object, error),
+            SceneError::UnknownObject { instance, object } => {
+                write!(
+                    f,
+                    "instance {} references unknown object '{}'",
+                    instance, object
+                )
+            }
+            SceneError::InvalidInstance { instance, reason } => {
+                write!(f, "instance {} is invalid: {}", instance, reason)
+            }
+            SceneError::InvalidLight { light, reason } => {
+                write!(f, "light {} is invalid: {}", light, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { error, .. } => Some(error),
+            SceneError::Parse(error) => Some(error),
+            SceneError::Import(error) => Some(error),
+            SceneError::Texture { error, .. } => Some(error),
+            SceneError::EnvironmentMap { error, .. } => Some(error),
+            SceneError::InObject { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
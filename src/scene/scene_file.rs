@@ -0,0 +1,840 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use cgmath::{Angle, Deg, InnerSpace, Matrix4, SquareMatrix, Vector3};
+use serde::Deserialize;
+
+use crate::camera::camera_path::CameraPath;
+use crate::camera::main_camera::Camera;
+use crate::import::gltf::load_gltf;
+use crate::import::obj::load_obj;
+use crate::scene::scene_error::SceneError;
+use crate::types::axis_aligned_box::AxisAlignedBox;
+use crate::types::disc::Disc;
+use crate::types::environment::{Environment, EnvironmentMap};
+use crate::types::instance::Instance;
+use crate::types::intersectable::{IntersectableContainer, IntersectableLayout};
+use crate::types::light::Light;
+use crate::types::material::{Emission, Material};
+use crate::types::mesh::Mesh;
+use crate::types::plane::Plane;
+use crate::types::quad::Quad;
+use crate::types::sphere::Sphere;
+use crate::types::texture::TextureSet;
+
+/// Scene used when no `--scene` argument is given.
+const DEFAULT_SCENE: &str = include_str!("../../scenes/default.ron");
+
+/// Camera as written in a scene file. The aspect ratio is not part of the
+/// scene, it comes from the render target.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CameraDescription {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    pub fovy: f32,
+    pub znear: f32,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self {
+            eye: [0.0, 0.0, -5.0],
+            target: [0.0, 0.0, 0.0],
+            up: [0.0, 1.0, 0.0],
+            fovy: 70.0,
+            znear: 0.01,
+        }
+    }
+}
+
+impl CameraDescription {
+    pub fn to_camera(&self, aspect: f32) -> Camera {
+        Camera {
+            eye: self.eye.into(),
+            target: self.target.into(),
+            up: self.up.into(),
+            aspect,
+            fovy: self.fovy,
+            znear: self.znear,
+        }
+    }
+}
+
+/// Sphere as written in a scene file, referencing its material by name.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SphereDescription {
+    pub position: [f32; 3],
+    pub radius: f32,
+    pub material: String,
+}
+
+/// Infinite plane through `point`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaneDescription {
+    pub point: [f32; 3],
+    pub normal: [f32; 3],
+    pub material: String,
+}
+
+/// Parallelogram with the corners `corner`, `corner + edge_u`,
+/// `corner + edge_v` and `corner + edge_u + edge_v`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuadDescription {
+    pub corner: [f32; 3],
+    pub edge_u: [f32; 3],
+    pub edge_v: [f32; 3],
+    pub material: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscDescription {
+    pub center: [f32; 3],
+    pub normal: [f32; 3],
+    pub radius: f32,
+    pub material: String,
+}
+
+/// Axis aligned box between two corners.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BoxDescription {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub material: String,
+}
+
+/// Triangle mesh written out in a scene file. Normals and uvs are optional,
+/// smooth normals are computed when they are left out.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MeshDescription {
+    pub positions: Vec<[f32; 3]>,
+    #[serde(default)]
+    pub normals: Vec<[f32; 3]>,
+    #[serde(default)]
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<[u32; 3]>,
+    pub material: String,
+    /// Whether the mesh encloses a volume, see `Mesh::closed`. Found for
+    /// each part from its edges if not given.
+    #[serde(default)]
+    pub closed: Option<bool>,
+}
+
+/// Model imported from an OBJ or glTF file. Relative paths are resolved
+/// against the directory of the scene file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelDescription {
+    pub path: String,
+    /// Whether the model encloses a volume, see `Mesh::closed`. Found for
+    /// each part from its edges if not given.
+    #[serde(default)]
+    pub closed: Option<bool>,
+}
+
+/// A light that isn't geometry. Colors are multiplied by the intensity, angles
+/// are half angles in degrees.
+#[derive(Debug, Clone, Deserialize)]
+pub enum LightDescription {
+    Point {
+        position: [f32; 3],
+        #[serde(default = "default_light_color")]
+        color: [f32; 3],
+        #[serde(default = "default_light_intensity")]
+        intensity: f32,
+    },
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        #[serde(default = "default_light_color")]
+        color: [f32; 3],
+        #[serde(default = "default_light_intensity")]
+        intensity: f32,
+        /// Full intensity inside this angle to the direction.
+        #[serde(default)]
+        inner_angle: f32,
+        /// No light outside this angle to the direction.
+        outer_angle: f32,
+    },
+    /// `direction` is the direction the light travels in. A sun with a
+    /// `angular_radius` above 0 casts soft shadows.
+    Directional {
+        direction: [f32; 3],
+        #[serde(default = "default_light_color")]
+        color: [f32; 3],
+        #[serde(default = "default_light_intensity")]
+        intensity: f32,
+        #[serde(default)]
+        angular_radius: f32,
+    },
+}
+
+fn default_light_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_light_intensity() -> f32 {
+    1.0
+}
+
+impl LightDescription {
+    pub fn to_light(&self) -> Result<Light, String> {
+        let emission = |color: [f32; 3], intensity: f32| {
+            if !intensity.is_finite() || intensity < 0.0 {
+                return Err(format!(
+                    "intensity {} must be finite and not negative",
+                    intensity
+                ));
+            }
+            if color.iter().any(|value| !value.is_finite() || *value < 0.0) {
+                return Err(format!("color {:?} must be finite and not negative", color));
+            }
+            Ok(Vector3::from(color) * intensity)
+        };
+        let unit = |direction: [f32; 3]| {
+            let direction = Vector3::from(direction);
+            if !direction.magnitude2().is_normal() {
+                return Err(format!("direction {:?} has no length", direction));
+            }
+            Ok(direction.normalize())
+        };
+
+        match *self {
+            LightDescription::Point {
+                position,
+                color,
+                intensity,
+            } => Ok(Light::Point {
+                position: position.into(),
+                intensity: emission(color, intensity)?,
+            }),
+            LightDescription::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => {
+                if !(0.0..=180.0).contains(&outer_angle)
+                    || !(0.0..=outer_angle).contains(&inner_angle)
+                {
+                    return Err(format!(
+                        "angles {} and {} must be between 0 and 180 with the inner one not larger",
+                        inner_angle, outer_angle
+                    ));
+                }
+                Ok(Light::Spot {
+                    position: position.into(),
+                    direction: unit(direction)?,
+                    intensity: emission(color, intensity)?,
+                    cos_inner: Deg(inner_angle).cos(),
+                    cos_outer: Deg(outer_angle).cos(),
+                })
+            }
+            LightDescription::Directional {
+                direction,
+                color,
+                intensity,
+                angular_radius,
+            } => {
+                if !(0.0..90.0).contains(&angular_radius) {
+                    return Err(format!(
+                        "angular radius {} must be at least 0 and less than 90",
+                        angular_radius
+                    ));
+                }
+                Ok(Light::Directional {
+                    direction: unit(direction)?,
+                    irradiance: emission(color, intensity)?,
+                    cos_angular_radius: Deg(angular_radius).cos(),
+                })
+            }
+        }
+    }
+}
+
+/// Offline camera animation, rendered with `--gif`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnimationDescription {
+    pub path: CameraPath,
+    #[serde(default = "default_frames")]
+    pub frames: u32,
+    /// Number of passes averaged into each frame.
+    #[serde(default = "default_samples")]
+    pub samples: u32,
+    /// Delay between frames in hundredths of a second.
+    #[serde(default = "default_delay")]
+    pub delay: u16,
+    /// Hours after midnight of the first and the last frame, for skies whose
+    /// sun follows the time of day.
+    #[serde(default)]
+    pub time_of_day: Option<(f32, f32)>,
+}
+
+fn default_frames() -> u32 {
+    36
+}
+
+fn default_samples() -> u32 {
+    1
+}
+
+fn default_delay() -> u16 {
+    4
+}
+
+/// Primitives that are stored once and placed in the world by instances. The
+/// same lists can be given at the top level of a scene, except for planes.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ObjectDescription {
+    pub spheres: Vec<SphereDescription>,
+    pub quads: Vec<QuadDescription>,
+    pub discs: Vec<DiscDescription>,
+    pub boxes: Vec<BoxDescription>,
+    pub meshes: Vec<MeshDescription>,
+    pub models: Vec<ModelDescription>,
+}
+
+/// Object placed in the world. It's scaled first, then rotated around the x,
+/// y and z axis by `rotation` degrees and then translated.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstanceDescription {
+    pub object: String,
+    #[serde(default)]
+    pub translation: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 3],
+    /// Material used instead of the ones of the object.
+    #[serde(default)]
+    pub material: Option<String>,
+}
+
+fn default_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+impl InstanceDescription {
+    pub fn transform(&self) -> Matrix4<f32> {
+        let [x, y, z] = self.rotation;
+        Matrix4::from_translation(self.translation.into())
+            * Matrix4::from_angle_z(Deg(z))
+            * Matrix4::from_angle_y(Deg(y))
+            * Matrix4::from_angle_x(Deg(x))
+            * Matrix4::from_nonuniform_scale(self.scale[0], self.scale[1], self.scale[2])
+    }
+}
+
+/// Raw contents of a `.ron` scene file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename = "Scene")]
+pub struct SceneFile {
+    #[serde(default)]
+    pub cameras: Vec<CameraDescription>,
+    #[serde(default)]
+    pub environment: Environment,
+    #[serde(default)]
+    pub materials: BTreeMap<String, Material>,
+    #[serde(default)]
+    pub spheres: Vec<SphereDescription>,
+    #[serde(default)]
+    pub planes: Vec<PlaneDescription>,
+    #[serde(default)]
+    pub quads: Vec<QuadDescription>,
+    #[serde(default)]
+    pub discs: Vec<DiscDescription>,
+    #[serde(default)]
+    pub boxes: Vec<BoxDescription>,
+    #[serde(default)]
+    pub meshes: Vec<MeshDescription>,
+    #[serde(default)]
+    pub models: Vec<ModelDescription>,
+    #[serde(default)]
+    pub objects: BTreeMap<String, ObjectDescription>,
+    #[serde(default)]
+    pub instances: Vec<InstanceDescription>,
+    #[serde(default)]
+    pub lights: Vec<LightDescription>,
+    #[serde(default)]
+    pub animation: Option<AnimationDescription>,
+}
+
+/// Bounded primitives with their materials resolved, either placed directly
+/// in the world or instanced.
+#[derive(Default)]
+pub struct SceneObject {
+    pub spheres: Vec<Sphere>,
+    pub quads: Vec<Quad>,
+    pub discs: Vec<Disc>,
+    pub boxes: Vec<AxisAlignedBox>,
+    pub meshes: Vec<Mesh>,
+}
+
+impl SceneObject {
+    pub fn push_into(&self, container: &mut IntersectableContainer) {
+        for sphere in &self.spheres {
+            container.push(sphere);
+        }
+        for quad in &self.quads {
+            container.push(quad);
+        }
+        for disc in &self.discs {
+            container.push(disc);
+        }
+        for axis_aligned_box in &self.boxes {
+            container.push(axis_aligned_box);
+        }
+        for mesh in &self.meshes {
+            container.push_mesh(mesh);
+        }
+    }
+}
+
+/// A scene with material names resolved to indices, ready to be uploaded.
+pub struct Scene {
+    pub cameras: Vec<CameraDescription>,
+    pub environment: Environment,
+    /// The image of `environment.map`, loaded with its sampling distribution.
+    pub environment_map: Option<EnvironmentMap>,
+    pub materials: Vec<Material>,
+    pub material_names: Vec<String>,
+    /// Images of the material textures, indexed by `TextureReference::index`.
+    pub textures: TextureSet,
+    /// Primitives listed at the top level of the scene file.
+    pub world: SceneObject,
+    pub planes: Vec<Plane>,
+    pub objects: Vec<SceneObject>,
+    pub object_names: Vec<String>,
+    /// Instances of `objects`, indexed by `Instance::object`.
+    pub instances: Vec<Instance>,
+    /// Lights that aren't geometry, in world space.
+    pub lights: Vec<Light>,
+    pub animation: Option<AnimationDescription>,
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|error| SceneError::Io {
+            path: path.display().to_string(),
+            error,
+        })?;
+
+        let file: SceneFile = ron::from_str(&source).map_err(SceneError::Parse)?;
+        Self::from_file(file, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Loads a scene file, or a model file as a scene of its own.
+    pub fn load_any<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        if ModelFormat::from_path(path).is_none() {
+            return Self::load(path);
+        }
+
+        let file = SceneFile {
+            models: vec![ModelDescription {
+                path: path.display().to_string(),
+                closed: None,
+            }],
+            ..Default::default()
+        };
+        Self::from_file(file, Path::new(""))
+    }
+
+    /// Parses a scene, models are looked up relative to the working directory.
+    pub fn parse(source: &str) -> Result<Self, SceneError> {
+        let file: SceneFile = ron::from_str(source).map_err(SceneError::Parse)?;
+        Self::from_file(file, Path::new(""))
+    }
+
+    pub fn from_file(mut file: SceneFile, directory: &Path) -> Result<Self, SceneError> {
+        let mut textures = TextureSet::default();
+        for (name, material) in &mut file.materials {
+            material
+                .validate()
+                .map_err(|reason| SceneError::InvalidMaterial {
+                    material: name.clone(),
+                    reason,
+                })?;
+
+            // Texture paths are relative to the scene file like model paths
+            for texture in material.textures_mut().into_iter().flatten() {
+                texture.index = textures
+                    .load(&directory.join(&texture.path))
+                    .map_err(|error| SceneError::Texture {
+                        path: texture.path.clone(),
+                        error,
+                    })?;
+            }
+        }
+
+        file.environment
+            .validate()
+            .map_err(|reason| SceneError::InvalidEnvironment { reason })?;
+        let environment_map = match &file.environment.map {
+            Some(map) => {
+                let map = EnvironmentMap::load(&directory.join(&map.path)).map_err(|error| {
+                    SceneError::EnvironmentMap {
+                        path: map.path.clone(),
+                        error,
+                    }
+                })?;
+                Some(map)
+            }
+            None => None,
+        };
+
+        let mut loader = ObjectLoader {
+            directory,
+            material_names: file.materials.keys().cloned().collect(),
+            materials: file.materials.into_values().collect(),
+            cameras: file.cameras,
+        };
+
+        let lights = file
+            .lights
+            .iter()
+            .enumerate()
+            .map(|(index, light)| {
+                light.to_light().map_err(|reason| SceneError::InvalidLight {
+                    light: index,
+                    reason,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut planes = vec![];
+        for (index, plane) in file.planes.iter().enumerate() {
+            let object = || format!("plane {}", index);
+            let plane = Plane {
+                point: plane.point.into(),
+                normal: plane.normal.into(),
+                material_id: Self::material_id(&loader.material_names, &plane.material, object)?,
+            };
+            plane
+                .validate()
+                .map_err(|reason| SceneError::InvalidShape {
+                    object: object(),
+                    reason,
+                })?;
+            planes.push(plane);
+        }
+
+        let world = loader.load(&ObjectDescription {
+            spheres: file.spheres,
+            quads: file.quads,
+            discs: file.discs,
+            boxes: file.boxes,
+            meshes: file.meshes,
+            models: file.models,
+        })?;
+
+        let object_names: Vec<String> = file.objects.keys().cloned().collect();
+        let mut objects = vec![];
+        for (name, description) in &file.objects {
+            let object = loader
+                .load(description)
+                .map_err(|error| SceneError::InObject {
+                    object: name.clone(),
+                    error: Box::new(error),
+                })?;
+            objects.push(object);
+        }
+
+        let mut instances = vec![];
+        for (index, instance) in file.instances.iter().enumerate() {
+            let object = object_names
+                .iter()
+                .position(|name| *name == instance.object)
+                .ok_or_else(|| SceneError::UnknownObject {
+                    instance: index,
+                    object: instance.object.clone(),
+                })?;
+            let material_override = match &instance.material {
+                Some(material) => {
+                    Some(Self::material_id(&loader.material_names, material, || {
+                        format!("instance {}", index)
+                    })?)
+                }
+                None => None,
+            };
+
+            let instance = Instance {
+                object: object as u32,
+                transform: instance.transform(),
+                material_override,
+            };
+            instance
+                .validate()
+                .map_err(|reason| SceneError::InvalidInstance {
+                    instance: index,
+                    reason,
+                })?;
+            instances.push(instance);
+        }
+
+        Ok(Self {
+            cameras: loader.cameras,
+            environment: file.environment,
+            environment_map,
+            materials: loader.materials,
+            material_names: loader.material_names,
+            textures,
+            world,
+            planes,
+            objects,
+            object_names,
+            instances,
+            lights,
+            animation: file.animation,
+        })
+    }
+
+    /// Every primitive of the scene with its acceleration structure. The
+    /// world is one more object with a single instance.
+    pub fn intersectables(&self) -> IntersectableLayout {
+        let material_emission = self
+            .materials
+            .iter()
+            .map(|material| material.emission(&self.textures))
+            .collect();
+        let mut container = IntersectableContainer::new(material_emission);
+        self.world.push_into(&mut container);
+        let world = container.finish_object();
+        for plane in &self.planes {
+            container.push(plane);
+        }
+
+        let first_object = world + 1;
+        for object in &self.objects {
+            object.push_into(&mut container);
+            container.finish_object();
+        }
+
+        let mut instances = vec![Instance {
+            object: world,
+            transform: Matrix4::identity(),
+            material_override: None,
+        }];
+        instances.extend(self.instances.iter().map(|instance| Instance {
+            object: instance.object + first_object,
+            ..instance.clone()
+        }));
+        let mut layout = IntersectableLayout::build(container, &instances);
+        // Lights without geometry are never hit, so no instance refers to them
+        layout.lights.extend(self.lights.iter().map(Light::uniform));
+
+        // Lights far away shine on the whole scene, planes aside
+        let bounds = layout.bounds();
+        layout.scene_radius = if bounds.is_empty() {
+            1.0
+        } else {
+            0.5 * bounds.extent().magnitude()
+        };
+        let scene_radius = layout.scene_radius;
+        layout.light_powers.extend(
+            self.lights
+                .iter()
+                .map(|light| light.power(Emission::default(), scene_radius)),
+        );
+        let environment_power = self.environment.is_light().then(|| {
+            let hours = self.environment.sky.as_ref().map_or(0.0, |sky| sky.hours());
+            self.environment
+                .power(self.environment_map.as_ref(), scene_radius, hours)
+        });
+        layout.light_table =
+            IntersectableLayout::light_table(&layout.light_powers, environment_power);
+        layout
+    }
+
+    fn material_id<F>(
+        material_names: &[String],
+        material: &str,
+        object: F,
+    ) -> Result<u32, SceneError>
+    where
+        F: FnOnce() -> String,
+    {
+        material_names
+            .iter()
+            .position(|name| name == material)
+            .map(|index| index as u32)
+            .ok_or_else(|| SceneError::UnknownMaterial {
+                object: object(),
+                material: material.to_string(),
+            })
+    }
+
+    /// The camera the scene is viewed through, the first one in the file.
+    pub fn camera(&self) -> CameraDescription {
+        self.cameras.first().cloned().unwrap_or_default()
+    }
+}
+
+/// Resolves the primitives of the world and of every object, collecting the
+/// materials and cameras of imported models on the way.
+struct ObjectLoader<'a> {
+    directory: &'a Path,
+    materials: Vec<Material>,
+    material_names: Vec<String>,
+    cameras: Vec<CameraDescription>,
+}
+
+impl ObjectLoader<'_> {
+    fn load(&mut self, description: &ObjectDescription) -> Result<SceneObject, SceneError> {
+        let mut object = SceneObject::default();
+        let shape_error =
+            |object: String| move |reason: String| SceneError::InvalidShape { object, reason };
+
+        for (index, sphere) in description.spheres.iter().enumerate() {
+            if !sphere.radius.is_finite() || sphere.radius <= 0.0 {
+                return Err(SceneError::InvalidRadius {
+                    sphere: index,
+                    radius: sphere.radius,
+                });
+            }
+
+            let material_id = Scene::material_id(&self.material_names, &sphere.material, || {
+                format!("sphere {}", index)
+            })?;
+
+            object.spheres.push(Sphere {
+                position: sphere.position.into(),
+                radius: sphere.radius,
+                material_id,
+            });
+        }
+
+        for (index, quad) in description.quads.iter().enumerate() {
+            let name = || format!("quad {}", index);
+            let quad = Quad {
+                corner: quad.corner.into(),
+                edge_u: quad.edge_u.into(),
+                edge_v: quad.edge_v.into(),
+                material_id: Scene::material_id(&self.material_names, &quad.material, name)?,
+            };
+            quad.validate().map_err(shape_error(name()))?;
+            object.quads.push(quad);
+        }
+
+        for (index, disc) in description.discs.iter().enumerate() {
+            let name = || format!("disc {}", index);
+            let disc = Disc {
+                center: disc.center.into(),
+                normal: disc.normal.into(),
+                radius: disc.radius,
+                material_id: Scene::material_id(&self.material_names, &disc.material, name)?,
+            };
+            disc.validate().map_err(shape_error(name()))?;
+            object.discs.push(disc);
+        }
+
+        for (index, description) in description.boxes.iter().enumerate() {
+            let name = || format!("box {}", index);
+            let axis_aligned_box = AxisAlignedBox {
+                min: description.min.into(),
+                max: description.max.into(),
+                material_id: Scene::material_id(&self.material_names, &description.material, name)?,
+            };
+            axis_aligned_box.validate().map_err(shape_error(name()))?;
+            object.boxes.push(axis_aligned_box);
+        }
+
+        for (index, mesh) in description.meshes.iter().enumerate() {
+            let material_id = Scene::material_id(&self.material_names, &mesh.material, || {
+                format!("mesh {}", index)
+            })?;
+
+            let closed = mesh.closed;
+            let mut mesh = Mesh {
+                positions: mesh
+                    .positions
+                    .iter()
+                    .map(|position| (*position).into())
+                    .collect(),
+                normals: mesh.normals.iter().map(|normal| (*normal).into()).collect(),
+                uvs: mesh.uvs.iter().map(|uv| (*uv).into()).collect(),
+                triangles: mesh.indices.clone(),
+                material_ids: vec![material_id; mesh.indices.len()],
+                closed: vec![],
+            };
+            mesh.validate().map_err(|reason| SceneError::InvalidMesh {
+                mesh: index,
+                reason,
+            })?;
+            match closed {
+                Some(closed) => mesh.set_closed(closed),
+                None => mesh.find_closed(),
+            }
+            object.meshes.push(mesh);
+        }
+
+        // Imported materials are named after their model, so they can't clash with the scene's
+        for model in &description.models {
+            let path = self.directory.join(&model.path);
+            let (mut mesh, imported_materials, imported_names) = match ModelFormat::from_path(&path)
+            {
+                Some(ModelFormat::Obj) => {
+                    let model = load_obj(&path).map_err(SceneError::Import)?;
+                    (model.mesh, model.materials, model.material_names)
+                }
+                Some(ModelFormat::Gltf) => {
+                    let model = load_gltf(&path).map_err(SceneError::Import)?;
+                    // Cameras from the scene file take precedence
+                    if self.cameras.is_empty() {
+                        self.cameras.extend(model.camera);
+                    }
+                    (model.mesh, model.materials, model.material_names)
+                }
+                None => {
+                    return Err(SceneError::UnsupportedModel {
+                        path: model.path.clone(),
+                    })
+                }
+            };
+
+            if let Some(closed) = model.closed {
+                mesh.set_closed(closed);
+            }
+            let first_material = self.materials.len() as u32;
+            for material_id in &mut mesh.material_ids {
+                *material_id += first_material;
+            }
+            self.materials.extend(imported_materials);
+            self.material_names.extend(
+                imported_names
+                    .iter()
+                    .map(|name| format!("{}/{}", model.path, name)),
+            );
+            object.meshes.push(mesh);
+        }
+
+        Ok(object)
+    }
+}
+
+/// File formats models can be imported from, told apart by their extension.
+enum ModelFormat {
+    Obj,
+    Gltf,
+}
+
+impl ModelFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => Some(ModelFormat::Obj),
+            "gltf" | "glb" => Some(ModelFormat::Gltf),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::parse(DEFAULT_SCENE).expect("built-in default scene is valid")
+    }
+}
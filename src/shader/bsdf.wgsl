@@ -0,0 +1,350 @@
+/*
+ * Principled BSDF
+ *
+ * One BSDF for every material, loosely after Burley 2012, "Physically Based
+ * Shading at Disney". A clearcoat layer sits on top of a base that blends an
+ * opaque and a transmissive part:
+ *  - opaque: an anisotropic GGX specular lobe over an Ashikhmin-Shirley diffuse
+ *    lobe with sheen at grazing angles. The diffuse lobe only gets the energy
+ *    the specular one doesn't reflect.
+ *  - transmissive: a rough dielectric that reflects or refracts through the
+ *    same microfacets.
+ * Directions are in a local frame with the shading normal along +z, on the
+ * side wo is on, and the tangent along x.
+ *
+ * src/bsdf/ mirrors this file on the CPU for testing, keep both in sync.
+ */
+
+// Smallest GGX alpha, keeps mirrors numerically stable
+const MIN_ALPHA: f32 = 0.001;
+// Reflectance of the clearcoat at normal incidence, a polyurethane-like IOR of 1.5
+const CLEARCOAT_F0: f32 = 0.04;
+
+// Value and solid angle density of the BSDF for a pair of directions
+struct BsdfEval {
+    // BSDF times the cosine of the incoming direction
+    value: vec3<f32>,
+    pdf: f32,
+};
+
+struct BsdfSample {
+    direction: vec3<f32>,
+    // BSDF times cosine divided by pdf, the factor the path throughput is multiplied by
+    weight: vec3<f32>,
+    pdf: f32,
+    valid: bool,
+};
+
+// Lobe parameters derived from a material
+struct BsdfLobes {
+    diffuse_color: vec3<f32>,
+    // Reflectance at normal incidence
+    f0: vec3<f32>,
+    // GGX alpha along the tangent and the bitangent
+    alpha: vec2<f32>,
+    // Amount and color of the sheen replacing the diffuse lobe at grazing angles
+    sheen: f32,
+    sheen_color: vec3<f32>,
+    clearcoat: f32,
+    clearcoat_alpha: f32,
+    // Weight of the dielectric lobe, the opaque ones get the rest
+    transmission: f32,
+    // Index of refraction of the side wi refracts to over the one of wo
+    eta: f32,
+    // Tint of refracted light
+    transmission_color: vec3<f32>,
+};
+
+// front_face tells whether wo is outside the material, which decides the
+// direction light refracts in.
+fn material_lobes(material: Material, front_face: bool) -> BsdfLobes {
+    let tint = tint_of(material.color);
+
+    // Dielectrics reflect 8% at most, 4% with the default specular of 0.5
+    let dielectric_f0 = 0.08 * material.specular * mix(vec3<f32>(1.0), tint, material.specular_tint);
+
+    // Anisotropy stretches the highlight along the tangent
+    let aspect = sqrt(1.0 - 0.9 * material.anisotropic);
+    let alpha = material.roughness * material.roughness;
+
+    return BsdfLobes(
+        material.color * (1.0 - material.metallic),
+        mix(dielectric_f0, material.color, material.metallic),
+        max(vec2<f32>(alpha / aspect, alpha * aspect), vec2<f32>(MIN_ALPHA)),
+        material.sheen * (1.0 - material.metallic),
+        mix(vec3<f32>(1.0), tint, material.sheen_tint),
+        material.clearcoat,
+        max(material.clearcoat_roughness * material.clearcoat_roughness, MIN_ALPHA),
+        material.transmission * (1.0 - material.metallic),
+        select(1.0 / material.ior, material.ior, front_face),
+        material.color,
+    );
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Hue and saturation of a color at unit luminance
+fn tint_of(color: vec3<f32>) -> vec3<f32> {
+    let color_luminance = luminance(color);
+    if (color_luminance <= 0.0) {
+        return vec3<f32>(1.0);
+    }
+    return color / color_luminance;
+}
+
+fn fresnel_schlick(f0: vec3<f32>, cos_theta: f32) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(1.0 - clamp(cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Exact Fresnel reflectance of unpolarized light at a dielectric boundary, eta
+// is the index of refraction of the far side over the one of the near side.
+// Total internal reflection reflects everything.
+fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let cos_i = clamp(cos_theta_i, 0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if (sin2_t >= 1.0) {
+        return 1.0;
+    }
+    let cos_t = sqrt(1.0 - sin2_t);
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    return 0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular);
+}
+
+// Energy left for the base after passing the clearcoat at cos_theta to the normal
+fn clearcoat_transmittance(lobes: BsdfLobes, cos_theta: f32) -> f32 {
+    return 1.0 - lobes.clearcoat * fresnel_schlick(vec3<f32>(CLEARCOAT_F0), cos_theta).x;
+}
+
+// Anisotropic GGX normal distribution of the half vector h
+fn ggx_d(alpha: vec2<f32>, h: vec3<f32>) -> f32 {
+    let stretched = vec3<f32>(h.x / alpha.x, h.y / alpha.y, h.z);
+    let length2 = dot(stretched, stretched);
+    return 1.0 / (PI * alpha.x * alpha.y * length2 * length2);
+}
+
+// Smith masking lambda of anisotropic GGX for direction w
+fn ggx_lambda(alpha: vec2<f32>, w: vec3<f32>) -> f32 {
+    let projected = alpha.x * alpha.x * w.x * w.x + alpha.y * alpha.y * w.y * w.y;
+    return (sqrt(1.0 + projected / max(w.z * w.z, 1e-8)) - 1.0) * 0.5;
+}
+
+fn ggx_g1(alpha: vec2<f32>, w: vec3<f32>) -> f32 {
+    return 1.0 / (1.0 + ggx_lambda(alpha, w));
+}
+
+// Height correlated masking and shadowing
+fn ggx_g2(alpha: vec2<f32>, wo: vec3<f32>, wi: vec3<f32>) -> f32 {
+    return 1.0 / (1.0 + ggx_lambda(alpha, wo) + ggx_lambda(alpha, wi));
+}
+
+// Probabilities of sampling the clearcoat, specular, diffuse and dielectric
+// lobes, by how much each of them reflects towards wo
+fn lobe_probabilities(lobes: BsdfLobes, wo: vec3<f32>) -> vec4<f32> {
+    let clearcoat = 1.0 - clearcoat_transmittance(lobes, wo.z);
+    let base = 1.0 - clearcoat;
+    let opaque = base * (1.0 - lobes.transmission);
+    let specular = opaque * luminance(fresnel_schlick(lobes.f0, wo.z));
+    let diffuse = opaque * (luminance(lobes.diffuse_color * (1.0 - lobes.f0)) + lobes.sheen * luminance(lobes.sheen_color));
+    let probabilities = vec4<f32>(clearcoat, specular, diffuse, base * lobes.transmission);
+
+    let total = dot(probabilities, vec4<f32>(1.0));
+    if (total <= 0.0) {
+        return vec4<f32>(0.0, 1.0, 0.0, 0.0);
+    }
+    return probabilities / total;
+}
+
+fn bsdf_eval(lobes: BsdfLobes, wo: vec3<f32>, wi: vec3<f32>) -> BsdfEval {
+    var result = BsdfEval(vec3<f32>(0.0), 0.0);
+    if (wo.z <= 0.0 || wi.z == 0.0) {
+        return result;
+    }
+
+    let probabilities = lobe_probabilities(lobes, wo);
+    // The base is seen through the clearcoat on the way in and out
+    let base = clearcoat_transmittance(lobes, wo.z) * clearcoat_transmittance(lobes, abs(wi.z));
+
+    if (wi.z > 0.0) {
+        let h = normalize(wo + wi);
+        let cos_d = dot(wi, h);
+
+        let d = ggx_d(lobes.alpha, h);
+        let specular = fresnel_schlick(lobes.f0, cos_d) * d * ggx_g2(lobes.alpha, wo, wi) / (4.0 * wo.z);
+        // Visible normal density, mapped from half vectors to incoming directions
+        let specular_pdf = ggx_g1(lobes.alpha, wo) * d / (4.0 * wo.z);
+
+        // Ashikhmin and Shirley 2000, "An Anisotropic Phong BRDF Model"
+        let ashikhmin_shirley = 28.0 / (23.0 * PI) * lobes.diffuse_color * (1.0 - lobes.f0)
+            * (1.0 - pow(1.0 - 0.5 * wi.z, 5.0))
+            * (1.0 - pow(1.0 - 0.5 * wo.z, 5.0));
+        let sheen = lobes.sheen_color * (1.0 - lobes.f0) / PI;
+        // cos_d can round to just above 1, and pow of a negative number is NaN even without sheen
+        let diffuse = mix(ashikhmin_shirley, sheen, lobes.sheen * pow(1.0 - min(cos_d, 1.0), 5.0)) * wi.z;
+
+        let clearcoat_alpha = vec2<f32>(lobes.clearcoat_alpha);
+        let clearcoat_d = ggx_d(clearcoat_alpha, h);
+        let clearcoat = lobes.clearcoat * fresnel_schlick(vec3<f32>(CLEARCOAT_F0), cos_d).x
+            * clearcoat_d * ggx_g2(clearcoat_alpha, wo, wi) / (4.0 * wo.z);
+        let clearcoat_pdf = ggx_g1(clearcoat_alpha, wo) * clearcoat_d / (4.0 * wo.z);
+
+        result.value = vec3<f32>(clearcoat) + base * (1.0 - lobes.transmission) * (specular + diffuse);
+        result.pdf = probabilities.x * clearcoat_pdf + probabilities.y * specular_pdf + probabilities.z * wi.z / PI;
+    }
+
+    if (lobes.transmission > 0.0) {
+        let dielectric = dielectric_eval(lobes, wo, wi);
+        result.value += base * lobes.transmission * dielectric.value;
+        result.pdf += probabilities.w * dielectric.pdf;
+    }
+    return result;
+}
+
+// Picks one of the lobes with u_lobe and samples a direction from it with u.
+// The weight uses the pdf of all lobes, so it doesn't matter which one was picked.
+fn bsdf_sample(lobes: BsdfLobes, wo: vec3<f32>, u_lobe: f32, u: vec2<f32>) -> BsdfSample {
+    var result = BsdfSample(vec3<f32>(0.0), vec3<f32>(0.0), 0.0, false);
+    if (wo.z <= 0.0) {
+        return result;
+    }
+
+    let probabilities = lobe_probabilities(lobes, wo);
+    let clearcoat_end = probabilities.x;
+    let specular_end = clearcoat_end + probabilities.y;
+    let diffuse_end = specular_end + probabilities.z;
+
+    var wi: vec3<f32>;
+    var reflected = true;
+    if (u_lobe < clearcoat_end) {
+        wi = reflect(-wo, sample_ggx_visible_normal(vec2<f32>(lobes.clearcoat_alpha), wo, u));
+    } else if (u_lobe < specular_end) {
+        wi = reflect(-wo, sample_ggx_visible_normal(lobes.alpha, wo, u));
+    } else if (u_lobe < diffuse_end) {
+        wi = sample_cosine_hemisphere(u);
+    } else {
+        // What is left of u_lobe picks between reflection and refraction by the
+        // Fresnel reflectance, total internal reflection always reflects
+        let u_reflect = (u_lobe - diffuse_end) / probabilities.w;
+        let h = sample_ggx_visible_normal(lobes.alpha, wo, u);
+        if (u_reflect < fresnel_dielectric(dot(wo, h), lobes.eta)) {
+            wi = reflect(-wo, h);
+        } else {
+            wi = refract(-wo, h, 1.0 / lobes.eta);
+            reflected = false;
+        }
+    }
+
+    // Reflections off microfacets can point below the surface and refractions
+    // above it, bsdf_eval would count them towards the other side's lobes
+    if ((wi.z > 0.0) != reflected) {
+        return result;
+    }
+
+    let evaluation = bsdf_eval(lobes, wo, wi);
+    if (evaluation.pdf <= 0.0) {
+        return result;
+    }
+
+    result.direction = wi;
+    result.weight = evaluation.value / evaluation.pdf;
+    result.pdf = evaluation.pdf;
+    result.valid = true;
+    return result;
+}
+
+// Rough dielectric (Walter et al. 2007, "Microfacet Models for Refraction
+// through Rough Surfaces"). Directions below the surface are refracted.
+fn dielectric_eval(lobes: BsdfLobes, wo: vec3<f32>, wi: vec3<f32>) -> BsdfEval {
+    if (wi.z > 0.0) {
+        let h = normalize(wo + wi);
+        let fresnel = fresnel_dielectric(dot(wo, h), lobes.eta);
+        let d = ggx_d(lobes.alpha, h);
+        return BsdfEval(
+            vec3<f32>(fresnel * d * ggx_g2(lobes.alpha, wo, wi) / (4.0 * wo.z)),
+            fresnel * ggx_g1(lobes.alpha, wo) * d / (4.0 * wo.z),
+        );
+    }
+
+    // The half vector of a refraction lies between wo and wi scaled by eta
+    var h = normalize(wo + lobes.eta * wi);
+    if (h.z < 0.0) {
+        h = -h;
+    }
+    let cos_o = dot(wo, h);
+    let cos_i = dot(wi, h);
+    if (cos_o <= 0.0 || cos_i >= 0.0) {
+        return BsdfEval(vec3<f32>(0.0), 0.0);
+    }
+
+    let transmitted = 1.0 - fresnel_dielectric(cos_o, lobes.eta);
+    let d = ggx_d(lobes.alpha, h);
+    let denominator = cos_o + lobes.eta * cos_i;
+    let jacobian = -cos_i / (denominator * denominator);
+    // Radiance is compressed into the smaller solid angle of the denser side,
+    // which cancels the eta^2 of the jacobian
+    let value = transmitted * d * ggx_g2(lobes.alpha, wo, wi) * cos_o * jacobian / wo.z;
+    let pdf = transmitted * ggx_g1(lobes.alpha, wo) * d * cos_o * jacobian * lobes.eta * lobes.eta / wo.z;
+    return BsdfEval(lobes.transmission_color * value, pdf);
+}
+
+// Heitz 2018, "Sampling the GGX Distribution of Visible Normals"
+fn sample_ggx_visible_normal(alpha: vec2<f32>, wo: vec3<f32>, u: vec2<f32>) -> vec3<f32> {
+    // Stretch the view direction to the hemisphere configuration
+    let v = normalize(vec3<f32>(alpha.x * wo.x, alpha.y * wo.y, wo.z));
+
+    let length2 = v.x * v.x + v.y * v.y;
+    var t1 = vec3<f32>(1.0, 0.0, 0.0);
+    if (length2 > 0.0) {
+        t1 = vec3<f32>(-v.y, v.x, 0.0) / sqrt(length2);
+    }
+    let t2 = cross(v, t1);
+
+    // Point on the projected disc, compressed towards the visible half
+    let r = sqrt(u.x);
+    let phi = 2.0 * PI * u.y;
+    let p1 = r * cos(phi);
+    var p2 = r * sin(phi);
+    let s = 0.5 * (1.0 + v.z);
+    p2 = (1.0 - s) * sqrt(1.0 - p1 * p1) + s * p2;
+
+    let n = p1 * t1 + p2 * t2 + sqrt(max(0.0, 1.0 - p1 * p1 - p2 * p2)) * v;
+    return normalize(vec3<f32>(alpha.x * n.x, alpha.y * n.y, max(0.0, n.z)));
+}
+
+fn sample_cosine_hemisphere(u: vec2<f32>) -> vec3<f32> {
+    let r = sqrt(u.x);
+    let phi = 2.0 * PI * u.y;
+    return vec3<f32>(r * cos(phi), r * sin(phi), sqrt(max(0.0, 1.0 - u.x)));
+}
+
+// Orthonormal frame around a unit normal, for moving directions into and out of the local frame
+struct Frame {
+    tangent: vec3<f32>,
+    bitangent: vec3<f32>,
+    normal: vec3<f32>,
+};
+
+fn frame_around(normal: vec3<f32>) -> Frame {
+    let tangent = tangent_of(normal);
+    return Frame(tangent, cross(normal, tangent), normal);
+}
+
+// Frame around a unit normal with the tangent as close to tangent as possible
+fn shading_frame(normal: vec3<f32>, tangent: vec3<f32>) -> Frame {
+    let projected = tangent - normal * dot(normal, tangent);
+    if (dot(projected, projected) < 1e-12) {
+        return frame_around(normal);
+    }
+    let frame_tangent = normalize(projected);
+    return Frame(frame_tangent, cross(normal, frame_tangent), normal);
+}
+
+fn to_local(frame: Frame, direction: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(dot(direction, frame.tangent), dot(direction, frame.bitangent), dot(direction, frame.normal));
+}
+
+fn to_world(frame: Frame, direction: vec3<f32>) -> vec3<f32> {
+    return direction.x * frame.tangent + direction.y * frame.bitangent + direction.z * frame.normal;
+}
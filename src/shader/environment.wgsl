@@ -0,0 +1,160 @@
+// Light from rays that leave the scene: a sky gradient, an environment map
+// or a physical sky with a sun, see sky.wgsl.
+//
+// Environment maps are equirectangular images with +y at the top and +z in
+// the middle. Directions are importance sampled in proportion to the
+// luminance of the pixels times the solid angle they cover, by inverting the
+// marginal cdf over the rows and the conditional cdf of the row picked. The
+// cdfs are built on the CPU in src/sampler/distribution.rs. Of the physical
+// sky only the sun disc is sampled.
+
+const ENVIRONMENT_GRADIENT: u32 = 0u;
+const ENVIRONMENT_MAP: u32 = 1u;
+const ENVIRONMENT_SKY: u32 = 2u;
+
+// The map, one black pixel without one
+@group (0) @binding(18) var environment_map: texture_2d<f32>;
+// The marginal cdf with map_height + 1 entries, then the conditional cdf with
+// map_width + 1 entries of every row
+@group (0) @binding(19) var<storage, read> environment_distribution: array<f32>;
+
+// Light arriving from direction
+fn environment_light(direction: vec3<f32>) -> vec3<f32> {
+    if (environment.kind == ENVIRONMENT_MAP) {
+        let pixel = environment_pixel(environment_uv(direction));
+        return textureLoad(environment_map, vec2<i32>(pixel), 0).rgb * environment.intensity;
+    }
+
+    let ground_to_sky_t = smoothstep(-0.01, 0.0, direction.y);
+    if (environment.kind == ENVIRONMENT_SKY) {
+        let sun = sun_direction();
+        let ground = environment.ground_color * (environment.intensity * daylight(sun));
+        if (ground_to_sky_t <= 0.0) {
+            return ground;
+        }
+        // The sky at the horizon continues down to the ground
+        var sky = sky_radiance(normalize(vec3<f32>(direction.x, max(direction.y, 0.0), direction.z)), sun);
+        if (dot(direction, sun) >= environment.cos_sun_radius) {
+            sky += sun_radiance(sun);
+        }
+        return mix(ground, sky, ground_to_sky_t);
+    }
+
+    // Sky
+    let sky_gradient_t = pow(smoothstep(0.0, 0.4, direction.y), 0.35);
+    let sky_color = mix(environment.sky_horizon_color, environment.sky_zenith_color, sky_gradient_t);
+    // Mix
+    return mix(environment.ground_color, sky_color, ground_to_sky_t);
+}
+
+// Image coordinates of direction on the turned map, see equirectangular_uv
+fn environment_uv(direction: vec3<f32>) -> vec2<f32> {
+    let u = 0.5 + atan2(direction.x, direction.z) / (2.0 * PI) - environment.rotation;
+    let v = acos(clamp(direction.y, -1.0, 1.0)) / PI;
+    return vec2<f32>(fract(u), v);
+}
+
+fn environment_pixel(uv: vec2<f32>) -> vec2<u32> {
+    let size = vec2<u32>(environment.map_width, environment.map_height);
+    return min(vec2<u32>(uv * vec2<f32>(size)), size - 1u);
+}
+
+// Whether the environment is sampled as a light, the gradient isn't
+fn environment_is_light() -> bool {
+    return environment.kind != ENVIRONMENT_GRADIENT;
+}
+
+// Direction towards the environment and its solid angle pdf
+fn sample_environment(u: vec2<f32>) -> LightSample {
+    if (environment.kind == ENVIRONMENT_SKY) {
+        // Uniform in the cone of the sun disc
+        let cos_theta = 1.0 - u.x * environment.sun_cone;
+        let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+        let phi = 2.0 * PI * u.y;
+        let axis = frame_around(sun_direction());
+        let direction = normalize(to_world(axis, vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta)));
+        return LightSample(direction, 1.0 / (2.0 * PI * environment.sun_cone));
+    }
+
+    let width = environment.map_width;
+    let height = environment.map_height;
+
+    let row = find_interval(0u, height, u.y);
+    let row_probability = environment_distribution[row + 1u] - environment_distribution[row];
+    let v = (f32(row) + (u.y - environment_distribution[row]) / max(row_probability, 1e-30)) / f32(height);
+
+    let offset = height + 1u + row * (width + 1u);
+    let column = find_interval(offset, width, u.x);
+    let column_probability = environment_distribution[offset + column + 1u] - environment_distribution[offset + column];
+    let u_map = (f32(column) + (u.x - environment_distribution[offset + column]) / max(column_probability, 1e-30)) / f32(width);
+
+    let phi = 2.0 * PI * (u_map + environment.rotation - 0.5);
+    let theta = PI * clamp(v, 0.0, 1.0);
+    let sin_theta = sin(theta);
+    let direction = vec3<f32>(sin_theta * sin(phi), cos(theta), sin_theta * cos(phi));
+    if (sin_theta <= 0.0) {
+        return LightSample(direction, 0.0);
+    }
+    // Density over the image times the image area per solid angle
+    let pdf = row_probability * f32(height) * column_probability * f32(width);
+    return LightSample(direction, pdf / (2.0 * PI * PI * sin_theta));
+}
+
+// Solid angle pdf of sample_environment returning direction
+fn environment_pdf(direction: vec3<f32>) -> f32 {
+    if (environment.kind == ENVIRONMENT_SKY) {
+        if (dot(direction, sun_direction()) < environment.cos_sun_radius) {
+            return 0.0;
+        }
+        return 1.0 / (2.0 * PI * environment.sun_cone);
+    }
+
+    let sin_theta = sqrt(max(0.0, 1.0 - direction.y * direction.y));
+    if (sin_theta <= 0.0) {
+        return 0.0;
+    }
+    let width = environment.map_width;
+    let height = environment.map_height;
+    let pixel = environment_pixel(environment_uv(direction));
+    let row_probability = environment_distribution[pixel.y + 1u] - environment_distribution[pixel.y];
+    let offset = height + 1u + pixel.y * (width + 1u);
+    let column_probability = environment_distribution[offset + pixel.x + 1u] - environment_distribution[offset + pixel.x];
+    return row_probability * f32(height) * column_probability * f32(width) / (2.0 * PI * PI * sin_theta);
+}
+
+// Last of the count bins of the cdf starting at offset whose entry isn't
+// above u, like Distribution1D::find
+fn find_interval(offset: u32, count: u32, u: f32) -> u32 {
+    var low = 0u;
+    var high = count;
+    while (high - low > 1u) {
+        let middle = (low + high) / 2u;
+        if (environment_distribution[offset + middle] <= u) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    return low;
+}
+
+// Light reflected at the point hit from a direction sampled on the
+// environment map, without the probability of picking the environment
+fn sample_environment_light(hitInfo: HitInfo, frame: Frame, lobes: BsdfLobes, wo: vec3<f32>, u: vec2<f32>) -> vec3<f32> {
+    let sample = sample_environment(u);
+    if (sample.pdf <= 0.0) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    let bsdf = bsdf_eval(lobes, wo, to_local(frame, sample.direction));
+    if (all(bsdf.value == vec3<f32>(0.0, 0.0, 0.0))) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    let shadow_hit = intersect_scene(Ray(offset_origin(hitInfo, sample.direction), sample.direction));
+    if (shadow_hit.hit) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    // Weighted against the BSDF with the probability of picking the environment
+    let light_pdf = sample.pdf * light_table(primitiveMetadata.light_count).pmf;
+    return bsdf.value * environment_light(sample.direction) * (power_heuristic(light_pdf, bsdf.pdf) / sample.pdf);
+}
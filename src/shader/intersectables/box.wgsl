@@ -0,0 +1,67 @@
+// Axis aligned box, data: minimum and maximum corner
+// Rays starting inside hit it where they leave, with the normal flipped into
+// the box. The uvs run from 0 to 1 across each face.
+fn intersect_box(ray: Ray, primitive: Primitive) -> HitInfo {
+    var hitInfo: HitInfo = no_hit();
+
+    let box_min = primitive_vec3(primitive.data_offset);
+    let box_max = primitive_vec3(primitive.data_offset + 3u);
+
+    let t0 = (box_min - ray.origin) / ray.direction;
+    let t1 = (box_max - ray.origin) / ray.direction;
+    let t_near = min(t0, t1);
+    let t_far = max(t0, t1);
+    let entry = max(max(t_near.x, t_near.y), t_near.z);
+    let exit = min(min(t_far.x, t_far.y), t_far.z);
+    if (entry > exit || exit < 0.0) {
+        return hitInfo;
+    }
+
+    let front_face = entry >= 0.0;
+    var distance = entry;
+    if (!front_face) {
+        distance = exit;
+    }
+    let position = ray.origin + (ray.direction * distance);
+
+    // The face hit is on the axis along which the point is furthest out, relative to the box size
+    let local = (position - (box_min + box_max) * 0.5) / ((box_max - box_min) * 0.5);
+    let distance_to_center = abs(local);
+    var normal: vec3<f32>;
+    var uv: vec2<f32>;
+    var tangent: vec3<f32>;
+    var bitangent: vec3<f32>;
+    if (distance_to_center.x >= distance_to_center.y && distance_to_center.x >= distance_to_center.z) {
+        normal = vec3<f32>(sign(local.x), 0.0, 0.0);
+        uv = local.zy;
+        tangent = vec3<f32>(0.0, 0.0, 1.0);
+        bitangent = vec3<f32>(0.0, 1.0, 0.0);
+    } else if (distance_to_center.y >= distance_to_center.z) {
+        normal = vec3<f32>(0.0, sign(local.y), 0.0);
+        uv = local.xz;
+        tangent = vec3<f32>(1.0, 0.0, 0.0);
+        bitangent = vec3<f32>(0.0, 0.0, 1.0);
+    } else {
+        normal = vec3<f32>(0.0, 0.0, sign(local.z));
+        uv = local.xy;
+        tangent = vec3<f32>(1.0, 0.0, 0.0);
+        bitangent = vec3<f32>(0.0, 1.0, 0.0);
+    }
+
+    if (!front_face) {
+        normal = -normal;
+    }
+
+    hitInfo.hit = true;
+    hitInfo.distance = distance;
+    hitInfo.position = position;
+    hitInfo.normal = normal;
+    hitInfo.geometric_normal = normal;
+    hitInfo.front_face = front_face;
+    hitInfo.leaving = !front_face;
+    hitInfo.uv = uv * 0.5 + 0.5;
+    hitInfo.tangent = tangent;
+    hitInfo.bitangent = bitangent;
+
+    return hitInfo;
+}
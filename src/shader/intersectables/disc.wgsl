@@ -0,0 +1,43 @@
+// Disc, data: center, unit normal and radius
+// Two sided, the front is the side the normal points to. The uvs map the disc
+// onto the unit square.
+fn intersect_disc(ray: Ray, primitive: Primitive) -> HitInfo {
+    var hitInfo: HitInfo = no_hit();
+
+    let center = primitive_vec3(primitive.data_offset);
+    let disc_normal = primitive_vec3(primitive.data_offset + 3u);
+    let radius = primitive_f32(primitive.data_offset + 6u);
+
+    let denominator = dot(disc_normal, ray.direction);
+    if (abs(denominator) < PLANE_EPSILON) {
+        return hitInfo;
+    }
+    let distance = dot(disc_normal, center - ray.origin) / denominator;
+    if (distance < 0.0) {
+        return hitInfo;
+    }
+
+    let position = ray.origin + (ray.direction * distance);
+    let offset = position - center;
+    if (dot(offset, offset) > radius * radius) {
+        return hitInfo;
+    }
+
+    var normal = disc_normal;
+    if (denominator > 0.0) {
+        normal = -normal;
+    }
+    let tangent = tangent_of(disc_normal);
+
+    hitInfo.hit = true;
+    hitInfo.distance = distance;
+    hitInfo.position = position;
+    hitInfo.normal = normal;
+    hitInfo.geometric_normal = normal;
+    hitInfo.front_face = denominator < 0.0;
+    hitInfo.uv = vec2<f32>(dot(offset, tangent), dot(offset, cross(disc_normal, tangent))) / (2.0 * radius) + 0.5;
+    hitInfo.tangent = tangent;
+    hitInfo.bitangent = cross(disc_normal, tangent);
+
+    return hitInfo;
+}
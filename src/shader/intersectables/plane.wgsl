@@ -0,0 +1,40 @@
+// Plane, data: point and unit normal
+// Two sided, the normal is flipped towards the ray and the front is the side
+// the normal points to. The uvs are world units
+// along a tangent frame of the plane.
+fn intersect_plane(ray: Ray, primitive: Primitive) -> HitInfo {
+    var hitInfo: HitInfo = no_hit();
+
+    let point = primitive_vec3(primitive.data_offset);
+    let plane_normal = primitive_vec3(primitive.data_offset + 3u);
+
+    let denominator = dot(plane_normal, ray.direction);
+    if (abs(denominator) < PLANE_EPSILON) {
+        return hitInfo;
+    }
+    let distance = dot(plane_normal, point - ray.origin) / denominator;
+    if (distance < 0.0) {
+        return hitInfo;
+    }
+
+    var normal = plane_normal;
+    if (denominator > 0.0) {
+        normal = -normal;
+    }
+
+    let position = ray.origin + (ray.direction * distance);
+    let tangent = tangent_of(plane_normal);
+    let offset = position - point;
+
+    hitInfo.hit = true;
+    hitInfo.distance = distance;
+    hitInfo.position = position;
+    hitInfo.normal = normal;
+    hitInfo.geometric_normal = normal;
+    hitInfo.front_face = denominator < 0.0;
+    hitInfo.uv = vec2<f32>(dot(offset, tangent), dot(offset, cross(plane_normal, tangent)));
+    hitInfo.tangent = tangent;
+    hitInfo.bitangent = cross(plane_normal, tangent);
+
+    return hitInfo;
+}
@@ -0,0 +1,47 @@
+// Quad, data: corner and the two edges spanning the parallelogram
+// Two sided, the front is the side cross(edge_u, edge_v) points to. The uvs
+// run from 0 to 1 along the edges.
+fn intersect_quad(ray: Ray, primitive: Primitive) -> HitInfo {
+    var hitInfo: HitInfo = no_hit();
+
+    let corner = primitive_vec3(primitive.data_offset);
+    let edge_u = primitive_vec3(primitive.data_offset + 3u);
+    let edge_v = primitive_vec3(primitive.data_offset + 6u);
+    let plane_normal = cross(edge_u, edge_v);
+
+    let denominator = dot(plane_normal, ray.direction);
+    if (abs(denominator) < PLANE_EPSILON) {
+        return hitInfo;
+    }
+    let distance = dot(plane_normal, corner - ray.origin) / denominator;
+    if (distance < 0.0) {
+        return hitInfo;
+    }
+
+    // Coordinates of the hit point in the basis of the edges
+    let position = ray.origin + (ray.direction * distance);
+    let offset = position - corner;
+    let w = plane_normal / dot(plane_normal, plane_normal);
+    let u = dot(w, cross(offset, edge_v));
+    let v = dot(w, cross(edge_u, offset));
+    if (u < 0.0 || u > 1.0 || v < 0.0 || v > 1.0) {
+        return hitInfo;
+    }
+
+    var normal = normalize(plane_normal);
+    if (denominator > 0.0) {
+        normal = -normal;
+    }
+
+    hitInfo.hit = true;
+    hitInfo.distance = distance;
+    hitInfo.position = position;
+    hitInfo.normal = normal;
+    hitInfo.geometric_normal = normal;
+    hitInfo.front_face = denominator < 0.0;
+    hitInfo.uv = vec2<f32>(u, v);
+    hitInfo.tangent = edge_u;
+    hitInfo.bitangent = edge_v;
+
+    return hitInfo;
+}
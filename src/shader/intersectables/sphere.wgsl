@@ -0,0 +1,51 @@
+// Sphere, data: position (3 words) and radius
+// Rays starting inside hit it where they leave, with the normal flipped
+// towards the center.
+fn intersect_sphere(ray: Ray, primitive: Primitive) -> HitInfo {
+    var hitInfo: HitInfo = no_hit();
+
+    let position = primitive_vec3(primitive.data_offset);
+    let radius = primitive_f32(primitive.data_offset + 3u);
+
+    let offsetRayOrigin = ray.origin - position;
+
+    let a: f32 = dot(ray.direction, ray.direction);
+    let b: f32 = 2.0 * dot(offsetRayOrigin, ray.direction);
+    let c: f32 = dot(offsetRayOrigin, offsetRayOrigin) - radius * radius;
+
+    let discriminant: f32 = b * b - 4.0 * a * c;
+    if (discriminant < 0.0) {
+        return hitInfo;
+    }
+
+    // The near root is behind rays starting inside, they hit the far one
+    var distance = (-b - sqrt(discriminant)) / (2.0 * a);
+    let front_face = distance >= 0.0;
+    if (!front_face) {
+        distance = (-b + sqrt(discriminant)) / (2.0 * a);
+        if (distance < 0.0) {
+            return hitInfo;
+        }
+    }
+
+    let hit_position = ray.origin + (ray.direction * distance);
+    let outward_normal = normalize(hit_position - position);
+    let normal = select(-outward_normal, outward_normal, front_face);
+
+    hitInfo.hit = true;
+    hitInfo.distance = distance;
+    hitInfo.position = hit_position;
+    hitInfo.normal = normal;
+    hitInfo.geometric_normal = normal;
+    hitInfo.front_face = front_face;
+    hitInfo.leaving = !front_face;
+    hitInfo.uv = vec2<f32>(
+        0.5 + atan2(outward_normal.z, outward_normal.x) / (2.0 * PI),
+        0.5 - asin(clamp(outward_normal.y, -1.0, 1.0)) / PI,
+    );
+    // u runs around the y axis and v from the top to the bottom, both vanish at the poles
+    hitInfo.tangent = vec3<f32>(-outward_normal.z, 0.0, outward_normal.x);
+    hitInfo.bitangent = outward_normal * outward_normal.y - vec3<f32>(0.0, 1.0, 0.0);
+
+    return hitInfo;
+}
@@ -0,0 +1,92 @@
+// Triangle, data: three vertex indices, see mesh_vertex, 1 if the mesh is closed
+// Möller–Trumbore intersection, normals and uvs are interpolated from the vertices.
+fn intersect_triangle(ray: Ray, primitive: Primitive) -> HitInfo {
+    var hitInfo: HitInfo = no_hit();
+
+    let v0 = mesh_vertex(primitive_data[primitive.data_offset]);
+    let v1 = mesh_vertex(primitive_data[primitive.data_offset + 1u]);
+    let v2 = mesh_vertex(primitive_data[primitive.data_offset + 2u]);
+
+    let edge1 = v1.position - v0.position;
+    let edge2 = v2.position - v0.position;
+
+    let p = cross(ray.direction, edge2);
+    let determinant = dot(edge1, p);
+    if (abs(determinant) < TRIANGLE_EPSILON) {
+        return hitInfo;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let offsetRayOrigin = ray.origin - v0.position;
+    let u = dot(offsetRayOrigin, p) * inverse_determinant;
+    if (u < 0.0 || u > 1.0) {
+        return hitInfo;
+    }
+
+    let q = cross(offsetRayOrigin, edge1);
+    let v = dot(ray.direction, q) * inverse_determinant;
+    if (v < 0.0 || u + v > 1.0) {
+        return hitInfo;
+    }
+
+    let distance = dot(edge2, q) * inverse_determinant;
+    if (distance < 0.0) {
+        return hitInfo;
+    }
+
+    let w = 1.0 - u - v;
+
+    // Triangles are two sided, both normals are flipped towards the ray. The
+    // front is the side counter-clockwise winding is seen from, rays hitting
+    // the back of a closed mesh leave its inside.
+    var geometric_normal = normalize(cross(edge1, edge2));
+    let front_face = dot(geometric_normal, ray.direction) < 0.0;
+    if (!front_face) {
+        geometric_normal = -geometric_normal;
+    }
+    var normal = normalize(w * v0.normal + u * v1.normal + v * v2.normal);
+    if (dot(normal, geometric_normal) < 0.0) {
+        normal = -normal;
+    }
+
+    let position = ray.origin + (ray.direction * distance);
+
+    hitInfo.hit = true;
+    hitInfo.distance = distance;
+    hitInfo.position = position;
+    hitInfo.normal = normal;
+    hitInfo.geometric_normal = geometric_normal;
+    hitInfo.front_face = front_face;
+    hitInfo.leaving = !front_face && primitive_data[primitive.data_offset + 3u] != 0u;
+    hitInfo.uv = w * vec2<f32>(v0.u, v0.v) + u * vec2<f32>(v1.u, v1.v) + v * vec2<f32>(v2.u, v2.v);
+
+    // Solve the edges for the directions of increasing u and v, meshes
+    // without uvs have none
+    let delta_uv1 = vec2<f32>(v1.u - v0.u, v1.v - v0.v);
+    let delta_uv2 = vec2<f32>(v2.u - v0.u, v2.v - v0.v);
+    let uv_determinant = delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x;
+    if (abs(uv_determinant) > TRIANGLE_EPSILON) {
+        hitInfo.tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) / uv_determinant;
+        hitInfo.bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) / uv_determinant;
+    }
+
+    return hitInfo;
+}
+
+// Vertex of a mesh, 8 words each from vertex_offset in primitive_data
+fn mesh_vertex(index: u32) -> MeshVertex {
+    let offset = primitiveMetadata.vertex_offset + index * 8u;
+    let position_u = bitcast<vec4<f32>>(vec4<u32>(
+        primitive_data[offset],
+        primitive_data[offset + 1u],
+        primitive_data[offset + 2u],
+        primitive_data[offset + 3u],
+    ));
+    let normal_v = bitcast<vec4<f32>>(vec4<u32>(
+        primitive_data[offset + 4u],
+        primitive_data[offset + 5u],
+        primitive_data[offset + 6u],
+        primitive_data[offset + 7u],
+    ));
+    return MeshVertex(position_u.xyz, position_u.w, normal_v.xyz, normal_v.w);
+}
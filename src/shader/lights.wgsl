@@ -0,0 +1,225 @@
+// Next event estimation: every scattering point samples a point on a light,
+// picked in proportion to its power, and casts a shadow ray to it. Emission found this way and
+// emission found by BSDF sampling are weighted with multiple importance
+// sampling (Veach 1997, "Robust Monte Carlo Methods for Light Transport
+// Simulation", chapter 9), so each is used where its pdf is higher. Point,
+// spot and directional lights can't be hit by rays and need no weighting.
+// Spheres are sampled in the cone they cover, quads and discs uniformly by
+// area with the pdf converted to solid angle. Environment maps are sampled
+// in environment.wgsl.
+
+// Number of lights one is picked from, the environment map comes after the lights
+fn light_choice_count() -> u32 {
+    return primitiveMetadata.light_count + select(0u, 1u, environment_is_light());
+}
+
+// Entry of the alias table, 3 words each from light_table_offset in primitive_data
+fn light_table(index: u32) -> AliasEntry {
+    let offset = primitiveMetadata.light_table_offset + index * 3u;
+    return AliasEntry(
+        bitcast<f32>(primitive_data[offset]),
+        primitive_data[offset + 1u],
+        bitcast<f32>(primitive_data[offset + 2u]),
+    );
+}
+
+// Index of a light picked in proportion to its power from the alias table,
+// in constant time however many lights there are. u picks a slot, the
+// fraction left decides between it and its alias.
+fn pick_light(u: f32) -> u32 {
+    let count = light_choice_count();
+    let scaled = u * f32(count);
+    let slot = min(u32(scaled), count - 1u);
+    let entry = light_table(slot);
+    if (scaled - f32(slot) < entry.probability) {
+        return slot;
+    }
+    return entry.alias_index;
+}
+
+// Light reflected at the point hit from a randomly picked light, without the path throughput
+fn sample_direct_light(hitInfo: HitInfo, frame: Frame, lobes: BsdfLobes, wo: vec3<f32>) -> vec3<f32> {
+    if (light_choice_count() == 0u) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    let light_id = pick_light(sample_2d().x);
+    let pick_pmf = light_table(light_id).pmf;
+    if (pick_pmf <= 0.0) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    if (light_id == primitiveMetadata.light_count) {
+        return sample_environment_light(hitInfo, frame, lobes, wo, sample_2d()) / pick_pmf;
+    }
+    let light = lights[light_id];
+    if (!is_shape_light(light)) {
+        return sample_analytic_light(hitInfo, frame, lobes, wo, light, sample_2d()) / pick_pmf;
+    }
+
+    var sample: LightSample;
+    if (light.kind == LIGHT_SPHERE) {
+        sample = sample_sphere_light(light, hitInfo.position, sample_2d());
+    } else {
+        sample = sample_area_light(light, hitInfo.position, sample_2d());
+    }
+    if (sample.pdf <= 0.0) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    let light_pdf = sample.pdf * pick_pmf;
+
+    let bsdf = bsdf_eval(lobes, wo, to_local(frame, sample.direction));
+    if (all(bsdf.value == vec3<f32>(0.0, 0.0, 0.0))) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    // The light is visible if it is the first thing the shadow ray hits, its
+    // emission is taken from there so emission textures apply
+    let shadow_hit = intersect_scene(Ray(offset_origin(hitInfo, sample.direction), sample.direction));
+    if (!shadow_hit.hit || shadow_hit.light_id != light_id) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    let light_material = textured_material(materials[shadow_hit.material_id], shadow_hit);
+    let emission = emitted_light(light_material, shadow_hit);
+
+    return bsdf.value * emission * (power_heuristic(light_pdf, bsdf.pdf) / light_pdf);
+}
+
+// Light reflected at the point hit from a point, spot or directional light,
+// without the probability of picking it
+fn sample_analytic_light(hitInfo: HitInfo, frame: Frame, lobes: BsdfLobes, wo: vec3<f32>, light: Light, u: vec2<f32>) -> vec3<f32> {
+    var direction: vec3<f32>;
+    var distance = INFINITY;
+    var incident = light.intensity;
+    if (light.kind == LIGHT_DIRECTIONAL) {
+        // Uniform in the cone of the sun, each direction brings the irradiance
+        // over the cone's solid angle and is picked with its inverse
+        let cos_theta = 1.0 - u.x * (1.0 - light.cos_outer);
+        let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+        let phi = 2.0 * PI * u.y;
+        let axis = frame_around(-light.direction);
+        direction = normalize(to_world(axis, vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta)));
+    } else {
+        let to_light = light.position - hitInfo.position;
+        distance = length(to_light);
+        direction = to_light / distance;
+        incident /= distance * distance;
+        if (light.kind == LIGHT_SPOT) {
+            let cos_axis = dot(-direction, light.direction);
+            let t = clamp((cos_axis - light.cos_outer) / max(light.cos_inner - light.cos_outer, 1e-4), 0.0, 1.0);
+            incident *= t * t * (3.0 - 2.0 * t);
+        }
+    }
+    if (all(incident == vec3<f32>(0.0, 0.0, 0.0))) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    let bsdf = bsdf_eval(lobes, wo, to_local(frame, direction));
+    if (all(bsdf.value == vec3<f32>(0.0, 0.0, 0.0))) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    // Anything between the point and the light casts a shadow
+    let shadow_hit = intersect_scene(Ray(offset_origin(hitInfo, direction), direction));
+    if (shadow_hit.hit && shadow_hit.distance < distance - SURFACE_OFFSET) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    return bsdf.value * incident;
+}
+
+// Whether the light is an emissive primitive rays can hit
+fn is_shape_light(light: Light) -> bool {
+    return light.kind == LIGHT_SPHERE || light.kind == LIGHT_QUAD || light.kind == LIGHT_DISC;
+}
+
+// Solid angle pdf of sampling the direction from position to light_position
+// on a light, including picking the light
+fn light_sample_pdf(light_id: u32, position: vec3<f32>, light_position: vec3<f32>) -> f32 {
+    let light = lights[light_id];
+    let pick_pmf = light_table(light_id).pmf;
+    if (light.kind != LIGHT_SPHERE) {
+        return area_light_pdf(light, light_position - position) * pick_pmf;
+    }
+    let cone = sphere_light_cone(light, position);
+    if (cone.y <= 0.0) {
+        return 0.0;
+    }
+    return pick_pmf / (2.0 * PI * cone.y);
+}
+
+struct LightSample {
+    direction: vec3<f32>,
+    // Solid angle pdf, zero if the light can't be sampled from the position
+    pdf: f32,
+};
+
+// Uniform direction in the cone of directions from position to the sphere
+fn sample_sphere_light(light: Light, position: vec3<f32>, u: vec2<f32>) -> LightSample {
+    let cone = sphere_light_cone(light, position);
+    if (cone.y <= 0.0) {
+        return LightSample(vec3<f32>(0.0, 0.0, 1.0), 0.0);
+    }
+
+    let cos_theta = 1.0 - u.x * cone.y;
+    let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+    let phi = 2.0 * PI * u.y;
+    let axis = frame_around(normalize(light.position - position));
+    let direction = to_world(axis, vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta));
+    return LightSample(normalize(direction), 1.0 / (2.0 * PI * cone.y));
+}
+
+// Uniform point on a quad or disc, as the direction to it from position
+fn sample_area_light(light: Light, position: vec3<f32>, u: vec2<f32>) -> LightSample {
+    let to_light = area_light_point(light, u) - position;
+    let pdf = area_light_pdf(light, to_light);
+    if (pdf <= 0.0) {
+        return LightSample(vec3<f32>(0.0, 0.0, 1.0), 0.0);
+    }
+    return LightSample(normalize(to_light), pdf);
+}
+
+// Uniform point on the shape of a quad or disc light
+fn area_light_point(light: Light, u: vec2<f32>) -> vec3<f32> {
+    if (light.kind == LIGHT_QUAD) {
+        return light.position + u.x * light.edge_u + u.y * light.edge_v;
+    }
+    // The square root spreads the points evenly over the rings of the disc
+    let r = sqrt(u.x);
+    let phi = 2.0 * PI * u.y;
+    return light.position + r * (cos(phi) * light.edge_u + sin(phi) * light.edge_v);
+}
+
+// Solid angle pdf of the direction to_light points in, for points sampled
+// uniformly by area. An area element dA seen at distance d and angle theta
+// to its normal covers a solid angle of cos(theta) dA / d².
+fn area_light_pdf(light: Light, to_light: vec3<f32>) -> f32 {
+    let distance2 = dot(to_light, to_light);
+    let normal = normalize(cross(light.edge_u, light.edge_v));
+    let cos_light = abs(dot(normal, to_light)) * inverseSqrt(distance2);
+    if (cos_light <= 1e-6 || light.area <= 0.0) {
+        return 0.0;
+    }
+    return distance2 / (cos_light * light.area);
+}
+
+// Cosine of the half angle of the cone a sphere subtends from position and
+// one minus it, which is zero from inside the sphere
+fn sphere_light_cone(light: Light, position: vec3<f32>) -> vec2<f32> {
+    let to_center = light.position - position;
+    let distance2 = dot(to_center, to_center);
+    let sin2_max = light.radius * light.radius / distance2;
+    if (sin2_max >= 1.0) {
+        return vec2<f32>(0.0, 0.0);
+    }
+    let cos_max = sqrt(1.0 - sin2_max);
+    // 1 - cos_max without cancellation for small and distant lights
+    return vec2<f32>(cos_max, sin2_max / (1.0 + cos_max));
+}
+
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let pdf2 = pdf * pdf;
+    let other_pdf2 = other_pdf * other_pdf;
+    if (pdf2 + other_pdf2 <= 0.0) {
+        return 0.0;
+    }
+    return pdf2 / (pdf2 + other_pdf2);
+}
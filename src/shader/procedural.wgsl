@@ -0,0 +1,123 @@
+// Procedural textures, mirrored on the CPU in src/procedural/. Changes have
+// to be made to both.
+//
+// Patterns blend the material color into pattern_color. Checkers and
+// gradients use the uvs, the others the position in object space.
+
+const PATTERN_NONE: u32 = 0u;
+const PATTERN_CHECKER: u32 = 1u;
+const PATTERN_SOLID_CHECKER: u32 = 2u;
+const PATTERN_GRADIENT: u32 = 3u;
+const PATTERN_NOISE: u32 = 4u;
+const PATTERN_MARBLE: u32 = 5u;
+const PATTERN_WOOD: u32 = 6u;
+
+// Blend factor of the pattern of material, between 0 and 1
+fn pattern_value(material: Material, position: vec3<f32>, uv: vec2<f32>) -> f32 {
+    let p = position * material.pattern_scale;
+    var value = 0.0;
+    switch (material.pattern) {
+        case PATTERN_CHECKER: {
+            let cell = floor(uv * material.pattern_scale);
+            value = checker(cell.x + cell.y);
+        }
+        case PATTERN_SOLID_CHECKER: {
+            value = checker(floor(p.x) + floor(p.y) + floor(p.z));
+        }
+        case PATTERN_GRADIENT: {
+            value = fract(uv.y * material.pattern_scale);
+        }
+        case PATTERN_NOISE: {
+            value = 0.5 + 0.5 * fbm(p, material.pattern_octaves);
+        }
+        case PATTERN_MARBLE: {
+            let phase = p.x + material.pattern_distortion * 4.0 * turbulence(p, material.pattern_octaves);
+            value = 0.5 + 0.5 * sin(phase);
+        }
+        case PATTERN_WOOD: {
+            let rings = length(p.xz) + material.pattern_distortion * 0.5 * fbm(p, material.pattern_octaves);
+            value = fract(rings);
+        }
+        default: {}
+    }
+    return clamp(value, 0.0, 1.0);
+}
+
+// 0 for even and 1 for odd sums of cell coordinates
+fn checker(sum: f32) -> f32 {
+    return sum - 2.0 * floor(sum * 0.5);
+}
+
+// Hash of a lattice point (Jarzynski and Olano 2020, "Hash Functions for GPU Rendering")
+fn pcg3d(cell: vec3<i32>) -> u32 {
+    var v = bitcast<vec3<u32>>(cell) * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> vec3<u32>(16u);
+    v.x += v.y * v.z;
+    return v.x;
+}
+
+// Offset to a lattice point dotted with one of the twelve edge directions of a cube
+fn noise_gradient(hash: u32, p: vec3<f32>) -> f32 {
+    let h = hash & 15u;
+    var u = select(p.y, p.x, h < 8u);
+    var v = select(select(p.z, p.x, h == 12u || h == 14u), p.y, h < 4u);
+    u = select(-u, u, (h & 1u) == 0u);
+    v = select(-v, v, (h & 2u) == 0u);
+    return u + v;
+}
+
+// Perlin 2002, "Improving Noise", about between -1 and 1
+fn perlin(p: vec3<f32>) -> f32 {
+    let cell = floor(p);
+    let f = p - cell;
+    let i = vec3<i32>(cell);
+    let w = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+
+    let c000 = noise_gradient(pcg3d(i), f);
+    let c100 = noise_gradient(pcg3d(i + vec3<i32>(1, 0, 0)), f - vec3<f32>(1.0, 0.0, 0.0));
+    let c010 = noise_gradient(pcg3d(i + vec3<i32>(0, 1, 0)), f - vec3<f32>(0.0, 1.0, 0.0));
+    let c110 = noise_gradient(pcg3d(i + vec3<i32>(1, 1, 0)), f - vec3<f32>(1.0, 1.0, 0.0));
+    let c001 = noise_gradient(pcg3d(i + vec3<i32>(0, 0, 1)), f - vec3<f32>(0.0, 0.0, 1.0));
+    let c101 = noise_gradient(pcg3d(i + vec3<i32>(1, 0, 1)), f - vec3<f32>(1.0, 0.0, 1.0));
+    let c011 = noise_gradient(pcg3d(i + vec3<i32>(0, 1, 1)), f - vec3<f32>(0.0, 1.0, 1.0));
+    let c111 = noise_gradient(pcg3d(i + vec3<i32>(1, 1, 1)), f - vec3<f32>(1.0, 1.0, 1.0));
+
+    return mix(
+        mix(mix(c000, c100, w.x), mix(c010, c110, w.x), w.y),
+        mix(mix(c001, c101, w.x), mix(c011, c111, w.x), w.y),
+        w.z,
+    );
+}
+
+// Octaves of noise with doubling frequency and halving amplitude, normalized
+fn fbm(p: vec3<f32>, octaves: u32) -> f32 {
+    var sum = 0.0;
+    var total_amplitude = 0.0;
+    var amplitude = 1.0;
+    var frequency = 1.0;
+    for (var i = 0u; i < octaves; i++) {
+        sum += amplitude * perlin(p * frequency);
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    return select(sum / total_amplitude, 0.0, total_amplitude == 0.0);
+}
+
+// Like fbm with the absolute values of the octaves, between 0 and about 1
+fn turbulence(p: vec3<f32>, octaves: u32) -> f32 {
+    var sum = 0.0;
+    var total_amplitude = 0.0;
+    var amplitude = 1.0;
+    var frequency = 1.0;
+    for (var i = 0u; i < octaves; i++) {
+        sum += amplitude * abs(perlin(p * frequency));
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    return select(sum / total_amplitude, 0.0, total_amplitude == 0.0);
+}
@@ -1,10 +1,18 @@
 const INFINITY: f32 = 10000000.0;
 const PI: f32 = 3.14159;
 const BACKGROUND_COLOR: vec3<f32> = vec3<f32>(0.2, 0.2, 0.2);
-const RAY_COUNT: u32 = 10u;
-const MAX_BOUNCE_COUNT: i32 = 5;
 var<private> state: u32;
 
+// Samplers, selected by extern_globals.sampler_kind
+const SAMPLER_RANDOM: u32 = 0u;
+const SAMPLER_SOBOL: u32 = 1u;
+const SAMPLER_BLUE_NOISE: u32 = 2u;
+const BLUE_NOISE_SIZE: u32 = 64u;
+var<private> pixel_coords: vec2<u32>;
+var<private> pixel_seed: u32;
+var<private> sample_index: u32;
+var<private> sample_dimension: u32;
+
 
 /*
  * Bindings
@@ -12,11 +20,26 @@ var<private> state: u32;
 
 // Color buffer
 @group(0) @binding(0) var color_buffer: texture_storage_2d<rgba8unorm, write>;
+// Average of all frames since the last reset, read from the previous frame and written for the next one
+@group(0) @binding(8) var previous_accumulation_buffer: texture_2d<f32>;
+@group(0) @binding(9) var accumulation_buffer: texture_storage_2d<rgba32float, write>;
 
 @group(0) @binding(1)
 var<uniform> extern_globals: ExternGlobals;
 struct ExternGlobals {
     timestamp: u32,
+    // frames in the accumulation buffer, including this one
+    frame: u32,
+    sampler_kind: u32,
+    // Hours after midnight, see sun_direction
+    time_of_day: f32,
+    // 1 if direct light at primary hits is resampled, see restir.wgsl
+    restir: u32,
+    // Paths per pixel and frame, and bounces per path, see RenderSettings
+    samples_per_frame: u32,
+    max_bounces: u32,
+    // Bounces before Russian roulette may end a path
+    roulette_depth: u32,
 };
 
 struct Globals {
@@ -34,19 +57,77 @@ struct Camera {
    view_params: vec3<f32>,
    _padding2: u32,
 }
-
-
-// Spheres
-@group (0) @binding(3) var<uniform> sphereMetadata: SphereMetadata;
-@group (0) @binding(4) var<storage> spheres: array<Sphere>;
-struct SphereMetadata {
+// Camera of the frame before, to find where points were on screen
+@group(0) @binding(24)
+var<uniform> previous_camera: Camera;
+
+
+// Primitives of every type, dispatched on type_tag by intersect_primitive. The
+// intersection function of each type reads its data starting at data_offset.
+// The mesh vertices and the light table follow the data of the primitives.
+@group (0) @binding(3) var<uniform> primitiveMetadata: PrimitiveMetadata;
+@group (0) @binding(4) var<storage, read> primitives: array<Primitive>;
+@group (0) @binding(12) var<storage, read> primitive_data: array<u32>;
+struct PrimitiveMetadata {
     count: u32,
+    // The first bvh_count primitives are in the BVHs, the rest are unbounded and tested against every ray
+    bvh_count: u32,
+    instance_count: u32,
+    light_count: u32,
+    // Words in primitive_data where the vertices and the light table start
+    vertex_offset: u32,
+    light_table_offset: u32,
+    _padding: vec2<u32>,
 }
-struct Sphere {
-    position: vec3<f32>,
-    radius: f32,
+struct Primitive {
+    type_tag: u32,
+    data_offset: u32,
     material_id: u32,
+    // Index among the lights of the object, NO_LIGHT if it isn't one
+    light_index: u32,
+};
+
+// Instances place objects in the world. Rays are moved into object space and
+// traverse the bottom level BVH of the object starting at root_node.
+@group (0) @binding(14) var<storage, read> instances: array<Instance>;
+struct Instance {
+    transform: mat4x4<f32>,
+    inverse_transform: mat4x4<f32>,
+    root_node: u32,
+    // Material used instead of the ones of the primitives
+    material_override: u32,
+    // Index of the first light of the instance in lights, NO_LIGHT if they aren't sampled
+    light_offset: u32,
+};
+const NO_MATERIAL_OVERRIDE: u32 = 0xffffffffu;
+
+// The top level BVH over the instances starting at node 0, followed by the bottom level
+// BVH of every object. Top level leaves reference ranges of the instance buffer, bottom
+// level leaves ranges of the primitive buffer.
+@group (0) @binding(11) var<storage, read> primitive_bvh: array<BvhNode>;
+struct BvhNode {
+    min: vec3<f32>,
+    // Index of the left child for interior nodes, first primitive for leaves
+    left_or_first: u32,
+    max: vec3<f32>,
+    // Primitive count, zero for interior nodes
+    count: u32,
 };
+const BVH_STACK_SIZE: u32 = 32u;
+
+
+// Vertices shared by the triangles of all meshes, see mesh_vertex
+struct MeshVertex {
+    position: vec3<f32>,
+    u: f32,
+    normal: vec3<f32>,
+    v: f32,
+};
+const TRIANGLE_EPSILON: f32 = 1e-8;
+// Denominator below which a ray counts as parallel to a plane, quad or disc
+const PLANE_EPSILON: f32 = 1e-8;
+// Distance rays leaving a surface start off it
+const SURFACE_OFFSET: f32 = 0.001;
 
 
 // Materials
@@ -57,9 +138,138 @@ struct MaterialMetadata {
 }
 struct Material {
     color: vec3<f32>,
+    metallic: f32,
     emission_color: vec3<f32>,
     emission_strength: f32,
+    roughness: f32,
+    // Reflectance of dielectrics, 0.5 is 4% at normal incidence
+    specular: f32,
+    // Fraction of light refracted into the surface, with index of refraction ior
+    transmission: f32,
+    ior: f32,
+    // Color of white light after attenuation_distance inside the material
+    attenuation_color: vec3<f32>,
+    attenuation_distance: f32,
+    // Principled BSDF parameters, see bsdf.wgsl
+    anisotropic: f32,
+    specular_tint: f32,
+    sheen: f32,
+    sheen_tint: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    // Layers of the texture array, NO_TEXTURE for none
+    base_color_texture: u32,
+    roughness_texture: u32,
+    emission_texture: u32,
+    normal_texture: u32,
+    // Procedural texture, see procedural.wgsl
+    pattern: u32,
+    pattern_scale: f32,
+    pattern_color: vec3<f32>,
+    pattern_octaves: u32,
+    pattern_distortion: f32,
+    // 0 if only the front of the surface emits
+    two_sided_emission: u32,
+};
+const NO_TEXTURE: u32 = 0xffffffffu;
+
+// Material textures, one array for each size they are stretched to. Texture
+// indices hold the array in their top byte and the layer below it.
+@group (0) @binding(15) var textures_256: texture_2d_array<f32>;
+@group (0) @binding(25) var textures_512: texture_2d_array<f32>;
+@group (0) @binding(26) var textures_1024: texture_2d_array<f32>;
+@group (0) @binding(27) var textures_2048: texture_2d_array<f32>;
+@group (0) @binding(16) var texture_sampler: sampler;
+
+// Environment
+@group (0) @binding(7) var<uniform> environment: Environment;
+struct Environment {
+    ground_color: vec3<f32>,
+    // ENVIRONMENT_GRADIENT, ENVIRONMENT_MAP or ENVIRONMENT_SKY, see environment.wgsl
+    kind: u32,
+    sky_horizon_color: vec3<f32>,
+    // Turns of the map around the y axis
+    rotation: f32,
+    sky_zenith_color: vec3<f32>,
+    // Scales the map or the sky
+    intensity: f32,
+    map_width: u32,
+    map_height: u32,
+    // 0 if camera rays leaving the scene see black
+    background: u32,
+    // Sky only, see sky.wgsl
+    sun_follows_time: u32,
+    sun_direction: vec3<f32>,
+    turbidity: f32,
+    latitude: f32,
+    sun_azimuth: f32,
+    cos_sun_radius: f32,
+    sun_cone: f32,
+};
+
+// Emissive primitives and lights without geometry in world space, sampled
+// by next event estimation, see lights.wgsl. Fields a kind doesn't use are zero.
+@group (0) @binding(17) var<storage, read> lights: array<Light>;
+struct Light {
+    position: vec3<f32>,
+    radius: f32,
+    // Spot axis, or the direction directional light travels in
+    direction: vec3<f32>,
+    kind: u32,
+    // Intensity of point and spot lights, irradiance of directional ones
+    intensity: vec3<f32>,
+    cos_inner: f32,
+    // Edges of quads and discs from their corner or center at position.
+    // Points on quads are position + u * edge_u + v * edge_v for u and v in
+    // [0, 1], on discs position + r * (cos(phi) * edge_u + sin(phi) * edge_v)
+    // for r in [0, 1].
+    edge_u: vec3<f32>,
+    // Outer cone of spot lights, angular radius of directional ones
+    cos_outer: f32,
+    edge_v: vec3<f32>,
+    // Area of quads and discs
+    area: f32,
+    // Material of spheres, quads and discs
+    material: u32,
 };
+const LIGHT_SPHERE: u32 = 0u;
+const LIGHT_POINT: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+const LIGHT_DIRECTIONAL: u32 = 3u;
+const LIGHT_QUAD: u32 = 4u;
+const LIGHT_DISC: u32 = 5u;
+const NO_LIGHT: u32 = 0xffffffffu;
+
+// Alias table picking lights in proportion to their power, the environment
+// comes after the lights if it is sampled, see pick_light and light_table.
+struct AliasEntry {
+    probability: f32,
+    // AliasEntry::alias, the name is reserved in WGSL
+    alias_index: u32,
+    // Probability of picking the light of the same index
+    pmf: f32,
+};
+
+// Direct light reservoir of every pixel in two halves. The previous frame's
+// are read from one while the new ones are written to the other, they swap
+// every frame like the accumulation textures, see restir.wgsl
+@group (0) @binding(22) var<storage, read_write> reservoirs: array<Reservoir>;
+struct Reservoir {
+    // Point on the light, or the direction to it for directional lights and the environment
+    light_point: vec3<f32>,
+    light_id: u32,
+    // Primary hit the reservoir belongs to
+    position: vec3<f32>,
+    // Number of candidates seen, zero for empty reservoirs
+    count: f32,
+    normal: vec3<f32>,
+    // Unbiased contribution weight of the light point
+    weight: f32,
+};
+
+// Blue noise tile, BLUE_NOISE_SIZE x BLUE_NOISE_SIZE
+@group (0) @binding(10) var blue_noise: texture_2d<f32>;
+
 //struct MaterialStorage {
 //    count: u32,
 //    materials: array<Material>,
@@ -93,96 +303,153 @@ struct Ray {
 fn main(globals: Globals) {
 
     let dimensions: vec2<u32> = textureDimensions(color_buffer);
-    let uv: vec2<f32> = vec2<f32>(f32(globals.globalInvocationId.x) / f32(dimensions.x), 1.0 - f32(globals.globalInvocationId.y) / f32(dimensions.y));
-//    state = fract(sin(dot(uv.xy * f32(extern_globals.timestamp) ,vec2(12.9898,78.233))) * 43758.5453);
-    state = globals.globalInvocationId.x;
-    let r: u32 = pcrng(&state);
-    state = r + globals.globalInvocationId.y;
-
-    let view_params: vec3<f32> = camera.view_params;
-
-    let viewPointLocal: vec3<f32> = vec3<f32>(uv - 0.5, 1.0) * view_params;
 
-    let viewPointWorld: vec3<f32> = vec3<f32>((vec4<f32>(viewPointLocal, 1.0) * camera.rotation).xyz) + camera.eye;
+    // seed per pixel and frame, so frames can be averaged
+    pixel_coords = globals.globalInvocationId.xy;
+    pixel_seed = hash(pixel_coords.x + hash(pixel_coords.y));
+    state = hash(pixel_seed ^ hash(extern_globals.timestamp));
 
-    let pixel_width = 1.0 / f32(dimensions.x);
-    let pixel_height = 1.0 / f32(dimensions.y);
+    let view_params: vec3<f32> = camera.view_params;
 
     // Ray
     let origin: vec3<f32> = camera.eye;
-//    var ray: Ray =
+
+    // strata for the pixel jitter of the random sampler, rotated every frame
+    let strata_size = u32(ceil(sqrt(f32(extern_globals.samples_per_frame))));
+    let strata_offset = hash(extern_globals.timestamp);
+
+    pixel_reservoir = empty_reservoir();
+    previous_frame_reused = false;
 
     var incoming_light: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
-    for (var i = 0u; i < RAY_COUNT; i++) {
+    for (var i = 0u; i < extern_globals.samples_per_frame; i++) {
+        begin_sample((extern_globals.frame - 1u) * extern_globals.samples_per_frame + i);
+
+        var jitter: vec2<f32> = sample_2d();
+        if (extern_globals.sampler_kind == SAMPLER_RANDOM) {
+            let stratum = (i + strata_offset) % (strata_size * strata_size);
+            jitter = (vec2<f32>(f32(stratum % strata_size), f32(stratum / strata_size)) + jitter) / f32(strata_size);
+        }
 
-        let offset: vec3<f32> = vec3<f32>(rand(&state) * pixel_width, rand(&state) * pixel_height, 0.0);
-//        let offset: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+        let pixel_position = vec2<f32>(pixel_coords) + jitter;
+        let uv: vec2<f32> = vec2<f32>(pixel_position.x / f32(dimensions.x), 1.0 - pixel_position.y / f32(dimensions.y));
+        let viewPointLocal: vec3<f32> = vec3<f32>(uv - 0.5, 1.0) * view_params;
+        let viewPointWorld: vec3<f32> = vec3<f32>((vec4<f32>(viewPointLocal, 1.0) * camera.rotation).xyz) + camera.eye;
 
         incoming_light += trace_path(
             Ray (
-                 origin + offset,
+                 origin,
                  normalize(viewPointWorld - origin),
             )
         );
     }
-    incoming_light /= f32(RAY_COUNT);
-//    incoming_light = rand_direction(&state);
-//    incoming_light = vec3<f32>(rand(&state), rand(&state), rand(&state));
-
-    textureStore(color_buffer, globals.globalInvocationId.xy, vec4<f32>(incoming_light, 1.0));
+    incoming_light /= f32(extern_globals.samples_per_frame);
+
+    // progressive accumulation: running average over all frames since the last reset
+    let pixel = vec2<i32>(globals.globalInvocationId.xy);
+    let previous_light = textureLoad(previous_accumulation_buffer, pixel, 0).rgb;
+    let weight = 1.0 / f32(max(extern_globals.frame, 1u));
+    // The first frame ignores the old average, mix would keep NaNs in it as NaN * 0
+    let average_light = select(mix(previous_light, incoming_light, weight), incoming_light, extern_globals.frame <= 1u);
+    textureStore(accumulation_buffer, pixel, vec4<f32>(average_light, 1.0));
+    // Empty without ReSTIR, so turning it on doesn't pick up stale reservoirs
+    reservoirs[reservoir_offset(false) + pixel_coords.y * dimensions.x + pixel_coords.x] = pixel_reservoir;
+
+    textureStore(color_buffer, globals.globalInvocationId.xy, vec4<f32>(average_light, 1.0));
 }
 
 
 /*
  * Functions
+ *
+ * The intersection functions of the primitive types and intersect_primitive
+ * are appended to this file when the pipeline is created, see intersectable.rs.
  */
 fn trace_path(ray_param: Ray) -> vec3<f32> {
     var ray: Ray = ray_param;
     var ray_color: vec3<f32> = vec3<f32>(1.0, 1.0, 1.0);
     var incoming_light: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    // Where the ray was scattered and the BSDF pdf of its direction, to weight
+    // the emission it finds against light sampling
+    var scatter_position = ray.origin;
+    var scatter_pdf = 0.0;
 
-    for (var i = 0; i < MAX_BOUNCE_COUNT; i++) {
+    for (var i = 0; i < i32(extern_globals.max_bounces); i++) {
 
-        var closestHitInfo: HitInfo = HitInfo (
-            false,
-            INFINITY,
-            vec3<f32>(0.0, 0.0, 0.0),
-            vec3<f32>(0.0, 0.0, 0.0),
-            0u,
-        );
-
-        for (var i = 0u; i < sphereMetadata.count; i++) {
-            let sphere = spheres[i];
-
-            var hitInfo = sphereIntersect(ray, sphere);
-            if (hitInfo.hit && hitInfo.distance < closestHitInfo.distance) {
-                closestHitInfo = hitInfo;
-            }
-        }
+        let closestHitInfo = intersect_scene(ray);
 
 
         if (closestHitInfo.hit) {
+            let material = textured_material(materials[closestHitInfo.material_id], closestHitInfo);
 
-//            incoming_light = closestHitInfo.normal;
-//            break;
+            // Rays leaving a shape travelled through it, Beer-Lambert absorption
+            // tints them by the distance covered
+            if (closestHitInfo.leaving) {
+                let absorption = -log(max(material.attenuation_color, vec3<f32>(1e-6))) / material.attenuation_distance;
+                ray_color *= exp(-absorption * closestHitInfo.distance);
+            }
 
-//            let dir = rand_hemisphere_direction(&state, closestHitInfo.normal);
-            // distribution of rays shifted to the normal
-            let dir = normalize(closestHitInfo.normal + rand_direction(&state));
+            // Lights hit by camera rays can't have been sampled
+            var emission_weight = 1.0;
+            if (i > 0 && closestHitInfo.light_id != NO_LIGHT) {
+                let light_pdf = light_sample_pdf(closestHitInfo.light_id, scatter_position, closestHitInfo.position);
+                emission_weight = power_heuristic(scatter_pdf, light_pdf);
+            }
+            incoming_light += emitted_light(material, closestHitInfo) * ray_color * emission_weight;
+
+            // Shading normals facing away from the ray are bent to grazing.
+            // Anisotropic highlights are stretched along the u direction.
+            let normal = mapped_normal(material, closestHitInfo);
+            let frame = shading_frame(normal, closestHitInfo.tangent);
+            var wo = to_local(frame, -ray.direction);
+            wo.z = max(wo.z, 1e-4);
+            wo = normalize(wo);
+
+            let lobes = material_lobes(material, !closestHitInfo.leaving);
+            if (i == 0 && extern_globals.restir != 0u) {
+                incoming_light += resample_direct_light(closestHitInfo, frame, lobes, wo) * ray_color;
+            } else {
+                incoming_light += sample_direct_light(closestHitInfo, frame, lobes, wo) * ray_color;
+            }
 
-            let bounce_ray: Ray = Ray (
-                closestHitInfo.position,
-                dir,
-            );
-            ray = bounce_ray;
+            // x picks the lobe, y decides the Russian roulette
+            let u_scatter = sample_2d();
+            let bsdf = bsdf_sample(lobes, wo, u_scatter.x, sample_2d());
+            if (!bsdf.valid) {
+                break;
+            }
+            scatter_position = closestHitInfo.position;
+            scatter_pdf = bsdf.pdf;
 
-            let material = materials[closestHitInfo.material_id];
-            let emission_color: vec3<f32> = material.emission_color * material.emission_strength;
-            incoming_light += emission_color * ray_color;
-            ray_color *= material.color;
+            let direction = to_world(frame, bsdf.direction);
+            ray = Ray (
+                offset_origin(closestHitInfo, direction),
+                direction,
+            );
+            ray_color *= bsdf.weight;
+
+            // Russian roulette: after roulette_depth bounces, paths survive with
+            // the light they still carry and are weighted up to make up for
+            // the ones that ended, which keeps the estimate unbiased
+            if (u32(i + 1) >= extern_globals.roulette_depth) {
+                let survival = min(max(ray_color.r, max(ray_color.g, ray_color.b)), 1.0);
+                if (u_scatter.y >= survival) {
+                    break;
+                }
+                ray_color /= survival;
+            }
 
         } else {
-            incoming_light += get_environment_light(ray) * ray_color;
+            if (i == 0 && environment.background == 0u) {
+                break;
+            }
+            // Camera rays and the gradient aren't sampled as lights
+            var environment_weight = 1.0;
+            if (i > 0 && environment_is_light()) {
+                let light_pdf = environment_pdf(ray.direction) * light_table(primitiveMetadata.light_count).pmf;
+                environment_weight = power_heuristic(scatter_pdf, light_pdf);
+            }
+            incoming_light += environment_light(ray.direction) * ray_color * environment_weight;
             break;
         }
     }
@@ -192,69 +459,413 @@ fn trace_path(ray_param: Ray) -> vec3<f32> {
 struct HitInfo {
     hit: bool,
     distance: f32,
+    // Point on the surface, rays leaving it are moved off by SURFACE_OFFSET
     position: vec3<f32>,
+    // Shading normal, on the side of the surface the ray came from
     normal: vec3<f32>,
+    // Normal of the surface itself, also facing against the ray
+    geometric_normal: vec3<f32>,
+    // Whether the ray hit the front of the surface, false for rays leaving a closed shape
+    front_face: bool,
+    // Whether the ray travelled through the inside of a closed shape. Open
+    // surfaces have no inside, hitting their back never leaves anything.
+    leaving: bool,
     material_id: u32,
+    uv: vec2<f32>,
+    // Directions in which u and v increase along the surface, zero where the
+    // uvs don't define them
+    tangent: vec3<f32>,
+    bitangent: vec3<f32>,
+    // Position in the space of the object hit, for solid textures
+    object_position: vec3<f32>,
+    // Index into lights if the primitive hit is sampled as a light, else NO_LIGHT
+    light_id: u32,
 };
 
-fn sphereIntersect(ray: Ray, sphere: Sphere) -> HitInfo {
-
-    var hitInfo: HitInfo = HitInfo (
+fn no_hit() -> HitInfo {
+    return HitInfo (
         false,
         INFINITY,
         vec3<f32>(0.0, 0.0, 0.0),
         vec3<f32>(0.0, 0.0, 0.0),
+        vec3<f32>(0.0, 0.0, 0.0),
+        true,
+        false,
         0u,
+        vec2<f32>(0.0, 0.0),
+        vec3<f32>(0.0, 0.0, 0.0),
+        vec3<f32>(0.0, 0.0, 0.0),
+        vec3<f32>(0.0, 0.0, 0.0),
+        NO_LIGHT,
     );
+}
 
-    let offsetRayOrigin = ray.origin - sphere.position;
+// Origin of a ray leaving the surface of a hit in direction, moved off the
+// surface on the side the ray goes to so it doesn't hit the surface again.
+fn offset_origin(hitInfo: HitInfo, direction: vec3<f32>) -> vec3<f32> {
+    let side = select(-1.0, 1.0, dot(direction, hitInfo.geometric_normal) >= 0.0);
+    return hitInfo.position + hitInfo.geometric_normal * (side * SURFACE_OFFSET);
+}
 
-    let a: f32 = dot(ray.direction, ray.direction);
-    let b: f32 = 2.0 * dot(offsetRayOrigin, ray.direction);
-    let c: f32 = dot(offsetRayOrigin, offsetRayOrigin) - sphere.radius * sphere.radius;
+// Closest hit over all primitives. The unbounded ones are tested first, so
+// their hits already cull the instances, which are found by walking the top
+// level BVH front to back.
+fn intersect_scene(ray: Ray) -> HitInfo {
+    var closestHitInfo: HitInfo = no_hit();
+
+    for (var i = primitiveMetadata.bvh_count; i < primitiveMetadata.count; i++) {
+        let primitive = primitives[i];
+        var hitInfo = intersect_primitive(ray, primitive);
+        if (hitInfo.hit && hitInfo.distance < closestHitInfo.distance) {
+            hitInfo.material_id = primitive.material_id;
+            // Planes aren't part of an object, they use world space
+            hitInfo.object_position = hitInfo.position;
+            closestHitInfo = hitInfo;
+        }
+    }
 
-    let discriminant: f32 = b * b - 4.0 * a * c;
+    if (primitiveMetadata.instance_count == 0u) {
+        return closestHitInfo;
+    }
 
+    let inverse_direction = 1.0 / ray.direction;
+    let root = primitive_bvh[0];
+    if (aabb_intersect(ray, inverse_direction, root.min, root.max, closestHitInfo.distance) == INFINITY) {
+        return closestHitInfo;
+    }
 
-    if (discriminant >= 0.0) {
-        let distance = (-b - sqrt(discriminant)) / (2.0 * a);
+    var stack: array<u32, BVH_STACK_SIZE>;
+    var stack_size = 1u;
+    stack[0] = 0u;
+
+    while (stack_size > 0u) {
+        stack_size--;
+        let node = primitive_bvh[stack[stack_size]];
+
+        if (node.count > 0u) {
+            for (var i = node.left_or_first; i < node.left_or_first + node.count; i++) {
+                let instance = instances[i];
+                // The direction isn't normalized, so distances along the object ray are world distances
+                let object_ray = Ray(
+                    (instance.inverse_transform * vec4<f32>(ray.origin, 1.0)).xyz,
+                    (instance.inverse_transform * vec4<f32>(ray.direction, 0.0)).xyz,
+                );
+                let hitInfo = intersect_object(object_ray, instance.root_node, closestHitInfo.distance);
+                if (hitInfo.hit) {
+                    closestHitInfo = instance_hit_to_world(ray, instance, hitInfo);
+                }
+            }
+            continue;
+        }
 
-        if (distance >= 0.0) {
+        push_children(ray, inverse_direction, node, closestHitInfo.distance, &stack, &stack_size);
+    }
 
-            let position = ray.origin + (ray.direction * distance);
-            let normal = normalize(position - sphere.position);
+    return closestHitInfo;
+}
 
-            hitInfo.hit = true;
-            hitInfo.distance = distance;
-            hitInfo.position = position + (normal * 0.001);
-            hitInfo.normal = normal;
-            hitInfo.material_id = sphere.material_id;
+// Closest hit closer than max_distance with the primitives of the bottom level BVH at root_node.
+fn intersect_object(ray: Ray, root_node: u32, max_distance: f32) -> HitInfo {
+    var closestHitInfo: HitInfo = no_hit();
+    closestHitInfo.distance = max_distance;
+
+    let inverse_direction = 1.0 / ray.direction;
+    var stack: array<u32, BVH_STACK_SIZE>;
+    var stack_size = 1u;
+    stack[0] = root_node;
+
+    while (stack_size > 0u) {
+        stack_size--;
+        let node = primitive_bvh[stack[stack_size]];
+
+        if (node.count > 0u) {
+            for (var i = node.left_or_first; i < node.left_or_first + node.count; i++) {
+                let primitive = primitives[i];
+                var hitInfo = intersect_primitive(ray, primitive);
+                if (hitInfo.hit && hitInfo.distance < closestHitInfo.distance) {
+                    hitInfo.material_id = primitive.material_id;
+                    hitInfo.light_id = primitive.light_index;
+                    closestHitInfo = hitInfo;
+                }
+            }
+            continue;
         }
+
+        push_children(ray, inverse_direction, node, closestHitInfo.distance, &stack, &stack_size);
     }
 
+    return closestHitInfo;
+}
+
+// Pushes the children of an interior node the ray enters before max_distance.
+// The near child is pushed last so it's visited first.
+fn push_children(
+    ray: Ray,
+    inverse_direction: vec3<f32>,
+    node: BvhNode,
+    max_distance: f32,
+    stack: ptr<function, array<u32, BVH_STACK_SIZE>>,
+    stack_size: ptr<function, u32>,
+) {
+    var near = node.left_or_first;
+    var far = node.left_or_first + 1u;
+    var near_distance = aabb_intersect(ray, inverse_direction, primitive_bvh[near].min, primitive_bvh[near].max, max_distance);
+    var far_distance = aabb_intersect(ray, inverse_direction, primitive_bvh[far].min, primitive_bvh[far].max, max_distance);
+    if (far_distance < near_distance) {
+        let node_index = near;
+        near = far;
+        far = node_index;
+        let distance = near_distance;
+        near_distance = far_distance;
+        far_distance = distance;
+    }
+
+    if (far_distance < INFINITY && *stack_size < BVH_STACK_SIZE) {
+        (*stack)[*stack_size] = far;
+        *stack_size += 1u;
+    }
+    if (near_distance < INFINITY && *stack_size < BVH_STACK_SIZE) {
+        (*stack)[*stack_size] = near;
+        *stack_size += 1u;
+    }
+}
+
+// Moves a hit found with the object space ray of an instance into world space.
+fn instance_hit_to_world(ray: Ray, instance: Instance, object_hit: HitInfo) -> HitInfo {
+    var hitInfo = object_hit;
+
+    // Normals are transformed by the inverse transpose
+    let normal_matrix = transpose(mat3x3<f32>(
+        instance.inverse_transform[0].xyz,
+        instance.inverse_transform[1].xyz,
+        instance.inverse_transform[2].xyz,
+    ));
+    hitInfo.normal = normalize(normal_matrix * object_hit.normal);
+    hitInfo.geometric_normal = normalize(normal_matrix * object_hit.geometric_normal);
+    // Tangents lie in the surface and move with it
+    let linear = mat3x3<f32>(instance.transform[0].xyz, instance.transform[1].xyz, instance.transform[2].xyz);
+    hitInfo.tangent = linear * object_hit.tangent;
+    hitInfo.bitangent = linear * object_hit.bitangent;
+    hitInfo.position = ray.origin + ray.direction * object_hit.distance;
+    hitInfo.object_position = object_hit.position;
+
+    if (instance.material_override != NO_MATERIAL_OVERRIDE) {
+        hitInfo.material_id = instance.material_override;
+    }
+    if (instance.light_offset == NO_LIGHT || object_hit.light_id == NO_LIGHT) {
+        hitInfo.light_id = NO_LIGHT;
+    } else {
+        hitInfo.light_id = instance.light_offset + object_hit.light_id;
+    }
     return hitInfo;
 }
 
+// Material with the textures applied at the point hit
+fn textured_material(material: Material, hitInfo: HitInfo) -> Material {
+    let uv = hitInfo.uv;
+    var textured = material;
+    if (material.pattern != PATTERN_NONE) {
+        textured.color = mix(material.color, material.pattern_color, pattern_value(material, hitInfo.object_position, uv));
+    }
+    if (material.base_color_texture != NO_TEXTURE) {
+        textured.color *= srgb_to_linear(sample_texture(material.base_color_texture, uv).rgb);
+    }
+    if (material.roughness_texture != NO_TEXTURE) {
+        textured.roughness *= sample_texture(material.roughness_texture, uv).g;
+    }
+    if (material.emission_texture != NO_TEXTURE) {
+        textured.emission_color *= srgb_to_linear(sample_texture(material.emission_texture, uv).rgb);
+    }
+    return textured;
+}
+
+// Light emitted towards the ray that hit the material, nothing from the back
+// of one sided emitters
+fn emitted_light(material: Material, hitInfo: HitInfo) -> vec3<f32> {
+    if (!hitInfo.front_face && material.two_sided_emission == 0u) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    return material.emission_color * material.emission_strength;
+}
+
+// Shading normal of a hit perturbed by the normal map of the material. The
+// map is in tangent space with red along u and green up in the image, which
+// is towards decreasing v.
+fn mapped_normal(material: Material, hitInfo: HitInfo) -> vec3<f32> {
+    if (material.normal_texture == NO_TEXTURE || dot(hitInfo.tangent, hitInfo.tangent) == 0.0) {
+        return hitInfo.normal;
+    }
+
+    let mapped = sample_texture(material.normal_texture, hitInfo.uv).xyz * 2.0 - 1.0;
+    // The tangents belong to the outside of the surface, the normal faces the ray
+    let outward = select(-hitInfo.normal, hitInfo.normal, hitInfo.front_face);
+    let frame = shading_frame(outward, hitInfo.tangent);
+    let bitangent = select(-frame.bitangent, frame.bitangent, dot(frame.bitangent, hitInfo.bitangent) >= 0.0);
+    let normal = normalize(mapped.x * frame.tangent - mapped.y * bitangent + mapped.z * outward);
+    return select(-normal, normal, hitInfo.front_face);
+}
+
+fn sample_texture(index: u32, uv: vec2<f32>) -> vec4<f32> {
+    let layer = i32(index & 0xffffffu);
+    switch (index >> 24u) {
+        case 0u: {
+            return textureSampleLevel(textures_256, texture_sampler, uv, layer, 0.0);
+        }
+        case 1u: {
+            return textureSampleLevel(textures_512, texture_sampler, uv, layer, 0.0);
+        }
+        case 2u: {
+            return textureSampleLevel(textures_1024, texture_sampler, uv, layer, 0.0);
+        }
+        default: {
+            return textureSampleLevel(textures_2048, texture_sampler, uv, layer, 0.0);
+        }
+    }
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+fn primitive_f32(offset: u32) -> f32 {
+    return bitcast<f32>(primitive_data[offset]);
+}
+
+fn primitive_vec3(offset: u32) -> vec3<f32> {
+    return vec3<f32>(primitive_f32(offset), primitive_f32(offset + 1u), primitive_f32(offset + 2u));
+}
+
+// Unit vector perpendicular to the unit vector normal, continuous except at
+// normal.z == 0 (Duff et al. 2017, "Building an Orthonormal Basis, Revisited").
+fn tangent_of(normal: vec3<f32>) -> vec3<f32> {
+    let sign = select(-1.0, 1.0, normal.z >= 0.0);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    return vec3<f32>(1.0 + sign * normal.x * normal.x * a, sign * b, -sign * normal.x);
+}
+
+// Slab test, returns the entry distance or INFINITY on a miss.
+fn aabb_intersect(ray: Ray, inverse_direction: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>, max_distance: f32) -> f32 {
+    let t0 = (box_min - ray.origin) * inverse_direction;
+    let t1 = (box_max - ray.origin) * inverse_direction;
+    // Where the ray runs in the plane of a face, 0 * inf is NaN, but the
+    // ray never leaves that slab
+    let in_face = is_nan(t0) | is_nan(t1);
+    let t_near = select(min(t0, t1), vec3<f32>(-INFINITY), in_face);
+    let t_far = select(max(t0, t1), vec3<f32>(INFINITY), in_face);
+    let entry = max(max(t_near.x, t_near.y), max(t_near.z, 0.0));
+    let exit = min(min(t_far.x, t_far.y), t_far.z);
+
+    if (entry <= exit && entry < max_distance) {
+        return entry;
+    }
+    return INFINITY;
+}
+
 /*
 // Utils
 */
 
+// Compared by bits, comparisons with NaN may be optimized away
+fn is_nan(v: vec3<f32>) -> vec3<bool> {
+    return (bitcast<vec3<u32>>(v) & vec3<u32>(0x7fffffffu)) > vec3<u32>(0x7f800000u);
+}
+
 // Light
-fn get_environment_light(ray: Ray) -> vec3<f32> {
-    // Ground
-    let ground_color = vec3<f32>(0.24, 0.2, 0.18);
-    // Sky
-    let sky_gradient_t = pow(smoothstep(0.0, 0.4, ray.direction.y), 0.35);
-    let sky_color_zenith = vec3<f32>(0.4, 0.6, 1.0);
-    let sky_color_horizon = vec3<f32>(0.8, 0.8, 0.8);
-    let sky_color = mix(sky_color_horizon, sky_color_zenith, sky_gradient_t);
-    // Mix
-    let ground_to_sky_t = smoothstep(-0.01, 0.0, ray.direction.y);
-    return mix(ground_color, sky_color, ground_to_sky_t);
-//    return vec3<f32>(0.2, 0.2, 0.2);
+// Sampling
+
+// Starts a new sample of the current pixel, dimensions restart at 0.
+fn begin_sample(index: u32) {
+    sample_index = index;
+    sample_dimension = 0u;
+}
+
+// Next two dimensions of the current sample from the selected sampler.
+fn sample_2d() -> vec2<f32> {
+    let dimension = sample_dimension;
+    sample_dimension += 1u;
+
+    switch extern_globals.sampler_kind {
+        case SAMPLER_SOBOL: {
+            return sobol_2d(sample_index, hash(pixel_seed ^ hash(dimension)));
+        }
+        case SAMPLER_BLUE_NOISE: {
+            return blue_noise_2d(sample_index, dimension);
+        }
+        default: {
+            return vec2<f32>(rand(&state), rand(&state));
+        }
+    }
+}
+
+// Owen-scrambled Sobol (0,2)-sequence with a per-pixel shuffled index
+// (Burley 2020, "Practical Hash-based Owen Scrambling").
+fn sobol_2d(index: u32, seed: u32) -> vec2<f32> {
+    let shuffled = nested_uniform_scramble(index, seed);
+
+    // first dimension is the van der Corput sequence
+    var x = reverseBits(shuffled);
+    // second dimension, direction numbers v_k = v_(k-1) ^ (v_(k-1) >> 1)
+    var y = 0u;
+    var direction = 1u << 31u;
+    var bits = shuffled;
+    while (bits != 0u) {
+        if ((bits & 1u) != 0u) {
+            y ^= direction;
+        }
+        bits >>= 1u;
+        direction ^= direction >> 1u;
+    }
+
+    x = nested_uniform_scramble(x, hash(seed ^ 0xa511e9b3u));
+    y = nested_uniform_scramble(y, hash(seed ^ 0x63d83595u));
+    return vec2<f32>(f32(x >> 8u), f32(y >> 8u)) / 16777216.0;
+}
+
+fn laine_karras_permutation(value: u32, seed: u32) -> u32 {
+    var x = value;
+    x ^= x * 0x3d20adeau;
+    x += seed;
+    x *= (seed >> 16u) | 1u;
+    x ^= x * 0x05526c56u;
+    x ^= x * 0x53a22864u;
+    return x;
+}
+
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    return reverseBits(laine_karras_permutation(reverseBits(value), seed));
+}
+
+// Blue noise tile, shifted per dimension and rotated over time by the R2 sequence.
+fn blue_noise_2d(index: u32, dimension: u32) -> vec2<f32> {
+    let shift_x = hash(dimension * 2u);
+    let shift_y = hash(dimension * 2u + 1u);
+    let coords_x = (pixel_coords + vec2<u32>(shift_x, shift_x >> 16u)) % BLUE_NOISE_SIZE;
+    let coords_y = (pixel_coords + vec2<u32>(shift_y, shift_y >> 16u)) % BLUE_NOISE_SIZE;
+    let noise = vec2<f32>(
+        textureLoad(blue_noise, coords_x, 0).r,
+        textureLoad(blue_noise, coords_y, 0).r,
+    );
+    return fract(noise + f32(index) * vec2<f32>(0.7548776662, 0.5698402910));
+}
+
+// Uniformly distributed direction on the unit sphere.
+fn sample_sphere_direction(u: vec2<f32>) -> vec3<f32> {
+    let z = 1.0 - 2.0 * u.x;
+    let r = sqrt(max(0.0, 1.0 - z * z));
+    let phi = 2.0 * PI * u.y;
+    return vec3<f32>(r * cos(phi), r * sin(phi), z);
 }
 
 // Random
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
 fn pcrng(state: ptr<private,u32>) -> u32 {
     *state = *state * 747796405u + 2891336453u;
     var result = ((*state >> ((*state >> 28u) + 4u)) ^ *state) * 277803737u;
@@ -0,0 +1,364 @@
+// Reservoir-based spatiotemporal importance resampling of the direct light at
+// primary hits (Bitterli et al. 2020, "Spatiotemporal Reservoir Resampling
+// for Real-Time Ray Tracing with Dynamic Direct Lighting"). Every primary hit
+// draws RESTIR_CANDIDATES points on lights picked with pick_light and keeps
+// one in proportion to the unshadowed light it reflects. The reservoir of the
+// pixel carries the point kept from one sample of the pixel to the next and
+// on to the next frame, where the first sample merges it with the reservoirs
+// of a few pixels around it, found by reprojecting the hit with the camera
+// of the frame before. The point kept is shaded with one shadow ray and
+// weighted against BSDF sampling like next event estimation.
+//
+// Points on spheres, quads and discs are measured by area, on point and spot
+// lights by the light picked and the directions to directional lights and the
+// environment by solid angle, so they mean the same from any hit. Merging
+// reservoirs with their counts as weights is biased where neighbors see
+// different lights, the price of the lower noise in the preview.
+
+const RESTIR_CANDIDATES: u32 = 8u;
+const RESTIR_SPATIAL_NEIGHBORS: u32 = 3u;
+// Pixels around the reprojected pixel neighbors are picked from
+const RESTIR_SPATIAL_RADIUS: f32 = 16.0;
+// Candidates a merged reservoir counts at most, in frames, so old samples fade out
+const RESTIR_MAX_HISTORY: f32 = 20.0;
+// Cosine between normals and difference in distance to the camera, relative
+// to it, above which reservoirs belong to a different surface
+const RESTIR_NORMAL_THRESHOLD: f32 = 0.9;
+const RESTIR_DEPTH_THRESHOLD: f32 = 0.1;
+
+// Reservoir shared by the samples of the pixel, written for the next frame in main
+var<private> pixel_reservoir: Reservoir;
+// Whether the reservoirs of the previous frame were merged into it
+var<private> previous_frame_reused: bool;
+
+// Reservoir while candidates stream through it
+struct LightReservoir {
+    light_id: u32,
+    light_point: vec3<f32>,
+    // Sum of the resampling weights of every candidate seen
+    weight_sum: f32,
+    // Target function of the light point kept at the current hit
+    point_target: f32,
+    count: f32,
+};
+
+// A light point as seen from a hit
+struct LightPointEval {
+    direction: vec3<f32>,
+    distance: f32,
+    // BSDF with the cosine times the change from the measure of the light
+    // point to solid angle at the hit
+    reflectance: vec3<f32>,
+    // Light expected along direction, without emission textures
+    emission: vec3<f32>,
+    // Next event estimation weight against BSDF sampling
+    mis_weight: f32,
+};
+
+struct LightCandidate {
+    light_id: u32,
+    light_point: vec3<f32>,
+    // Density in the measure of the light point, including picking the light
+    pdf: f32,
+};
+
+fn empty_reservoir() -> Reservoir {
+    return Reservoir(vec3<f32>(0.0, 0.0, 0.0), NO_LIGHT, vec3<f32>(0.0, 0.0, 0.0), 0.0, vec3<f32>(0.0, 0.0, 0.0), 0.0);
+}
+
+// Light reflected at a primary hit from a point on a light resampled with the
+// reservoir of the pixel, without the path throughput. Replaces
+// sample_direct_light.
+fn resample_direct_light(hitInfo: HitInfo, frame: Frame, lobes: BsdfLobes, wo: vec3<f32>) -> vec3<f32> {
+    var reservoir = LightReservoir(NO_LIGHT, vec3<f32>(0.0, 0.0, 0.0), 0.0, 0.0, 0.0);
+    if (light_choice_count() == 0u) {
+        pixel_reservoir = empty_reservoir();
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+
+    // Candidates, the point kept only counts if nothing blocks it
+    for (var i = 0u; i < RESTIR_CANDIDATES; i++) {
+        let candidate = light_candidate(hitInfo.position, sample_2d().x, sample_2d());
+        var weight = 0.0;
+        var point_target = 0.0;
+        if (candidate.pdf > 0.0) {
+            point_target = light_point_target(eval_light_point(hitInfo, frame, lobes, wo, candidate.light_id, candidate.light_point));
+            weight = point_target / candidate.pdf;
+        }
+        add_to_reservoir(&reservoir, candidate.light_id, candidate.light_point, weight, point_target, 1.0);
+    }
+    var candidates = stored_reservoir(reservoir, hitInfo);
+    if (candidates.weight > 0.0) {
+        let eval = eval_light_point(hitInfo, frame, lobes, wo, candidates.light_id, candidates.light_point);
+        if (all(visible_emission(hitInfo, eval, candidates.light_id) == vec3<f32>(0.0, 0.0, 0.0))) {
+            candidates.weight = 0.0;
+        }
+    }
+
+    // Merged with the reservoirs of earlier samples, as long as they are
+    // capped the new candidates keep a say
+    var merged = LightReservoir(NO_LIGHT, vec3<f32>(0.0, 0.0, 0.0), 0.0, 0.0, 0.0);
+    let max_count = RESTIR_MAX_HISTORY * f32(RESTIR_CANDIDATES);
+    merge_reservoir(&merged, candidates, hitInfo, frame, lobes, wo, max_count);
+    if (previous_frame_reused) {
+        merge_reservoir(&merged, pixel_reservoir, hitInfo, frame, lobes, wo, max_count);
+    } else {
+        reuse_previous_frame(&merged, hitInfo, frame, lobes, wo, max_count);
+        previous_frame_reused = true;
+    }
+    pixel_reservoir = stored_reservoir(merged, hitInfo);
+
+    if (pixel_reservoir.weight <= 0.0) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    let eval = eval_light_point(hitInfo, frame, lobes, wo, pixel_reservoir.light_id, pixel_reservoir.light_point);
+    return eval.reflectance * visible_emission(hitInfo, eval, pixel_reservoir.light_id) * (eval.mis_weight * pixel_reservoir.weight);
+}
+
+// Merges the reservoirs the previous frame left where the hit was on screen
+// and at a few pixels around it
+fn reuse_previous_frame(
+    reservoir: ptr<function, LightReservoir>,
+    hitInfo: HitInfo,
+    frame: Frame,
+    lobes: BsdfLobes,
+    wo: vec3<f32>,
+    max_count: f32,
+) {
+    let dimensions = vec2<f32>(textureDimensions(color_buffer));
+    var center = previous_screen_position(hitInfo.position);
+    let on_screen = all(center >= vec2<f32>(0.0, 0.0)) && all(center < dimensions);
+    if (on_screen) {
+        merge_reservoir(reservoir, reservoirs[reservoir_offset(true) + reservoir_index(center)], hitInfo, frame, lobes, wo, max_count);
+    } else {
+        center = vec2<f32>(pixel_coords) + 0.5;
+    }
+
+    for (var i = 0u; i < RESTIR_SPATIAL_NEIGHBORS; i++) {
+        let r = RESTIR_SPATIAL_RADIUS * sqrt(rand(&state));
+        let phi = 2.0 * PI * rand(&state);
+        let neighbor = center + r * vec2<f32>(cos(phi), sin(phi));
+        if (any(neighbor < vec2<f32>(0.0, 0.0)) || any(neighbor >= dimensions)) {
+            continue;
+        }
+        merge_reservoir(reservoir, reservoirs[reservoir_offset(true) + reservoir_index(neighbor)], hitInfo, frame, lobes, wo, max_count);
+    }
+}
+
+// Adds a finished reservoir of another hit as one candidate weighted with
+// the candidates it has seen
+fn merge_reservoir(
+    reservoir: ptr<function, LightReservoir>,
+    other: Reservoir,
+    hitInfo: HitInfo,
+    frame: Frame,
+    lobes: BsdfLobes,
+    wo: vec3<f32>,
+    max_count: f32,
+) {
+    if (other.count <= 0.0 || !same_surface(other, hitInfo)) {
+        return;
+    }
+    let count = min(other.count, max_count);
+    var point_target = 0.0;
+    if (other.weight > 0.0) {
+        point_target = light_point_target(eval_light_point(hitInfo, frame, lobes, wo, other.light_id, other.light_point));
+    }
+    add_to_reservoir(reservoir, other.light_id, other.light_point, point_target * other.weight * count, point_target, count);
+}
+
+fn add_to_reservoir(
+    reservoir: ptr<function, LightReservoir>,
+    light_id: u32,
+    light_point: vec3<f32>,
+    weight: f32,
+    point_target: f32,
+    count: f32,
+) {
+    (*reservoir).weight_sum += weight;
+    (*reservoir).count += count;
+    if (weight > 0.0 && rand(&state) * (*reservoir).weight_sum < weight) {
+        (*reservoir).light_id = light_id;
+        (*reservoir).light_point = light_point;
+        (*reservoir).point_target = point_target;
+    }
+}
+
+// The reservoir with the contribution weight of the light point kept, as it
+// is handed on
+fn stored_reservoir(reservoir: LightReservoir, hitInfo: HitInfo) -> Reservoir {
+    var weight = 0.0;
+    if (reservoir.point_target > 0.0) {
+        weight = reservoir.weight_sum / (reservoir.count * reservoir.point_target);
+    }
+    return Reservoir(reservoir.light_point, reservoir.light_id, hitInfo.position, reservoir.count, hitInfo.geometric_normal, weight);
+}
+
+// Whether the reservoir of another hit lies on about the same surface at
+// about the same distance from the camera
+fn same_surface(other: Reservoir, hitInfo: HitInfo) -> bool {
+    let depth = distance(camera.eye, hitInfo.position);
+    return dot(other.normal, hitInfo.geometric_normal) >= RESTIR_NORMAL_THRESHOLD
+        && abs(distance(camera.eye, other.position) - depth) <= RESTIR_DEPTH_THRESHOLD * depth;
+}
+
+// Pixel position of a point for the previous camera, negative behind it.
+// Inverts the camera ray of main.
+fn previous_screen_position(position: vec3<f32>) -> vec2<f32> {
+    let local = (previous_camera.rotation * vec4<f32>(position - previous_camera.eye, 0.0)).xyz;
+    if (local.z <= 0.0) {
+        return vec2<f32>(-1.0, -1.0);
+    }
+    let view_params = previous_camera.view_params;
+    let uv = local.xy * (view_params.z / local.z) / view_params.xy + 0.5;
+    return vec2<f32>(uv.x, 1.0 - uv.y) * vec2<f32>(textureDimensions(color_buffer));
+}
+
+fn reservoir_index(pixel_position: vec2<f32>) -> u32 {
+    let pixel = vec2<u32>(pixel_position);
+    return pixel.y * textureDimensions(color_buffer).x + pixel.x;
+}
+
+// First reservoir of the half the previous frame wrote, or of the one this
+// frame writes. Bind group i reads accumulation texture i, so the previous
+// frame is in half timestamp % 2.
+fn reservoir_offset(previous: bool) -> u32 {
+    let dimensions = textureDimensions(color_buffer);
+    let half = select(1u - extern_globals.timestamp % 2u, extern_globals.timestamp % 2u, previous);
+    return half * dimensions.x * dimensions.y;
+}
+
+// Point on a light picked in proportion to its power, as seen from position
+fn light_candidate(position: vec3<f32>, u_light: f32, u: vec2<f32>) -> LightCandidate {
+    let light_id = pick_light(u_light);
+    let pick_pmf = light_table(light_id).pmf;
+    if (pick_pmf <= 0.0) {
+        return LightCandidate(light_id, vec3<f32>(0.0, 0.0, 0.0), 0.0);
+    }
+    if (light_id == primitiveMetadata.light_count) {
+        let sample = sample_environment(u);
+        return LightCandidate(light_id, sample.direction, sample.pdf * pick_pmf);
+    }
+
+    let light = lights[light_id];
+    switch light.kind {
+        case LIGHT_POINT, LIGHT_SPOT: {
+            return LightCandidate(light_id, light.position, pick_pmf);
+        }
+        case LIGHT_DIRECTIONAL: {
+            // Uniform in the cone of the sun, as in sample_analytic_light
+            let cos_theta = 1.0 - u.x * (1.0 - light.cos_outer);
+            let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+            let phi = 2.0 * PI * u.y;
+            let axis = frame_around(-light.direction);
+            let direction = normalize(to_world(axis, vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta)));
+            return LightCandidate(light_id, direction, pick_pmf / directional_light_solid_angle(light));
+        }
+        case LIGHT_SPHERE: {
+            // The sphere is sampled in the cone it covers, the point is where
+            // the direction enters it and its density per area is the one
+            // per solid angle times the solid angle of an area element
+            let sample = sample_sphere_light(light, position, u);
+            if (sample.pdf <= 0.0) {
+                return LightCandidate(light_id, vec3<f32>(0.0, 0.0, 0.0), 0.0);
+            }
+            let to_center = light.position - position;
+            let along = dot(to_center, sample.direction);
+            let distance2 = dot(to_center, to_center) - along * along;
+            let distance = along - sqrt(max(0.0, light.radius * light.radius - distance2));
+            let point = position + sample.direction * distance;
+            let cos_light = abs(dot(normalize(point - light.position), sample.direction));
+            return LightCandidate(light_id, point, sample.pdf * cos_light / (distance * distance) * pick_pmf);
+        }
+        default: {
+            if (light.area <= 0.0) {
+                return LightCandidate(light_id, vec3<f32>(0.0, 0.0, 0.0), 0.0);
+            }
+            return LightCandidate(light_id, area_light_point(light, u), pick_pmf / light.area);
+        }
+    }
+}
+
+fn directional_light_solid_angle(light: Light) -> f32 {
+    return 2.0 * PI * max(1.0 - light.cos_outer, 1e-7);
+}
+
+// Unshadowed light a light point sends to the hit and the BSDF reflects
+fn eval_light_point(hitInfo: HitInfo, frame: Frame, lobes: BsdfLobes, wo: vec3<f32>, light_id: u32, light_point: vec3<f32>) -> LightPointEval {
+    var eval = LightPointEval(vec3<f32>(0.0, 0.0, 1.0), INFINITY, vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 0.0), 1.0);
+    if (light_id == NO_LIGHT || light_id >= light_choice_count()) {
+        return eval;
+    }
+
+    var geometry = 1.0;
+    var light_pdf = 0.0;
+    if (light_id == primitiveMetadata.light_count) {
+        eval.direction = light_point;
+        eval.emission = environment_light(light_point);
+        light_pdf = environment_pdf(light_point) * light_table(light_id).pmf;
+    } else {
+        let light = lights[light_id];
+        if (light.kind == LIGHT_DIRECTIONAL) {
+            eval.direction = light_point;
+            eval.emission = light.intensity / directional_light_solid_angle(light);
+        } else {
+            let to_light = light_point - hitInfo.position;
+            eval.distance = length(to_light);
+            eval.direction = to_light / eval.distance;
+            geometry = 1.0 / (eval.distance * eval.distance);
+
+            if (light.kind == LIGHT_POINT || light.kind == LIGHT_SPOT) {
+                eval.emission = light.intensity;
+                if (light.kind == LIGHT_SPOT) {
+                    let cos_axis = dot(-eval.direction, light.direction);
+                    let t = clamp((cos_axis - light.cos_outer) / max(light.cos_inner - light.cos_outer, 1e-4), 0.0, 1.0);
+                    eval.emission *= t * t * (3.0 - 2.0 * t);
+                }
+            } else {
+                var normal: vec3<f32>;
+                if (light.kind == LIGHT_SPHERE) {
+                    normal = normalize(light_point - light.position);
+                } else {
+                    normal = normalize(cross(light.edge_u, light.edge_v));
+                }
+                // The front of the light faces along its normal
+                let cos_light = dot(normal, -eval.direction);
+                let material = materials[light.material];
+                if (cos_light > 0.0 || (light.kind != LIGHT_SPHERE && material.two_sided_emission != 0u)) {
+                    eval.emission = material.emission_color * material.emission_strength;
+                }
+                geometry *= abs(cos_light);
+                light_pdf = light_sample_pdf(light_id, hitInfo.position, light_point);
+            }
+        }
+    }
+
+    let bsdf = bsdf_eval(lobes, wo, to_local(frame, eval.direction));
+    eval.reflectance = bsdf.value * geometry;
+    // Point, spot and directional lights can't be hit by BSDF samples
+    if (light_pdf > 0.0) {
+        eval.mis_weight = power_heuristic(light_pdf, bsdf.pdf);
+    }
+    return eval;
+}
+
+fn light_point_target(eval: LightPointEval) -> f32 {
+    return luminance(eval.reflectance * eval.emission);
+}
+
+// Light arriving at the hit from a light point, zero if it is blocked.
+// Emission textures apply to lights rays can hit.
+fn visible_emission(hitInfo: HitInfo, eval: LightPointEval, light_id: u32) -> vec3<f32> {
+    let shadow_hit = intersect_scene(Ray(offset_origin(hitInfo, eval.direction), eval.direction));
+    if (light_id < primitiveMetadata.light_count && is_shape_light(lights[light_id])) {
+        if (!shadow_hit.hit || shadow_hit.light_id != light_id) {
+            return vec3<f32>(0.0, 0.0, 0.0);
+        }
+        let light_material = textured_material(materials[shadow_hit.material_id], shadow_hit);
+        return emitted_light(light_material, shadow_hit);
+    }
+    if (shadow_hit.hit && shadow_hit.distance < eval.distance - SURFACE_OFFSET) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    return eval.emission;
+}
@@ -0,0 +1,108 @@
+// Physically based daylight sky (Preetham et al. 1999, "A Practical Analytic
+// Model for Daylight"), mirrored on the CPU in src/sky/. Changes have to be
+// made to both.
+
+// Sky luminance in kcd/m² that becomes a radiance of 1, see SKY_SCALE
+const SKY_SCALE: f32 = 0.05;
+// Irradiance of the sun before the atmosphere, see SUN_IRRADIANCE
+const SUN_IRRADIANCE: f32 = 8.0;
+
+// Unit direction towards the sun. Suns following the time of day move along
+// their path at an equinox, see sun_path.
+fn sun_direction() -> vec3<f32> {
+    if (environment.sun_follows_time == 0u) {
+        return environment.sun_direction;
+    }
+    let hour_angle = (extern_globals.time_of_day - 12.0) * PI / 12.0;
+    let x = sin(hour_angle);
+    let z = sin(environment.latitude) * cos(hour_angle);
+    let azimuth = environment.sun_azimuth;
+    return vec3<f32>(
+        x * cos(azimuth) + z * sin(azimuth),
+        cos(environment.latitude) * cos(hour_angle),
+        -x * sin(azimuth) + z * cos(azimuth),
+    );
+}
+
+// Fades the sky out while the sun sets
+fn daylight(sun: vec3<f32>) -> f32 {
+    return smoothstep(-0.1, 0.0, sun.y);
+}
+
+// Relative luminance of a direction theta from the zenith and gamma from the sun
+fn perez(a: f32, b: f32, c: f32, d: f32, e: f32, cos_theta: f32, gamma: f32) -> f32 {
+    let cos_gamma = cos(gamma);
+    return (1.0 + a * exp(b / max(cos_theta, 0.01))) * (1.0 + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+}
+
+// Luminance and chromaticities x and y of the sky in direction, see sky_values
+fn sky_values(direction: vec3<f32>, sun: vec3<f32>, turbidity: f32) -> vec3<f32> {
+    let t = turbidity;
+    let theta_sun = acos(clamp(sun.y, 0.0, 1.0));
+    let sun_above = normalize(vec3<f32>(sun.x, max(sun.y, 0.0), sun.z));
+    let gamma = acos(clamp(dot(direction, sun_above), -1.0, 1.0));
+    let cos_theta = max(direction.y, 0.0);
+
+    // Values at the zenith
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+    let zenith_luminance = (4.0453 * t - 4.9710) * tan(chi) - 0.2155 * t + 2.4192;
+    let s = theta_sun;
+    let s2 = s * s;
+    let s3 = s2 * s;
+    let zenith_x = t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+        + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+        + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+    let zenith_y = t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+        + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+        + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+
+    // Perez distributions of the luminance and the chromaticities
+    let luminance = zenith_luminance
+        * perez(0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703, cos_theta, gamma)
+        / perez(0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703, 1.0, theta_sun);
+    let x = zenith_x
+        * perez(-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452, cos_theta, gamma)
+        / perez(-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452, 1.0, theta_sun);
+    let y = zenith_y
+        * perez(-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529, cos_theta, gamma)
+        / perez(-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529, 1.0, theta_sun);
+    return vec3<f32>(luminance, x, y);
+}
+
+// Linear sRGB radiance of the sky in direction, without the sun disc
+fn sky_radiance(direction: vec3<f32>, sun: vec3<f32>) -> vec3<f32> {
+    let values = sky_values(direction, sun, environment.turbidity);
+    return xyy_to_rgb(values.x * SKY_SCALE * daylight(sun), values.y, values.z) * environment.intensity;
+}
+
+// Linear sRGB radiance of the sun disc
+fn sun_radiance(sun: vec3<f32>) -> vec3<f32> {
+    let solid_angle = 2.0 * PI * environment.sun_cone;
+    return sun_transmittance(sun, environment.turbidity)
+        * (SUN_IRRADIANCE * environment.intensity * daylight(sun) / solid_angle);
+}
+
+// Fraction of red, green and blue sunlight reaching the ground
+fn sun_transmittance(sun: vec3<f32>, turbidity: f32) -> vec3<f32> {
+    let zenith_degrees = degrees(acos(clamp(sun.y, 0.0, 1.0)));
+    let air_mass = 1.0 / (max(sun.y, 0.0) + 0.50572 * pow(96.07995 - zenith_degrees, -1.6364));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let lambda = vec3<f32>(0.68, 0.55, 0.44);
+    let rayleigh = 0.008735 * pow(lambda, vec3<f32>(-4.08));
+    let aerosol = beta * pow(lambda, vec3<f32>(-1.3));
+    return exp(-air_mass * (rayleigh + aerosol));
+}
+
+fn xyy_to_rgb(luminance: f32, x: f32, y: f32) -> vec3<f32> {
+    if (y <= 0.0) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    let rgb = vec3<f32>(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    );
+    return max(rgb, vec3<f32>(0.0, 0.0, 0.0));
+}
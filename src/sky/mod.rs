@@ -0,0 +1,274 @@
+//! Physically based daylight sky, and a CPU implementation of `src/shader/sky.wgsl`.
+//!
+//! The sky follows Preetham et al. 1999, "A Practical Analytic Model for
+//! Daylight": the luminance and chromaticity of every direction are the
+//! values at the zenith times a Perez distribution of the angles to the
+//! zenith and to the sun, all fitted to the turbidity of the air. The sun is
+//! a disc of light dimmed and reddened by the air it shines through.
+
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
+use serde::Deserialize;
+
+/// Sky luminance, in kcd/m², that becomes a radiance of 1.
+pub const SKY_SCALE: f32 = 0.05;
+
+/// Irradiance of the sun on a surface facing it, before the atmosphere.
+pub const SUN_IRRADIANCE: f32 = 8.0;
+
+/// Sky with a sun, replacing the gradient of the environment.
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(default)]
+pub struct Sky {
+    /// Haziness of the air, from 2 for a clear sky to 10 for a hazy one.
+    pub turbidity: f32,
+    pub sun: SunPosition,
+    /// Scales the light of the sky and the sun.
+    pub intensity: f32,
+    /// Half angle of the sun disc in degrees.
+    pub sun_angular_radius: f32,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self {
+            turbidity: 3.0,
+            sun: SunPosition::Fixed {
+                elevation: 45.0,
+                azimuth: 0.0,
+            },
+            intensity: 1.0,
+            sun_angular_radius: 0.27,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+pub enum SunPosition {
+    /// `elevation` degrees above the horizon, turned `azimuth` degrees around
+    /// the y axis from +z towards +x.
+    Fixed { elevation: f32, azimuth: f32 },
+    /// The path of the sun at an equinox seen from `latitude` degrees north,
+    /// at `hours` after midnight. The sun rises on the -x side, is highest
+    /// at noon in the direction `azimuth` degrees from +z and sets on the +x
+    /// side. The time can be animated, see `GlobalUniform::time_of_day`.
+    TimeOfDay {
+        hours: f32,
+        #[serde(default = "default_latitude")]
+        latitude: f32,
+        #[serde(default)]
+        azimuth: f32,
+    },
+}
+
+fn default_latitude() -> f32 {
+    45.0
+}
+
+impl Sky {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(2.0..=10.0).contains(&self.turbidity) {
+            return Err(format!(
+                "turbidity {} must be between 2 and 10",
+                self.turbidity
+            ));
+        }
+        if !(self.intensity.is_finite() && self.intensity >= 0.0) {
+            return Err(format!(
+                "intensity {} must be finite and not negative",
+                self.intensity
+            ));
+        }
+        if !(self.sun_angular_radius > 0.0 && self.sun_angular_radius < 90.0) {
+            return Err(format!(
+                "sun angular radius {} must be between 0 and 90 degrees",
+                self.sun_angular_radius
+            ));
+        }
+        let finite = match self.sun {
+            SunPosition::Fixed { elevation, azimuth } => {
+                elevation.is_finite() && azimuth.is_finite()
+            }
+            SunPosition::TimeOfDay {
+                hours,
+                latitude,
+                azimuth,
+            } => hours.is_finite() && (-90.0..=90.0).contains(&latitude) && azimuth.is_finite(),
+        };
+        if !finite {
+            return Err(format!("invalid sun position {:?}", self.sun));
+        }
+        Ok(())
+    }
+
+    /// Unit direction towards the sun, at `hours` after midnight if the sun
+    /// follows the time of day.
+    pub fn sun_direction(&self, hours: f32) -> Vector3<f32> {
+        match self.sun {
+            SunPosition::Fixed { elevation, azimuth } => {
+                let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+                Vector3::new(
+                    elevation.cos() * azimuth.sin(),
+                    elevation.sin(),
+                    elevation.cos() * azimuth.cos(),
+                )
+            }
+            SunPosition::TimeOfDay {
+                latitude, azimuth, ..
+            } => sun_path(hours, latitude.to_radians(), azimuth.to_radians()),
+        }
+    }
+
+    /// Hours after midnight the sky starts at, noon for a fixed sun.
+    pub fn hours(&self) -> f32 {
+        match self.sun {
+            SunPosition::Fixed { .. } => 12.0,
+            SunPosition::TimeOfDay { hours, .. } => hours,
+        }
+    }
+
+    /// Linear sRGB radiance of the sky in the unit `direction` above the
+    /// horizon, without the sun disc.
+    pub fn radiance(&self, direction: Vector3<f32>, sun: Vector3<f32>) -> [f32; 3] {
+        sky_radiance(direction, sun, self.turbidity).map(|value| value * self.intensity)
+    }
+
+    /// Linear sRGB radiance of the sun disc.
+    pub fn sun_radiance(&self, sun: Vector3<f32>) -> [f32; 3] {
+        let solid_angle = 2.0 * PI * (1.0 - self.sun_angular_radius.to_radians().cos());
+        sun_transmittance(sun, self.turbidity)
+            .map(|value| value * SUN_IRRADIANCE * self.intensity * daylight(sun) / solid_angle)
+    }
+}
+
+/// Direction of the sun at an equinox, `latitude` and `azimuth` in radians.
+pub fn sun_path(hours: f32, latitude: f32, azimuth: f32) -> Vector3<f32> {
+    let hour_angle = (hours - 12.0) * PI / 12.0;
+    let x = hour_angle.sin();
+    let z = latitude.sin() * hour_angle.cos();
+    Vector3::new(
+        x * azimuth.cos() + z * azimuth.sin(),
+        latitude.cos() * hour_angle.cos(),
+        -x * azimuth.sin() + z * azimuth.cos(),
+    )
+}
+
+/// Fades the sky out while the sun sets, dark when it is 6 degrees below the
+/// horizon.
+pub fn daylight(sun: Vector3<f32>) -> f32 {
+    smoothstep(-0.1, 0.0, sun.y)
+}
+
+/// Perez et al. 1993, relative luminance of a direction `theta` from the
+/// zenith and `gamma` from the sun.
+fn perez(coefficients: [f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = coefficients;
+    let cos_gamma = gamma.cos();
+    (1.0 + a * (b / cos_theta.max(0.01)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// Perez coefficients of the luminance Y and the chromaticities x and y.
+fn perez_coefficients(turbidity: f32) -> [[f32; 5]; 3] {
+    let t = turbidity;
+    [
+        [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ],
+        [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ],
+        [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ],
+    ]
+}
+
+/// Luminance in kcd/m² and chromaticity x and y at the zenith, for the sun
+/// `theta_sun` radians from it.
+pub fn zenith_values(theta_sun: f32, turbidity: f32) -> [f32; 3] {
+    let t = turbidity;
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+    let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+    let (s, s2, s3) = (theta_sun, theta_sun * theta_sun, theta_sun.powi(3));
+    let x = t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+        + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+        + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+    let y = t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+        + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+        + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+    [luminance, x, y]
+}
+
+/// Luminance in kcd/m² and chromaticity x and y of the sky in `direction`.
+/// The sun is clamped to the horizon, below it the sky only fades out.
+pub fn sky_values(direction: Vector3<f32>, sun: Vector3<f32>, turbidity: f32) -> [f32; 3] {
+    let theta_sun = sun.y.clamp(0.0, 1.0).acos();
+    let sun_above = Vector3::new(sun.x, sun.y.max(0.0), sun.z).normalize();
+    let gamma = direction.dot(sun_above).clamp(-1.0, 1.0).acos();
+    let cos_theta = direction.y.max(0.0);
+
+    let zenith = zenith_values(theta_sun, turbidity);
+    let coefficients = perez_coefficients(turbidity);
+    let mut values = [0.0; 3];
+    for i in 0..3 {
+        values[i] = zenith[i] * perez(coefficients[i], cos_theta, gamma)
+            / perez(coefficients[i], 1.0, theta_sun);
+    }
+    values
+}
+
+/// Linear sRGB radiance of the sky in `direction`.
+pub fn sky_radiance(direction: Vector3<f32>, sun: Vector3<f32>, turbidity: f32) -> [f32; 3] {
+    let [luminance, x, y] = sky_values(direction, sun, turbidity);
+    xyy_to_rgb(luminance * SKY_SCALE * daylight(sun), x, y)
+}
+
+/// Fraction of red, green and blue sunlight reaching the ground through
+/// Rayleigh scattering and aerosols (Ångström's formula), along the air mass
+/// of Kasten and Young 1989.
+pub fn sun_transmittance(sun: Vector3<f32>, turbidity: f32) -> [f32; 3] {
+    let zenith_degrees = sun.y.clamp(0.0, 1.0).acos().to_degrees();
+    let air_mass = 1.0 / (sun.y.max(0.0) + 0.50572 * (96.07995 - zenith_degrees).powf(-1.6364));
+    let beta = 0.04608 * turbidity - 0.04586;
+    // Wavelengths of red, green and blue in micrometers
+    [0.68f32, 0.55, 0.44].map(|lambda| {
+        let rayleigh = 0.008735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        (-air_mass * (rayleigh + aerosol)).exp()
+    })
+}
+
+/// Converts a luminance and chromaticity to linear sRGB.
+fn xyy_to_rgb(luminance: f32, x: f32, y: f32) -> [f32; 3] {
+    if y <= 0.0 {
+        return [0.0; 3];
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    [
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    ]
+    .map(|value| value.max(0.0))
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
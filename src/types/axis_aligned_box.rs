@@ -0,0 +1,62 @@
+use cgmath::Vector3;
+
+use crate::acceleration::aabb::Aabb;
+use crate::types::intersectable::{Intersectable, IntersectableType, PrimitiveData};
+
+/// Solid box between two corners. Rays starting inside hit it where they leave.
+pub struct AxisAlignedBox {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+    pub material_id: u32,
+}
+
+impl AxisAlignedBox {
+    pub fn validate(&self) -> Result<(), String> {
+        let extent = self.max - self.min;
+        if !(0..3).all(|axis| extent[axis] > 0.0 && extent[axis].is_finite()) {
+            let (min, max): ([f32; 3], [f32; 3]) = (self.min.into(), self.max.into());
+            return Err(format!(
+                "max {:?} must be greater than min {:?} on every axis",
+                max, min
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Intersectable for AxisAlignedBox {
+    const TYPE: IntersectableType = IntersectableType::AxisAlignedBox;
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+
+    fn material_id(&self) -> u32 {
+        self.material_id
+    }
+
+    /// Minimum and maximum corner.
+    fn write_data(&self, data: &mut PrimitiveData) {
+        data.push_vec3(self.min);
+        data.push_vec3(self.max);
+    }
+
+    fn intersect(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let mut entry = f32::NEG_INFINITY;
+        let mut exit = f32::INFINITY;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - origin[axis]) / direction[axis];
+            let t1 = (self.max[axis] - origin[axis]) / direction[axis];
+            entry = entry.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+
+        if entry > exit || exit < 0.0 {
+            None
+        } else if entry >= 0.0 {
+            Some(entry)
+        } else {
+            Some(exit)
+        }
+    }
+}
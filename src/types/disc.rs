@@ -0,0 +1,71 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::acceleration::aabb::Aabb;
+use crate::types::intersectable::{Intersectable, IntersectableType, PrimitiveData};
+use crate::types::light::Light;
+use crate::types::plane::{plane_distance, validate_normal};
+
+/// Flat disc facing along `normal`, two sided.
+pub struct Disc {
+    pub center: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub radius: f32,
+    pub material_id: u32,
+}
+
+impl Disc {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.radius.is_finite() || self.radius <= 0.0 {
+            return Err(format!(
+                "radius {} must be finite and greater than 0",
+                self.radius
+            ));
+        }
+        validate_normal(self.normal)
+    }
+}
+
+impl Intersectable for Disc {
+    const TYPE: IntersectableType = IntersectableType::Disc;
+
+    /// Tight bounds of the circle, along each axis it reaches `radius * sin` of
+    /// the angle between the axis and the normal.
+    fn bounds(&self) -> Aabb {
+        let normal = self.normal.normalize();
+        let extent = Vector3::new(
+            (1.0 - normal.x * normal.x).max(0.0).sqrt(),
+            (1.0 - normal.y * normal.y).max(0.0).sqrt(),
+            (1.0 - normal.z * normal.z).max(0.0).sqrt(),
+        ) * self.radius;
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+
+    fn material_id(&self) -> u32 {
+        self.material_id
+    }
+
+    /// Center, unit normal and radius.
+    fn write_data(&self, data: &mut PrimitiveData) {
+        data.push_vec3(self.center);
+        data.push_vec3(self.normal.normalize());
+        data.push_f32(self.radius);
+    }
+
+    fn intersect(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let distance = plane_distance(self.center, self.normal, origin, direction)?;
+        let offset = origin + direction * distance - self.center;
+        if offset.magnitude2() <= self.radius * self.radius {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    fn light(&self) -> Option<Light> {
+        Some(Light::Disc {
+            center: self.center,
+            normal: self.normal.normalize(),
+            radius: self.radius,
+        })
+    }
+}
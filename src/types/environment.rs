@@ -0,0 +1,378 @@
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use cgmath::{InnerSpace, Vector3};
+use image::codecs::hdr::HdrDecoder;
+use image::imageops::FilterType;
+use image::Rgb32FImage;
+use serde::Deserialize;
+use wgpu::util::DeviceExt;
+
+use crate::bsdf::microfacet::luminance;
+use crate::sampler::distribution::Distribution2D;
+use crate::sky::{daylight, Sky, SunPosition};
+
+/// Largest width of environment maps, wider images are scaled down.
+pub const MAX_ENVIRONMENT_WIDTH: u32 = 4096;
+
+/// `EnvironmentUniform::kind` of every type of environment.
+pub const ENVIRONMENT_GRADIENT: u32 = 0;
+pub const ENVIRONMENT_MAP: u32 = 1;
+pub const ENVIRONMENT_SKY: u32 = 2;
+
+/// Light arriving from rays that leave the scene, a sky gradient unless an
+/// environment map or a physical sky is given. The sky uses `ground_color`
+/// below the horizon.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Environment {
+    pub ground_color: [f32; 3],
+    pub sky_horizon_color: [f32; 3],
+    pub sky_zenith_color: [f32; 3],
+    pub map: Option<EnvironmentMapDescription>,
+    pub sky: Option<Sky>,
+    /// Whether camera rays that leave the scene see the environment, it
+    /// lights the scene either way.
+    pub background: bool,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            ground_color: [0.24, 0.2, 0.18],
+            sky_horizon_color: [0.8, 0.8, 0.8],
+            sky_zenith_color: [0.4, 0.6, 1.0],
+            map: None,
+            sky: None,
+            background: true,
+        }
+    }
+}
+
+impl Environment {
+    /// Whether rays leaving the scene are sampled as a light, the gradient isn't.
+    pub fn is_light(&self) -> bool {
+        self.map.is_some() || self.sky.is_some()
+    }
+
+    /// Luminance of the light falling on a scene of `scene_radius` from
+    /// outside it in total, comparable to `Light::power`. A sky counts as it
+    /// is `hours` after midnight.
+    pub fn power(&self, map: Option<&EnvironmentMap>, scene_radius: f32, hours: f32) -> f32 {
+        // A disc across the scene, facing any way, receives pi times the average radiance
+        let disc = PI * scene_radius * scene_radius;
+        if let (Some(description), Some(map)) = (&self.map, map) {
+            // The distribution's integral is the average of luminance times
+            // sin theta over the image, which spans 2 pi² of longitude and latitude
+            let average = map.distribution.marginal.integral * 2.0 * PI * PI / (4.0 * PI);
+            return disc * PI * average * description.intensity;
+        }
+        if let Some(sky) = &self.sky {
+            let sun = sky.sun_direction(hours);
+            let ground = luminance(self.ground_color.into()) * sky.intensity * daylight(sun);
+
+            // Midpoint rule over the upper hemisphere, the lower one is ground
+            let (steps_theta, steps_phi) = (8, 16);
+            let (mut sum, mut weights) = (0.0, 0.0);
+            for i in 0..steps_theta {
+                let theta = 0.5 * PI * (i as f32 + 0.5) / steps_theta as f32;
+                for j in 0..steps_phi {
+                    let phi = 2.0 * PI * (j as f32 + 0.5) / steps_phi as f32;
+                    let direction = Vector3::new(
+                        theta.sin() * phi.sin(),
+                        theta.cos(),
+                        theta.sin() * phi.cos(),
+                    );
+                    sum += luminance(sky.radiance(direction, sun).into()) * theta.sin();
+                    weights += theta.sin();
+                }
+            }
+            let average = 0.5 * (sum / weights + ground);
+
+            let sun_solid_angle = 2.0 * PI * (1.0 - sky.sun_angular_radius.to_radians().cos());
+            let sun_irradiance = luminance(sky.sun_radiance(sun).into()) * sun_solid_angle;
+            return disc * (PI * average + sun_irradiance);
+        }
+        0.0
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.map.is_some() && self.sky.is_some() {
+            return Err("can't have both a map and a sky".to_string());
+        }
+        if let Some(map) = &self.map {
+            map.validate()?;
+        }
+        if let Some(sky) = &self.sky {
+            sky.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// An equirectangular `.hdr` or `.exr` image around the scene, with +y at
+/// the top and +z in the middle.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnvironmentMapDescription {
+    /// Relative to the scene file.
+    pub path: String,
+    /// Degrees around the y axis.
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+impl EnvironmentMapDescription {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.rotation.is_finite() {
+            return Err(format!("rotation {} must be finite", self.rotation));
+        }
+        if !(self.intensity.is_finite() && self.intensity >= 0.0) {
+            return Err(format!(
+                "intensity {} must be finite and not negative",
+                self.intensity
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The image of an environment map with the distribution its directions are
+/// importance sampled with.
+pub struct EnvironmentMap {
+    pub image: Rgb32FImage,
+    /// Proportional to the luminance of the pixels times the solid angle they
+    /// cover, over the image coordinates.
+    pub distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn load(path: &Path) -> Result<Self, image::ImageError> {
+        let mut image = load_float_image(path)?;
+        if image.width() > MAX_ENVIRONMENT_WIDTH {
+            let height = (image.height() * MAX_ENVIRONMENT_WIDTH / image.width()).max(1);
+            image = image::imageops::resize(
+                &image,
+                MAX_ENVIRONMENT_WIDTH,
+                height,
+                FilterType::Triangle,
+            );
+        }
+        Ok(Self::new(image))
+    }
+
+    pub fn new(image: Rgb32FImage) -> Self {
+        let (width, height) = image.dimensions();
+        let function: Vec<f32> = image
+            .enumerate_pixels()
+            .map(|(_, y, pixel)| {
+                let [r, g, b] = pixel.0;
+                let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+                (0.2126 * r + 0.7152 * g + 0.0722 * b) * sin_theta
+            })
+            .collect();
+        let distribution = Distribution2D::new(&function, width as usize, height as usize);
+        Self {
+            image,
+            distribution,
+        }
+    }
+
+    /// Solid angle pdf of sampling the unit `direction` with the map turned
+    /// by `rotation` degrees.
+    pub fn pdf(&self, direction: Vector3<f32>, rotation: f32) -> f32 {
+        let sin_theta = (1.0 - direction.y * direction.y).max(0.0).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let uv = equirectangular_uv(direction, rotation);
+        self.distribution.pdf(uv) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+/// Loads an image without clamping its values. Radiance `.hdr` files are
+/// decoded directly, `image::open` would clamp them to 8 bits.
+fn load_float_image(path: &Path) -> Result<Rgb32FImage, image::ImageError> {
+    let is_radiance = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+    if !is_radiance {
+        return Ok(image::open(path)?.to_rgb32f());
+    }
+
+    let file = File::open(path).map_err(image::ImageError::IoError)?;
+    let decoder = HdrDecoder::new(BufReader::new(file))?;
+    let metadata = decoder.metadata();
+    let pixels: Vec<f32> = decoder
+        .read_image_hdr()?
+        .into_iter()
+        .flat_map(|pixel| pixel.0)
+        .collect();
+    Ok(
+        Rgb32FImage::from_raw(metadata.width, metadata.height, pixels)
+            .expect("decoder returns a pixel for every position"),
+    )
+}
+
+/// Image coordinates of the unit `direction` on a map turned by `rotation`
+/// degrees, mirrored by `environment_uv` in the shader.
+pub fn equirectangular_uv(direction: Vector3<f32>, rotation: f32) -> [f32; 2] {
+    let u = 0.5 + direction.x.atan2(direction.z) / (2.0 * PI) - rotation / 360.0;
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+    [u - u.floor(), v.min(1.0 - f32::EPSILON)]
+}
+
+/// Unit direction of image coordinates on a map turned by `rotation`
+/// degrees, the inverse of `equirectangular_uv`.
+pub fn equirectangular_direction(uv: [f32; 2], rotation: f32) -> Vector3<f32> {
+    let phi = 2.0 * PI * (uv[0] + rotation / 360.0 - 0.5);
+    let theta = PI * uv[1];
+    Vector3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        theta.sin() * phi.cos(),
+    )
+    .normalize()
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EnvironmentUniform {
+    pub ground_color: [f32; 3],
+    /// One of the `ENVIRONMENT_*` constants.
+    pub kind: u32,
+    pub sky_horizon_color: [f32; 3],
+    /// Turns of the map around the y axis.
+    pub rotation: f32,
+    pub sky_zenith_color: [f32; 3],
+    /// Scales the map or the sky.
+    pub intensity: f32,
+    pub map_width: u32,
+    pub map_height: u32,
+    pub background: u32,
+    /// 1 if the sun follows `GlobalUniform::time_of_day`, else it stays at
+    /// `sun_direction`.
+    pub sun_follows_time: u32,
+    pub sun_direction: [f32; 3],
+    pub turbidity: f32,
+    /// Latitude of the sun path and the azimuth of the sun at noon, in radians.
+    pub latitude: f32,
+    pub sun_azimuth: f32,
+    pub cos_sun_radius: f32,
+    /// One minus `cos_sun_radius`, without its rounding error.
+    pub sun_cone: f32,
+}
+
+pub struct EnvironmentState {
+    pub buffer: wgpu::Buffer,
+    pub uniform: EnvironmentUniform,
+    /// The environment map, a single black pixel without one.
+    pub map_view: wgpu::TextureView,
+    /// The cdfs of `EnvironmentMap::distribution`, see `Distribution2D::to_buffer`.
+    pub distribution_buffer: wgpu::Buffer,
+    /// The environment the state was made from, to weigh a sky again at
+    /// another time of day.
+    pub environment: Environment,
+}
+
+impl EnvironmentState {
+    pub fn new(
+        environment: &Environment,
+        map: Option<&EnvironmentMap>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        let description = environment.map.as_ref().filter(|_| map.is_some());
+        let (map_width, map_height) = map.map_or((1, 1), |map| map.image.dimensions());
+        let mut uniform = EnvironmentUniform {
+            ground_color: environment.ground_color,
+            kind: ENVIRONMENT_GRADIENT,
+            sky_horizon_color: environment.sky_horizon_color,
+            rotation: 0.0,
+            sky_zenith_color: environment.sky_zenith_color,
+            intensity: 1.0,
+            map_width,
+            map_height,
+            background: environment.background as u32,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        if let Some(description) = description {
+            uniform.kind = ENVIRONMENT_MAP;
+            uniform.rotation = description.rotation / 360.0;
+            uniform.intensity = description.intensity;
+        } else if let Some(sky) = &environment.sky {
+            uniform.kind = ENVIRONMENT_SKY;
+            uniform.intensity = sky.intensity;
+            uniform.turbidity = sky.turbidity;
+            uniform.sun_direction = sky.sun_direction(sky.hours()).into();
+            if let SunPosition::TimeOfDay {
+                latitude, azimuth, ..
+            } = sky.sun
+            {
+                uniform.sun_follows_time = 1;
+                uniform.latitude = latitude.to_radians();
+                uniform.sun_azimuth = azimuth.to_radians();
+            }
+            let radius = (sky.sun_angular_radius as f64).to_radians();
+            uniform.cos_sun_radius = radius.cos() as f32;
+            uniform.sun_cone = (2.0 * (radius / 2.0).sin().powi(2)) as f32;
+        }
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Environment Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let pixels: Vec<[f32; 4]> = match map {
+            Some(map) => map
+                .image
+                .pixels()
+                .map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2], 1.0])
+                .collect(),
+            None => vec![[0.0, 0.0, 0.0, 1.0]],
+        };
+        // Read without filtering, the pixels are the bins of the distribution
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Environment Map"),
+                size: wgpu::Extent3d {
+                    width: map_width,
+                    height: map_height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            bytemuck::cast_slice(&pixels),
+        );
+        let map_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let distribution = map.map_or(vec![0.0, 1.0], |map| map.distribution.to_buffer());
+        let distribution_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Environment Distribution Buffer"),
+            contents: bytemuck::cast_slice(&distribution),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        Self {
+            buffer,
+            uniform,
+            map_view,
+            distribution_buffer,
+            environment: environment.clone(),
+        }
+    }
+}
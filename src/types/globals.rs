@@ -1,14 +1,90 @@
 use wgpu::util::DeviceExt;
 
+use crate::sampler::sampler_kind::SamplerKind;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct GlobalUniform {
     pub timestamp: u32,
+    /// Number of frames averaged in the accumulation texture, including the
+    /// one currently being traced.
+    pub frame: u32,
+    /// `SamplerKind` the shader draws its samples from.
+    pub sampler_kind: u32,
+    /// Hours after midnight, moves the sun of skies that follow the time of day.
+    pub time_of_day: f32,
+    /// 1 if direct light at primary hits is resampled with ReSTIR, see
+    /// `restir.wgsl`.
+    pub restir: u32,
+    /// Paths traced per pixel in every frame.
+    pub samples_per_frame: u32,
+    /// Number of surfaces a path bounces off at most.
+    pub max_bounces: u32,
+    /// Bounce after which paths carrying little light are ended by Russian
+    /// roulette.
+    pub roulette_depth: u32,
+}
+
+impl Default for GlobalUniform {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GlobalUniform {
     pub fn new() -> Self {
-        Self { timestamp: 0 }
+        Self {
+            timestamp: 0,
+            frame: 0,
+            sampler_kind: SamplerKind::default() as u32,
+            time_of_day: 12.0,
+            restir: 0,
+            samples_per_frame: RenderSettings::default().samples_per_frame,
+            max_bounces: RenderSettings::default().max_bounces,
+            roulette_depth: RenderSettings::default().roulette_depth,
+        }
+    }
+}
+
+/// Quality settings of the path tracer, they can change between frames
+/// without rebuilding the pipeline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderSettings {
+    /// Paths traced per pixel in every frame, from 1 to `MAX_SAMPLES_PER_FRAME`.
+    pub samples_per_frame: u32,
+    /// Number of surfaces a path bounces off at most, from 1 to `MAX_BOUNCES`.
+    pub max_bounces: u32,
+    /// Bounces every path takes before Russian roulette may end it, paths
+    /// that go on are weighted up by the chance they had to survive.
+    pub roulette_depth: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            samples_per_frame: 10,
+            max_bounces: 5,
+            roulette_depth: 3,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// More paths per frame than this can keep a frame on the GPU long
+    /// enough for the driver to reset it.
+    pub const MAX_SAMPLES_PER_FRAME: u32 = 256;
+    /// Deeper paths hardly carry any light after Russian roulette.
+    pub const MAX_BOUNCES: u32 = 64;
+
+    /// The settings with samples and bounces in their ranges. Roulette
+    /// starting after the last bounce is the same as no roulette, so it is
+    /// kept to `MAX_BOUNCES` as well.
+    pub fn clamped(self) -> Self {
+        Self {
+            samples_per_frame: self.samples_per_frame.clamp(1, Self::MAX_SAMPLES_PER_FRAME),
+            max_bounces: self.max_bounces.clamp(1, Self::MAX_BOUNCES),
+            roulette_depth: self.roulette_depth.min(Self::MAX_BOUNCES),
+        }
     }
 }
 
@@ -30,8 +106,60 @@ impl GlobalState {
         Self { buffer, uniform }
     }
 
+    /// Discards the accumulated frames, the next frame starts a new average.
+    pub fn reset_accumulation(&mut self) {
+        self.uniform.frame = 0;
+    }
+
+    pub fn sampler_kind(&self) -> SamplerKind {
+        SamplerKind::ALL[self.uniform.sampler_kind as usize]
+    }
+
+    /// Switches the sampler, which also restarts the accumulation.
+    pub fn set_sampler_kind(&mut self, sampler_kind: SamplerKind) {
+        self.uniform.sampler_kind = sampler_kind as u32;
+        self.reset_accumulation();
+    }
+
+    /// Moves the sun to `hours` after midnight, which also restarts the accumulation.
+    pub fn set_time_of_day(&mut self, hours: f32) {
+        self.uniform.time_of_day = hours.rem_euclid(24.0);
+        self.reset_accumulation();
+    }
+
+    pub fn restir(&self) -> bool {
+        self.uniform.restir != 0
+    }
+
+    /// Turns reservoir resampling of direct light on or off, which also
+    /// restarts the accumulation.
+    pub fn set_restir(&mut self, restir: bool) {
+        self.uniform.restir = restir as u32;
+        self.reset_accumulation();
+    }
+
+    pub fn render_settings(&self) -> RenderSettings {
+        RenderSettings {
+            samples_per_frame: self.uniform.samples_per_frame,
+            max_bounces: self.uniform.max_bounces,
+            roulette_depth: self.uniform.roulette_depth,
+        }
+    }
+
+    /// Changes the number of samples and bounces, which also restarts the
+    /// accumulation. Settings out of range are clamped, see
+    /// `RenderSettings::clamped`.
+    pub fn set_render_settings(&mut self, settings: RenderSettings) {
+        let settings = settings.clamped();
+        self.uniform.samples_per_frame = settings.samples_per_frame;
+        self.uniform.max_bounces = settings.max_bounces;
+        self.uniform.roulette_depth = settings.roulette_depth;
+        self.reset_accumulation();
+    }
+
     pub fn update(&mut self, queue: &wgpu::Queue) {
         self.uniform.timestamp += 1;
+        self.uniform.frame += 1;
 
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
     }
@@ -0,0 +1,60 @@
+use cgmath::{Matrix4, SquareMatrix};
+
+use crate::acceleration::aabb::Aabb;
+
+/// `InstanceUniform::material_override` of instances that keep the materials of their object.
+pub const NO_MATERIAL_OVERRIDE: u32 = u32::MAX;
+
+/// An object placed in the world. The geometry of the object is stored once,
+/// no matter how many instances use it.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    /// Index of the object, see `IntersectableContainer::finish_object`.
+    pub object: u32,
+    /// Transform from object to world space.
+    pub transform: Matrix4<f32>,
+    /// Material used for every primitive of the object instead of their own.
+    pub material_override: Option<u32>,
+}
+
+impl Instance {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.transform.invert().is_none() {
+            return Err("transform can't be inverted, is a scale zero?".to_string());
+        }
+        Ok(())
+    }
+
+    /// World space bounds of the instance, given the bounds of its object.
+    pub fn bounds(&self, object_bounds: &Aabb) -> Aabb {
+        object_bounds.transform(&self.transform)
+    }
+
+    /// `root_node` is the index of the root of the object's BVH in the node
+    /// buffer, `light_offset` the index of its first light in the light buffer.
+    pub fn uniform(&self, root_node: u32, light_offset: u32) -> InstanceUniform {
+        InstanceUniform {
+            transform: self.transform.into(),
+            inverse_transform: self
+                .transform
+                .invert()
+                .unwrap_or(Matrix4::identity())
+                .into(),
+            root_node,
+            material_override: self.material_override.unwrap_or(NO_MATERIAL_OVERRIDE),
+            light_offset,
+            _padding: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceUniform {
+    pub transform: [[f32; 4]; 4],
+    pub inverse_transform: [[f32; 4]; 4],
+    pub root_node: u32,
+    pub material_override: u32,
+    pub light_offset: u32,
+    pub _padding: u32,
+}
@@ -1,67 +1,504 @@
-// TODO: Generic buffer for all intersectable types possible? Sending generic data to the GPU?
-
+use cgmath::Vector3;
 use wgpu::util::DeviceExt;
 
-struct IntersectableContainer {
-    pub object: Box<dyn Intersectable>,
-    pub intersectable_type: IntersectableType,
-    pub material_id: u32,
+use crate::acceleration::aabb::Aabb;
+use crate::acceleration::bvh::{Bvh, BvhNode, BvhState};
+use crate::sampler::alias::AliasTable;
+use crate::types::instance::{Instance, InstanceUniform};
+use crate::types::light::{Light, LightUniform, NO_LIGHT};
+use crate::types::material::Emission;
+use crate::types::mesh::{Mesh, MeshVertexUniform, Triangle};
+
+/// A primitive the raytracer can intersect.
+///
+/// Every primitive is stored in one buffer with its type tag and an offset
+/// into a shared buffer of 32 bit words, from which the WGSL function of its
+/// type reads whatever it needs. Adding a primitive type means adding an
+/// `IntersectableType` variant with its WGSL source and implementing this trait,
+/// no bindings change.
+pub trait Intersectable {
+    const TYPE: IntersectableType;
+
+    /// Unbounded primitives like planes are kept out of the BVH and tested
+    /// against every ray, their `bounds` are ignored.
+    const BOUNDED: bool = true;
+
+    fn bounds(&self) -> Aabb;
+
+    fn material_id(&self) -> u32;
+
+    /// Appends the data the WGSL intersection function reads, starting at `primitive.data_offset`.
+    fn write_data(&self, data: &mut PrimitiveData);
+
+    /// Distance to the first intersection in front of the ray origin, the same
+    /// test the WGSL function does.
+    fn intersect(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32>;
+
+    /// Shape of the primitive as a light, for types next event estimation can
+    /// sample when their material is emissive.
+    fn light(&self) -> Option<Light> {
+        None
+    }
 }
 
+/// Type tag of a primitive, the shader dispatches on it.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum IntersectableType {
-    Sphere,
+    Sphere = 0,
+    Triangle = 1,
+    Plane = 2,
+    Quad = 3,
+    Disc = 4,
+    AxisAlignedBox = 5,
+}
+
+impl IntersectableType {
+    pub const ALL: [IntersectableType; 6] = [
+        IntersectableType::Sphere,
+        IntersectableType::Triangle,
+        IntersectableType::Plane,
+        IntersectableType::Quad,
+        IntersectableType::Disc,
+        IntersectableType::AxisAlignedBox,
+    ];
+
+    /// Name of the WGSL function intersecting this type, with the signature
+    /// `fn(ray: Ray, primitive: Primitive) -> HitInfo`.
+    pub fn wgsl_function(self) -> &'static str {
+        match self {
+            IntersectableType::Sphere => "intersect_sphere",
+            IntersectableType::Triangle => "intersect_triangle",
+            IntersectableType::Plane => "intersect_plane",
+            IntersectableType::Quad => "intersect_quad",
+            IntersectableType::Disc => "intersect_disc",
+            IntersectableType::AxisAlignedBox => "intersect_box",
+        }
+    }
+
+    /// WGSL source defining `wgsl_function`.
+    pub fn wgsl_source(self) -> &'static str {
+        match self {
+            IntersectableType::Sphere => include_str!("../shader/intersectables/sphere.wgsl"),
+            IntersectableType::Triangle => include_str!("../shader/intersectables/triangle.wgsl"),
+            IntersectableType::Plane => include_str!("../shader/intersectables/plane.wgsl"),
+            IntersectableType::Quad => include_str!("../shader/intersectables/quad.wgsl"),
+            IntersectableType::Disc => include_str!("../shader/intersectables/disc.wgsl"),
+            IntersectableType::AxisAlignedBox => include_str!("../shader/intersectables/box.wgsl"),
+        }
+    }
+}
+
+/// WGSL of every intersectable type and `intersect_primitive`, which calls
+/// the right one for a primitive's type tag. Appended to the raytracer shader.
+pub fn intersectable_wgsl() -> String {
+    let mut source = String::new();
+    let mut cases = String::new();
+    for intersectable_type in IntersectableType::ALL {
+        source.push_str(intersectable_type.wgsl_source());
+        source.push('\n');
+        cases.push_str(&format!(
+            "        case {}u: {{ hitInfo = {}(ray, primitive); }}\n",
+            intersectable_type as u32,
+            intersectable_type.wgsl_function()
+        ));
+    }
+
+    source.push_str(&format!(
+        "fn intersect_primitive(ray: Ray, primitive: Primitive) -> HitInfo {{
+    var hitInfo: HitInfo = no_hit();
+    switch primitive.type_tag {{
+{}        default: {{}}
+    }}
+    return hitInfo;
+}}
+",
+        cases
+    ));
+    source
+}
+
+/// Words of the shared primitive data buffer. Floats are stored by their
+/// bits and read back with `bitcast<f32>`.
+#[derive(Debug, Clone, Default)]
+pub struct PrimitiveData {
+    pub words: Vec<u32>,
+}
+
+impl PrimitiveData {
+    pub fn push_u32(&mut self, value: u32) {
+        self.words.push(value);
+    }
+
+    pub fn push_f32(&mut self, value: f32) {
+        self.words.push(value.to_bits());
+    }
+
+    pub fn push_vec3(&mut self, value: Vector3<f32>) {
+        self.push_f32(value.x);
+        self.push_f32(value.y);
+        self.push_f32(value.z);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PrimitiveUniform {
+    pub type_tag: u32,
+    pub data_offset: u32,
+    pub material_id: u32,
+    /// Index of the primitive among the lights of its object, or `NO_LIGHT`.
+    pub light_index: u32,
+}
+
+/// Collects primitives of any type, grouped into objects that instances
+/// place in the world.
+#[derive(Default)]
+pub struct IntersectableContainer {
+    /// Light emitted by the material with each index, primitives with an
+    /// emissive material become lights if their type supports it.
+    pub material_emission: Vec<Emission>,
+    /// Bounded primitives of all objects, `bounds` holds one box for each.
+    pub primitives: Vec<PrimitiveUniform>,
+    pub bounds: Vec<Aabb>,
+    /// End of every finished object in `primitives`.
+    pub object_ends: Vec<usize>,
+    /// Primitives outside the BVH, see `Intersectable::BOUNDED`. They are
+    /// placed in world space and belong to no object.
+    pub unbounded: Vec<PrimitiveUniform>,
+    pub data: PrimitiveData,
+    /// Vertices shared by the triangles of all meshes.
+    pub vertices: Vec<MeshVertexUniform>,
+    /// Lights of every finished object in object space, with the index of
+    /// their material.
+    pub object_lights: Vec<Vec<(Light, u32)>>,
+    /// Lights pushed since the last finished object.
+    pub lights: Vec<(Light, u32)>,
+}
+
+impl IntersectableContainer {
+    pub fn new(material_emission: Vec<Emission>) -> Self {
+        Self {
+            material_emission,
+            ..Default::default()
+        }
+    }
+
+    pub fn push<T: Intersectable>(&mut self, object: &T) {
+        let data_offset = self.data.words.len() as u32;
+        object.write_data(&mut self.data);
+
+        let emission = self
+            .material_emission
+            .get(object.material_id() as usize)
+            .map_or(0.0, |emission| emission.radiance);
+        let mut light_index = NO_LIGHT;
+        if let Some(light) = object.light().filter(|_| emission > 0.0 && T::BOUNDED) {
+            light_index = self.lights.len() as u32;
+            self.lights.push((light, object.material_id()));
+        }
+
+        let primitive = PrimitiveUniform {
+            type_tag: T::TYPE as u32,
+            data_offset,
+            material_id: object.material_id(),
+            light_index,
+        };
+        if T::BOUNDED {
+            self.primitives.push(primitive);
+            self.bounds.push(object.bounds());
+        } else {
+            self.unbounded.push(primitive);
+        }
+    }
+
+    /// Adds the vertices of the mesh and one primitive per triangle.
+    pub fn push_mesh(&mut self, mesh: &Mesh) {
+        let mut mesh = mesh.clone();
+        if mesh.normals.is_empty() {
+            mesh.compute_normals();
+        }
+
+        let first_vertex = self.vertices.len() as u32;
+        self.vertices.extend(mesh.vertex_uniforms());
+        for index in 0..mesh.triangles.len() {
+            self.push(&Triangle::from_mesh(&mesh, index, first_vertex));
+        }
+    }
+
+    /// Makes the bounded primitives pushed since the last call an object and
+    /// returns its index.
+    pub fn finish_object(&mut self) -> u32 {
+        self.object_ends.push(self.primitives.len());
+        self.object_lights.push(std::mem::take(&mut self.lights));
+        self.object_ends.len() as u32 - 1
+    }
+}
+
+/// Two level acceleration structure over the primitives of a container, as
+/// it is uploaded. Every object gets a bottom level BVH over its primitives,
+/// a top level BVH over the instances sits on top. The size only depends on
+/// the unique geometry, apart from the top level BVH and the instances.
+pub struct IntersectableLayout {
+    /// Primitives of every object in the order of its BVH leaves, followed by
+    /// the unbounded primitives.
+    pub primitives: Vec<PrimitiveUniform>,
+    /// Number of primitives referenced by BVH leaves, the rest are unbounded.
+    pub bvh_primitive_count: usize,
+    /// The top level BVH starting at node 0, followed by the bottom level BVH of
+    /// every object. Top level leaves reference ranges of `instances`, bottom
+    /// level leaves ranges of `primitives`.
+    pub nodes: Vec<BvhNode>,
+    /// Instances in the order of the top level leaves. Instances of objects
+    /// without bounded primitives are left out.
+    pub instances: Vec<InstanceUniform>,
+    pub data: PrimitiveData,
+    pub vertices: Vec<MeshVertexUniform>,
+    /// World space lights of every instance, an instance's lights start at
+    /// its `light_offset`.
+    pub lights: Vec<LightUniform>,
+    /// Luminance of the light every light emits in total, see `Light::power`.
+    pub light_powers: Vec<f32>,
+    /// Picks lights in proportion to their power, with the environment after
+    /// them if it is sampled as a light. Made by `Scene::intersectables` once
+    /// every light is known.
+    pub light_table: AliasTable,
+    /// Radius of a sphere around every instance, which lights far away shine on.
+    pub scene_radius: f32,
+}
+
+impl IntersectableLayout {
+    /// Alias table picking lights by `light_powers`, followed by the
+    /// environment if it has a power, i.e. is sampled as a light.
+    pub fn light_table(light_powers: &[f32], environment_power: Option<f32>) -> AliasTable {
+        let mut weights = light_powers.to_vec();
+        weights.extend(environment_power);
+        AliasTable::new(&weights)
+    }
+
+    /// Bounds of every instance, empty if there are none.
+    pub fn bounds(&self) -> Aabb {
+        if self.instances.is_empty() {
+            Aabb::empty()
+        } else {
+            self.nodes[0].bounds()
+        }
+    }
+
+    /// Bounded primitives pushed after the last finished object form one more object.
+    pub fn build(mut container: IntersectableContainer, instances: &[Instance]) -> Self {
+        if container.object_ends.last().copied().unwrap_or(0) < container.primitives.len() {
+            container.finish_object();
+        }
+
+        // Bottom level, node indices are relative to the first object node until the top level is known
+        let mut primitives: Vec<PrimitiveUniform> = vec![];
+        let mut object_nodes: Vec<BvhNode> = vec![];
+        let mut object_roots: Vec<Option<(u32, Aabb)>> = vec![];
+        let mut object_start = 0;
+        for &object_end in &container.object_ends {
+            let range = object_start..object_end;
+            object_start = object_end;
+            if range.is_empty() {
+                object_roots.push(None);
+                continue;
+            }
+
+            let bvh = Bvh::build(&container.bounds[range.clone()]);
+            let first_primitive = primitives.len() as u32;
+            let first_node = object_nodes.len() as u32;
+            primitives.extend(
+                bvh.primitive_indices
+                    .iter()
+                    .map(|index| container.primitives[range.start + *index as usize]),
+            );
+            object_nodes.extend(bvh.nodes.iter().map(|node| BvhNode {
+                left_or_first: node.left_or_first
+                    + if node.is_leaf() {
+                        first_primitive
+                    } else {
+                        first_node
+                    },
+                ..*node
+            }));
+            object_roots.push(Some((first_node, bvh.nodes[0].bounds())));
+        }
+        let bvh_primitive_count = primitives.len();
+        primitives.extend(&container.unbounded);
+
+        // Top level over every instance of a non-empty object
+        let placed: Vec<(&Instance, u32, Aabb)> = instances
+            .iter()
+            .filter_map(|instance| {
+                let (root, bounds) = object_roots.get(instance.object as usize).copied()??;
+                Some((instance, root, instance.bounds(&bounds)))
+            })
+            .collect();
+        let instance_bounds: Vec<Aabb> = placed.iter().map(|(_, _, bounds)| *bounds).collect();
+        let top_level = Bvh::build(&instance_bounds);
+
+        let first_object_node = top_level.nodes.len() as u32;
+        let mut nodes = top_level.nodes.clone();
+        nodes.extend(object_nodes.iter().map(|node| BvhNode {
+            left_or_first: node.left_or_first + if node.is_leaf() { 0 } else { first_object_node },
+            ..*node
+        }));
+
+        // Instances replacing the materials of their object or distorting its
+        // lights are found by rays only
+        let mut lights = vec![];
+        let mut light_powers = vec![];
+        let mut instance_uniforms = vec![];
+        for index in &top_level.primitive_indices {
+            let (instance, root, _) = placed[*index as usize];
+            let object_lights = &container.object_lights[instance.object as usize];
+            let placed_lights: Option<Vec<(Light, u32)>> = object_lights
+                .iter()
+                .map(|(light, material)| Some((light.transformed(&instance.transform)?, *material)))
+                .collect();
+
+            let mut light_offset = NO_LIGHT;
+            if let Some(placed_lights) = placed_lights.filter(|placed_lights| {
+                !placed_lights.is_empty() && instance.material_override.is_none()
+            }) {
+                light_offset = lights.len() as u32;
+                for (light, material) in &placed_lights {
+                    lights.push(LightUniform {
+                        material: *material,
+                        ..light.uniform()
+                    });
+                    let emission = container.material_emission[*material as usize];
+                    light_powers.push(light.power(emission, 0.0));
+                }
+            }
+            instance_uniforms.push(instance.uniform(root + first_object_node, light_offset));
+        }
+
+        Self {
+            primitives,
+            bvh_primitive_count,
+            nodes,
+            instances: instance_uniforms,
+            data: container.data,
+            vertices: container.vertices,
+            lights,
+            light_powers,
+            light_table: AliasTable::default(),
+            scene_radius: 0.0,
+        }
+    }
 }
 
-pub trait Intersectable {}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PrimitiveMetadataUniform {
+    pub count: u32,
+    /// The first `bvh_count` primitives are referenced by the BVHs, the rest are unbounded.
+    pub bvh_count: u32,
+    pub instance_count: u32,
+    pub light_count: u32,
+    /// Words of the data buffer where the mesh vertices and the light table
+    /// start, after the data of the primitives.
+    pub vertex_offset: u32,
+    pub light_table_offset: u32,
+    pub _padding: [u32; 2],
+}
 
-struct IntersectableState {
-    pub objects: Vec<IntersectableContainer>,
+/// The unified primitive buffer with the instances and their acceleration structure.
+pub struct IntersectableState {
     pub buffer: wgpu::Buffer,
-    pub bind_group: wgpu::BindGroup,
+    pub metadata_buffer: wgpu::Buffer,
+    /// Data of the primitives followed by the mesh vertices and the light
+    /// table, which share it to keep the storage buffers within the default
+    /// limit, see `PrimitiveMetadataUniform`.
+    pub data_buffer: wgpu::Buffer,
+    pub instance_buffer: wgpu::Buffer,
+    pub light_buffer: wgpu::Buffer,
+    pub bvh_state: BvhState,
+    /// Kept to weigh the environment again when it changes, see `write_light_table`.
+    pub light_powers: Vec<f32>,
+    /// Word of the data buffer the light table starts at.
+    pub light_table_offset: u32,
+    pub scene_radius: f32,
 }
 
 impl IntersectableState {
-    pub fn new(objects: Vec<IntersectableContainer>, device: &wgpu::Device) -> Self {
-        // let mut storage_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        //     label: Some("Intersectable Storage Buffer"),
-        //     size: 0,
-        //     usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        //     mapped_at_creation: false,
-        // });
-        let storage_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Intersectable Storage Buffer"),
-            contents: bytemuck::cast_slice(&objects),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        });
+    pub fn new(layout: &IntersectableLayout, device: &wgpu::Device) -> Self {
+        log::info!(
+            "Primitive count: {} ({} unbounded), instance count: {}, light count: {}",
+            layout.primitives.len(),
+            layout.primitives.len() - layout.bvh_primitive_count,
+            layout.instances.len(),
+            layout.lights.len()
+        );
 
-        let object_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("object_bind_group_layout"),
-            });
-
-        let object_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &object_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: storage_buffer.as_entire_binding(),
-            }],
-            label: Some("object_bind_group"),
+        let mut data = layout.data.words.clone();
+        let vertex_offset = data.len() as u32;
+        data.extend_from_slice(bytemuck::cast_slice(&layout.vertices));
+        let light_table_offset = data.len() as u32;
+        data.extend_from_slice(bytemuck::cast_slice(&layout.light_table.entries));
+
+        let metadata = PrimitiveMetadataUniform {
+            count: layout.primitives.len() as u32,
+            bvh_count: layout.bvh_primitive_count as u32,
+            instance_count: layout.instances.len() as u32,
+            light_count: layout.lights.len() as u32,
+            vertex_offset,
+            light_table_offset,
+            _padding: [0; 2],
+        };
+        let metadata_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Primitive Metadata Buffer"),
+            contents: bytemuck::cast_slice(&[metadata]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        // Bindings can't be empty, the metadata counts keep the padding invisible
+        let create_storage_buffer = |label: &str, contents: &[u8]| {
+            let padding = [0u8; std::mem::size_of::<InstanceUniform>()];
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: if contents.is_empty() {
+                    &padding
+                } else {
+                    contents
+                },
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            })
+        };
+        let buffer = create_storage_buffer(
+            "Primitive Storage Buffer",
+            bytemuck::cast_slice(&layout.primitives),
+        );
+        let data_buffer =
+            create_storage_buffer("Primitive Data Buffer", bytemuck::cast_slice(&data));
+        let instance_buffer =
+            create_storage_buffer("Instance Buffer", bytemuck::cast_slice(&layout.instances));
+        let light_buffer =
+            create_storage_buffer("Light Buffer", bytemuck::cast_slice(&layout.lights));
+        let bvh_state = BvhState::new(&layout.nodes, "Primitive BVH Buffer", device);
+
         Self {
-            objects,
-            buffer: storage_buffer,
-            bind_group: object_bind_group,
+            buffer,
+            metadata_buffer,
+            data_buffer,
+            instance_buffer,
+            light_buffer,
+            bvh_state,
+            light_table_offset,
+            light_powers: layout.light_powers.clone(),
+            scene_radius: layout.scene_radius,
         }
     }
+
+    /// Replaces the light table with one weighing the environment by
+    /// `environment_power`. Whether the environment is a light can't change.
+    pub fn write_light_table(&self, queue: &wgpu::Queue, environment_power: Option<f32>) {
+        let table = IntersectableLayout::light_table(&self.light_powers, environment_power);
+        queue.write_buffer(
+            &self.data_buffer,
+            self.light_table_offset as wgpu::BufferAddress * 4,
+            bytemuck::cast_slice(&table.entries),
+        );
+    }
 }
@@ -0,0 +1,281 @@
+use std::f32::consts::PI;
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Transform, Vector3};
+
+use crate::bsdf::microfacet::luminance;
+use crate::types::material::Emission;
+
+/// `LightUniform::kind` of every type of light.
+pub const LIGHT_SPHERE: u32 = 0;
+pub const LIGHT_POINT: u32 = 1;
+pub const LIGHT_SPOT: u32 = 2;
+pub const LIGHT_DIRECTIONAL: u32 = 3;
+pub const LIGHT_QUAD: u32 = 4;
+pub const LIGHT_DISC: u32 = 5;
+
+/// `PrimitiveUniform::light_index` of primitives that aren't sampled as
+/// lights, and `InstanceUniform::light_offset` of instances whose lights aren't.
+pub const NO_LIGHT: u32 = u32::MAX;
+
+/// A light sampled directly by next event estimation. Spheres, quads and
+/// discs are emissive primitives, the others exist only as lights and can't
+/// be seen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Light {
+    Sphere {
+        position: Vector3<f32>,
+        radius: f32,
+    },
+    /// Parallelogram spanned by two edges from a corner, sampled uniformly by area.
+    Quad {
+        corner: Vector3<f32>,
+        edge_u: Vector3<f32>,
+        edge_v: Vector3<f32>,
+    },
+    /// Disc around the unit `normal`, sampled uniformly by area.
+    Disc {
+        center: Vector3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+    },
+    /// Emits `intensity`, in radiance times area, equally in all directions.
+    Point {
+        position: Vector3<f32>,
+        intensity: Vector3<f32>,
+    },
+    /// A point light that only shines in a cone around the unit `direction`.
+    /// It fades out from the cosine of the inner to that of the outer half angle.
+    Spot {
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        intensity: Vector3<f32>,
+        cos_inner: f32,
+        cos_outer: f32,
+    },
+    /// Light from infinitely far away travelling in the unit `direction`, like
+    /// the sun. `irradiance` arrives on a surface facing the light, spread over
+    /// a cone of directions with the cosine of its half angle `cos_angular_radius`.
+    Directional {
+        direction: Vector3<f32>,
+        irradiance: Vector3<f32>,
+        cos_angular_radius: f32,
+    },
+}
+
+impl Light {
+    /// The light placed by an instance transform, `None` when the transform
+    /// changes its shape so it can't be sampled the same way.
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Option<Light> {
+        let place = |position: Vector3<f32>| {
+            transform
+                .transform_point(Point3::from_vec(position))
+                .to_vec()
+        };
+        let turn = |direction: Vector3<f32>| transform.transform_vector(direction).normalize();
+
+        match *self {
+            Light::Sphere { position, radius } => {
+                let scale = uniform_scale(transform)?;
+                Some(Light::Sphere {
+                    position: place(position),
+                    radius: radius * scale,
+                })
+            }
+            // Any affine transform keeps a parallelogram one
+            Light::Quad {
+                corner,
+                edge_u,
+                edge_v,
+            } => Some(Light::Quad {
+                corner: place(corner),
+                edge_u: transform.transform_vector(edge_u),
+                edge_v: transform.transform_vector(edge_v),
+            }),
+            Light::Disc {
+                center,
+                normal,
+                radius,
+            } => {
+                let scale = uniform_scale(transform)?;
+                Some(Light::Disc {
+                    center: place(center),
+                    normal: turn(normal),
+                    radius: radius * scale,
+                })
+            }
+            Light::Point {
+                position,
+                intensity,
+            } => Some(Light::Point {
+                position: place(position),
+                intensity,
+            }),
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                cos_inner,
+                cos_outer,
+            } => Some(Light::Spot {
+                position: place(position),
+                direction: turn(direction),
+                intensity,
+                cos_inner,
+                cos_outer,
+            }),
+            Light::Directional {
+                direction,
+                irradiance,
+                cos_angular_radius,
+            } => Some(Light::Directional {
+                direction: turn(direction),
+                irradiance,
+                cos_angular_radius,
+            }),
+        }
+    }
+
+    /// Luminance of the light emitted in total, to pick bright lights more
+    /// often. Spheres emit `emission` on the outside of their surface, quads
+    /// and discs on their front or on both sides. Directional lights shine on
+    /// a scene of `scene_radius`, the other lights ignore `emission`.
+    pub fn power(&self, emission: Emission, scene_radius: f32) -> f32 {
+        let radiance = emission.radiance;
+        match *self {
+            Light::Sphere { radius, .. } => PI * radiance * 4.0 * PI * radius * radius,
+            Light::Quad { .. } | Light::Disc { .. } => {
+                let sides = if emission.two_sided { 2.0 } else { 1.0 };
+                sides * PI * radiance * self.uniform().area
+            }
+            Light::Point { intensity, .. } => 4.0 * PI * luminance(intensity),
+            // Solid angle of the cone, counting the fading half
+            Light::Spot {
+                intensity,
+                cos_inner,
+                cos_outer,
+                ..
+            } => 2.0 * PI * (1.0 - 0.5 * (cos_inner + cos_outer)) * luminance(intensity),
+            Light::Directional { irradiance, .. } => {
+                PI * scene_radius * scene_radius * luminance(irradiance)
+            }
+        }
+    }
+
+    /// The light as the shader reads it, with the material of emissive
+    /// primitives left zero.
+    pub fn uniform(&self) -> LightUniform {
+        let mut uniform: LightUniform = bytemuck::Zeroable::zeroed();
+        match *self {
+            Light::Sphere { position, radius } => {
+                uniform.kind = LIGHT_SPHERE;
+                uniform.position = position.into();
+                uniform.radius = radius;
+            }
+            Light::Quad {
+                corner,
+                edge_u,
+                edge_v,
+            } => {
+                uniform.kind = LIGHT_QUAD;
+                uniform.position = corner.into();
+                uniform.edge_u = edge_u.into();
+                uniform.edge_v = edge_v.into();
+                uniform.area = edge_u.cross(edge_v).magnitude();
+            }
+            Light::Disc {
+                center,
+                normal,
+                radius,
+            } => {
+                // Any two radius long tangents at right angles span the disc
+                let helper = if normal.x.abs() > 0.9 {
+                    Vector3::unit_y()
+                } else {
+                    Vector3::unit_x()
+                };
+                let tangent = normal.cross(helper).normalize();
+                let bitangent = normal.cross(tangent);
+                uniform.kind = LIGHT_DISC;
+                uniform.position = center.into();
+                uniform.edge_u = (tangent * radius).into();
+                uniform.edge_v = (bitangent * radius).into();
+                uniform.area = PI * radius * radius;
+            }
+            Light::Point {
+                position,
+                intensity,
+            } => {
+                uniform.kind = LIGHT_POINT;
+                uniform.position = position.into();
+                uniform.intensity = intensity.into();
+            }
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                cos_inner,
+                cos_outer,
+            } => {
+                uniform.kind = LIGHT_SPOT;
+                uniform.position = position.into();
+                uniform.direction = direction.into();
+                uniform.intensity = intensity.into();
+                uniform.cos_inner = cos_inner;
+                uniform.cos_outer = cos_outer;
+            }
+            Light::Directional {
+                direction,
+                irradiance,
+                cos_angular_radius,
+            } => {
+                uniform.kind = LIGHT_DIRECTIONAL;
+                uniform.direction = direction.into();
+                uniform.intensity = irradiance.into();
+                uniform.cos_outer = cos_angular_radius;
+            }
+        }
+        uniform
+    }
+}
+
+/// Scale of a transform made of a rotation, a reflection, a uniform scale and
+/// a translation, `None` for any other.
+fn uniform_scale(transform: &Matrix4<f32>) -> Option<f32> {
+    let axes = [transform.x, transform.y, transform.z].map(|axis| axis.truncate());
+    let scale = axes[0].magnitude();
+    let tolerance = 1e-4 * scale;
+    let uniform = axes
+        .iter()
+        .all(|axis| (axis.magnitude() - scale).abs() <= tolerance);
+    let orthogonal = axes[0].dot(axes[1]).abs() <= tolerance * scale
+        && axes[1].dot(axes[2]).abs() <= tolerance * scale
+        && axes[2].dot(axes[0]).abs() <= tolerance * scale;
+    (uniform && orthogonal).then_some(scale)
+}
+
+/// A light as the shader reads it, the fields a kind doesn't use are zero.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    pub position: [f32; 3],
+    pub radius: f32,
+    pub direction: [f32; 3],
+    pub kind: u32,
+    /// Intensity of point and spot lights, irradiance of directional ones.
+    pub intensity: [f32; 3],
+    pub cos_inner: f32,
+    /// Edges of quads and discs from their corner or center at `position`.
+    /// Points on quads are `position + u * edge_u + v * edge_v` for u and v
+    /// from 0 to 1, on discs `position + r * (cos(phi) * edge_u + sin(phi) *
+    /// edge_v)` for r from 0 to 1.
+    pub edge_u: [f32; 3],
+    /// Outer cone of spot lights, angular radius of directional ones.
+    pub cos_outer: f32,
+    pub edge_v: [f32; 3],
+    /// Area of quads and discs.
+    pub area: f32,
+    /// Material of spheres, quads and discs, for the emission ReSTIR expects
+    /// from them.
+    pub material: u32,
+    pub _padding: [u32; 3],
+}
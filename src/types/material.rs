@@ -1,22 +1,199 @@
+use cgmath::{ElementWise, Vector3};
+use serde::Deserialize;
 use wgpu::util::DeviceExt;
 
-unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
-    ::core::slice::from_raw_parts((p as *const T) as *const u8, ::core::mem::size_of::<T>())
-}
+use crate::bsdf::microfacet::luminance;
+use crate::procedural::{ProceduralTexture, NO_PATTERN};
+use crate::types::texture::{average_linear_color, texture_index, TextureReference, TextureSet};
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct Material {
     pub color: [f32; 3],
     pub emission_color: [f32; 3],
     pub emission_strength: f32,
+    /// Whether the back of a surface emits as well as the front. The front of
+    /// quads is the side `edge_u × edge_v` points to, of discs the side of
+    /// their normal, of closed shapes the outside.
+    pub two_sided_emission: bool,
+    /// 0 for dielectrics, 1 for metals, as in glTF's `pbrMetallicRoughness`.
+    pub metallic: f32,
+    /// Perceptual roughness between 0 (mirror) and 1 (fully rough).
+    pub roughness: f32,
+    /// Reflectance of dielectrics at normal incidence, from 0 to 8%. The
+    /// default of 0.5 is the 4% of most plastics and glass. Metals reflect
+    /// their color instead.
+    pub specular: f32,
+    /// Fraction of the light entering a dielectric instead of being diffused,
+    /// 1 for glass and water. Metals never transmit.
+    pub transmission: f32,
+    /// Index of refraction, 1.5 for glass and 1.33 for water.
+    pub ior: f32,
+    /// Color white light takes on after travelling `attenuation_distance`
+    /// through the inside of a transmissive material. Only spheres, boxes and
+    /// closed meshes have an inside, quads, discs, planes and open meshes are
+    /// thin.
+    pub attenuation_color: [f32; 3],
+    pub attenuation_distance: f32,
+    /// Stretches highlights along the surface tangent, from 0 (round) to 1.
+    pub anisotropic: f32,
+    /// Tints the reflection of dielectrics towards the color.
+    pub specular_tint: f32,
+    /// Retroreflective layer seen at grazing angles, as on cloth.
+    pub sheen: f32,
+    /// Tints the sheen towards the color, from white at 0.
+    pub sheen_tint: f32,
+    /// Strength of a clear varnish layer on top of everything else.
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    /// sRGB image the color is multiplied with.
+    pub base_color_texture: Option<TextureReference>,
+    /// Image whose green channel the roughness is multiplied with, so both
+    /// grayscale images and glTF's packed metallic-roughness images work.
+    pub roughness_texture: Option<TextureReference>,
+    /// sRGB image the emission color is multiplied with.
+    pub emission_texture: Option<TextureReference>,
+    /// Tangent space normal map, with green pointing up in the image.
+    pub normal_texture: Option<TextureReference>,
+    /// Pattern blending the color into another one, applied before the
+    /// base color texture.
+    pub procedural_texture: Option<ProceduralTexture>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0],
+            emission_color: [0.0, 0.0, 0.0],
+            emission_strength: 0.0,
+            two_sided_emission: true,
+            metallic: 0.0,
+            roughness: 1.0,
+            specular: 0.5,
+            transmission: 0.0,
+            ior: 1.5,
+            attenuation_color: [1.0, 1.0, 1.0],
+            attenuation_distance: 1.0,
+            anisotropic: 0.0,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            base_color_texture: None,
+            roughness_texture: None,
+            emission_texture: None,
+            normal_texture: None,
+            procedural_texture: None,
+        }
+    }
+}
+
+/// What a material emits, to weigh the lights using it against each other.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Emission {
+    /// Luminance of the light leaving the surface, on each emitting side.
+    pub radiance: f32,
+    pub two_sided: bool,
+}
+
+impl Material {
+    pub fn is_emissive(&self) -> bool {
+        self.emission_strength > 0.0 && self.emission_color.iter().any(|value| *value > 0.0)
+    }
+
+    /// Light emitted by the material, zero if it isn't emissive. Emission
+    /// textures count with their average color.
+    pub fn emission(&self, textures: &TextureSet) -> Emission {
+        if !self.is_emissive() {
+            return Emission::default();
+        }
+        let mut color = Vector3::from(self.emission_color) * self.emission_strength;
+        if let Some(image) = textures.image(texture_index(&self.emission_texture)) {
+            color = color.mul_element_wise(average_linear_color(image));
+        }
+        Emission {
+            radiance: luminance(color),
+            two_sided: self.two_sided_emission,
+        }
+    }
+
+    pub fn textures_mut(&mut self) -> [&mut Option<TextureReference>; 4] {
+        [
+            &mut self.base_color_texture,
+            &mut self.roughness_texture,
+            &mut self.emission_texture,
+            &mut self.normal_texture,
+        ]
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.ior.is_finite() || self.ior <= 0.0 {
+            return Err(format!(
+                "index of refraction {} must be finite and greater than 0",
+                self.ior
+            ));
+        }
+        let fractions = [
+            ("metallic", self.metallic),
+            ("roughness", self.roughness),
+            ("specular", self.specular),
+            ("transmission", self.transmission),
+            ("anisotropic", self.anisotropic),
+            ("specular_tint", self.specular_tint),
+            ("sheen", self.sheen),
+            ("sheen_tint", self.sheen_tint),
+            ("clearcoat", self.clearcoat),
+            ("clearcoat_roughness", self.clearcoat_roughness),
+        ];
+        for (name, value) in fractions {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{} {} must be between 0 and 1", name, value));
+            }
+        }
+        if !self.attenuation_distance.is_finite() || self.attenuation_distance <= 0.0 {
+            return Err(format!(
+                "attenuation distance {} must be finite and greater than 0",
+                self.attenuation_distance
+            ));
+        }
+        if let Some(texture) = &self.procedural_texture {
+            texture.validate()?;
+        }
+        Ok(())
+    }
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct MaterialUniform {
     pub color: [f32; 3],
-    pub _padding: u32,
+    pub metallic: f32,
     pub emission_color: [f32; 3],
     pub emission_strength: f32,
+    pub roughness: f32,
+    pub specular: f32,
+    pub transmission: f32,
+    pub ior: f32,
+    pub attenuation_color: [f32; 3],
+    pub attenuation_distance: f32,
+    pub anisotropic: f32,
+    pub specular_tint: f32,
+    pub sheen: f32,
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub base_color_texture: u32,
+    pub roughness_texture: u32,
+    pub emission_texture: u32,
+    pub normal_texture: u32,
+    pub pattern: u32,
+    pub pattern_scale: f32,
+    pub pattern_color: [f32; 3],
+    pub pattern_octaves: u32,
+    pub pattern_distortion: f32,
+    pub two_sided_emission: u32,
+    pub _padding: [u32; 2],
 }
 
 pub struct MaterialState {
@@ -26,21 +203,55 @@ pub struct MaterialState {
 }
 
 impl MaterialState {
-    pub fn new(materials: &Vec<Material>, device: &wgpu::Device) -> MaterialState {
-        let material_uniforms: Vec<MaterialUniform> = materials
+    pub fn new(materials: &[Material], device: &wgpu::Device) -> MaterialState {
+        let mut material_uniforms: Vec<MaterialUniform> = materials
             .iter()
-            .map(|material| MaterialUniform {
-                color: material.color,
-                _padding: 0,
-                emission_color: material.emission_color,
-                emission_strength: material.emission_strength,
+            .map(|material| {
+                let pattern = material.procedural_texture.unwrap_or_default();
+                MaterialUniform {
+                    color: material.color,
+                    metallic: material.metallic,
+                    emission_color: material.emission_color,
+                    emission_strength: material.emission_strength,
+                    roughness: material.roughness,
+                    specular: material.specular,
+                    transmission: material.transmission,
+                    ior: material.ior,
+                    attenuation_color: material.attenuation_color,
+                    attenuation_distance: material.attenuation_distance,
+                    anisotropic: material.anisotropic,
+                    specular_tint: material.specular_tint,
+                    sheen: material.sheen,
+                    sheen_tint: material.sheen_tint,
+                    clearcoat: material.clearcoat,
+                    clearcoat_roughness: material.clearcoat_roughness,
+                    base_color_texture: texture_index(&material.base_color_texture),
+                    roughness_texture: texture_index(&material.roughness_texture),
+                    emission_texture: texture_index(&material.emission_texture),
+                    normal_texture: texture_index(&material.normal_texture),
+                    pattern: material
+                        .procedural_texture
+                        .map_or(NO_PATTERN, |texture| texture.pattern.id()),
+                    pattern_scale: pattern.scale,
+                    pattern_color: pattern.color,
+                    pattern_octaves: pattern.octaves,
+                    pattern_distortion: pattern.distortion,
+                    two_sided_emission: material.two_sided_emission as u32,
+                    _padding: [0; 2],
+                }
             })
             .collect();
 
+        // Bindings can't be empty, the metadata count keeps the padding invisible
+        let material_count = material_uniforms.len();
+        if material_uniforms.is_empty() {
+            material_uniforms.push(bytemuck::Zeroable::zeroed());
+        }
+
         let material_metadata_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Material Metadata Buffer"),
-                contents: bytemuck::cast_slice(&[material_uniforms.len() as u32]),
+                contents: bytemuck::cast_slice(&[material_count as u32]),
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
 
@@ -50,6 +261,8 @@ impl MaterialState {
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
         });
 
+        material_uniforms.truncate(material_count);
+
         Self {
             buffer: material_buffer,
             metadata_buffer: material_metadata_buffer,
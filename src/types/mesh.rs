@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector2, Vector3, Zero};
+
+use crate::acceleration::aabb::Aabb;
+use crate::types::intersectable::{Intersectable, IntersectableType, PrimitiveData};
+
+/// Indexed triangle mesh. `normals` and `uvs` are either empty or hold one
+/// entry per position, every triangle has its own material.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vector3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub uvs: Vec<Vector2<f32>>,
+    pub triangles: Vec<[u32; 3]>,
+    pub material_ids: Vec<u32>,
+    /// Whether each triangle is part of a surface enclosing a volume, so
+    /// rays hitting its back leave a medium. Empty if no triangle is. Open
+    /// surfaces like planes and cloth have no inside, see
+    /// `Material::attenuation_color`.
+    pub closed: Vec<bool>,
+}
+
+impl Mesh {
+    /// Checks that all attribute and index counts fit together.
+    pub fn validate(&self) -> Result<(), String> {
+        let vertex_count = self.positions.len();
+        if !self.normals.is_empty() && self.normals.len() != vertex_count {
+            return Err(format!(
+                "{} normals for {} positions",
+                self.normals.len(),
+                vertex_count
+            ));
+        }
+        if !self.uvs.is_empty() && self.uvs.len() != vertex_count {
+            return Err(format!(
+                "{} uvs for {} positions",
+                self.uvs.len(),
+                vertex_count
+            ));
+        }
+        if self.material_ids.len() != self.triangles.len() {
+            return Err(format!(
+                "{} material ids for {} triangles",
+                self.material_ids.len(),
+                self.triangles.len()
+            ));
+        }
+        if !self.closed.is_empty() && self.closed.len() != self.triangles.len() {
+            return Err(format!(
+                "{} closed flags for {} triangles",
+                self.closed.len(),
+                self.triangles.len()
+            ));
+        }
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            if let Some(vertex) = triangle
+                .iter()
+                .find(|vertex| **vertex as usize >= vertex_count)
+            {
+                return Err(format!(
+                    "triangle {} references vertex {} but there are only {}",
+                    index, vertex, vertex_count
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks every triangle as closed or open.
+    pub fn set_closed(&mut self, closed: bool) {
+        self.closed = vec![closed; self.triangles.len()];
+    }
+
+    /// Marks the triangles of every part of the mesh without a boundary as
+    /// closed. Parts are triangles connected by edges, a part is closed if
+    /// each of its edges is shared by an even number of triangles. Vertices
+    /// split at uv or normal seams count as one where their positions are
+    /// the same.
+    pub fn find_closed(&mut self) {
+        let key = |index: u32| self.positions[index as usize].map(f32::to_bits);
+        let edge = |start: u32, end: u32| {
+            let (start, end) = (key(start), key(end));
+            if (start.x, start.y, start.z) < (end.x, end.y, end.z) {
+                (start, end)
+            } else {
+                (end, start)
+            }
+        };
+
+        // Triangles sharing an edge are joined into one part
+        let mut parts: Vec<usize> = (0..self.triangles.len()).collect();
+        fn root(parts: &mut [usize], mut index: usize) -> usize {
+            while parts[index] != index {
+                parts[index] = parts[parts[index]];
+                index = parts[index];
+            }
+            index
+        }
+        let mut edges: HashMap<_, (usize, usize)> = HashMap::new();
+        for (index, [a, b, c]) in self.triangles.iter().enumerate() {
+            for (start, end) in [(a, b), (b, c), (c, a)] {
+                let (first, count) = edges.entry(edge(*start, *end)).or_insert((index, 0));
+                *count += 1;
+                let (first, index) = (root(&mut parts, *first), root(&mut parts, index));
+                parts[index] = first;
+            }
+        }
+
+        let mut open_parts = vec![false; self.triangles.len()];
+        for (first, count) in edges.values() {
+            if count % 2 != 0 {
+                open_parts[root(&mut parts, *first)] = true;
+            }
+        }
+        self.closed = (0..self.triangles.len())
+            .map(|index| !open_parts[root(&mut parts, index)])
+            .collect();
+    }
+
+    /// Fills in smooth vertex normals, weighted by the area of the adjacent triangles.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vector3::zero(); self.positions.len()];
+        for [a, b, c] in &self.triangles {
+            let [p0, p1, p2] = [a, b, c].map(|index| self.positions[*index as usize]);
+            // The cross product is twice the triangle area long
+            let face_normal = (p1 - p0).cross(p2 - p0);
+            for index in [a, b, c] {
+                normals[*index as usize] += face_normal;
+            }
+        }
+
+        self.normals = normals
+            .into_iter()
+            .map(|normal: Vector3<f32>| {
+                if normal.magnitude2() > 0.0 {
+                    normal.normalize()
+                } else {
+                    Vector3::unit_y()
+                }
+            })
+            .collect();
+    }
+
+    pub fn triangle_positions(&self, triangle: usize) -> [Vector3<f32>; 3] {
+        self.triangles[triangle].map(|index| self.positions[index as usize])
+    }
+
+    /// Vertices as laid out on the GPU, normals have to be filled in already.
+    pub fn vertex_uniforms(&self) -> impl Iterator<Item = MeshVertexUniform> + '_ {
+        self.positions.iter().enumerate().map(|(index, position)| {
+            let uv = self.uvs.get(index).copied().unwrap_or(Vector2::zero());
+            MeshVertexUniform {
+                position: (*position).into(),
+                u: uv.x,
+                normal: self.normals[index].into(),
+                v: uv.y,
+            }
+        })
+    }
+}
+
+/// Determinant below which a ray counts as parallel to a triangle.
+const TRIANGLE_EPSILON: f32 = 1e-8;
+
+/// Vertex as laid out in the mesh vertex buffer, the uv coordinates fill the
+/// padding after the vec3s.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertexUniform {
+    pub position: [f32; 3],
+    pub u: f32,
+    pub normal: [f32; 3],
+    pub v: f32,
+}
+
+/// Triangle of a mesh whose vertices were uploaded to the shared vertex buffer.
+pub struct Triangle {
+    pub positions: [Vector3<f32>; 3],
+    /// Indices into the shared vertex buffer.
+    pub vertices: [u32; 3],
+    pub material_id: u32,
+    /// Whether the triangle has an inside, see `Mesh::closed`.
+    pub closed: bool,
+}
+
+impl Triangle {
+    /// Triangle `index` of `mesh`, whose vertices start at `first_vertex` in the vertex buffer.
+    pub fn from_mesh(mesh: &Mesh, index: usize, first_vertex: u32) -> Self {
+        Self {
+            positions: mesh.triangle_positions(index),
+            vertices: mesh.triangles[index].map(|vertex| vertex + first_vertex),
+            material_id: mesh.material_ids[index],
+            closed: mesh.closed.get(index).copied().unwrap_or(false),
+        }
+    }
+}
+
+impl Intersectable for Triangle {
+    const TYPE: IntersectableType = IntersectableType::Triangle;
+
+    fn bounds(&self) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for position in self.positions {
+            bounds.grow(position);
+        }
+        bounds
+    }
+
+    fn material_id(&self) -> u32 {
+        self.material_id
+    }
+
+    /// The three vertex indices and 1 if the mesh is closed.
+    fn write_data(&self, data: &mut PrimitiveData) {
+        for vertex in self.vertices {
+            data.push_u32(vertex);
+        }
+        data.push_u32(self.closed as u32);
+    }
+
+    /// Möller–Trumbore intersection.
+    fn intersect(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let [p0, p1, p2] = self.positions;
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+
+        let p = direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < TRIANGLE_EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+
+        let offset_origin = origin - p0;
+        let u = offset_origin.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = offset_origin.cross(edge1);
+        let v = direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = edge2.dot(q) * inverse_determinant;
+        if distance >= 0.0 {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
@@ -1,5 +1,14 @@
+pub mod axis_aligned_box;
+pub mod disc;
+pub mod environment;
 pub mod globals;
+pub mod instance;
+pub mod intersectable;
+pub mod light;
 pub mod material;
+pub mod mesh;
+pub mod plane;
+pub mod quad;
 pub mod sphere;
+pub mod texture;
 pub mod vertex;
-// pub mod intersectable;
@@ -0,0 +1,73 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::acceleration::aabb::Aabb;
+use crate::types::intersectable::{Intersectable, IntersectableType, PrimitiveData};
+
+/// Denominator below which a ray counts as parallel to a plane.
+pub const PLANE_EPSILON: f32 = 1e-8;
+
+/// Infinite plane through `point`. It's two sided, the normal only orients it.
+pub struct Plane {
+    pub point: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub material_id: u32,
+}
+
+impl Plane {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_normal(self.normal)
+    }
+}
+
+impl Intersectable for Plane {
+    const TYPE: IntersectableType = IntersectableType::Plane;
+    const BOUNDED: bool = false;
+
+    fn bounds(&self) -> Aabb {
+        Aabb::empty()
+    }
+
+    fn material_id(&self) -> u32 {
+        self.material_id
+    }
+
+    /// Point and unit normal.
+    fn write_data(&self, data: &mut PrimitiveData) {
+        data.push_vec3(self.point);
+        data.push_vec3(self.normal.normalize());
+    }
+
+    fn intersect(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        plane_distance(self.point, self.normal, origin, direction)
+    }
+}
+
+/// Distance along the ray to the plane through `point`, if it's hit in front of the origin.
+pub fn plane_distance(
+    point: Vector3<f32>,
+    normal: Vector3<f32>,
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> Option<f32> {
+    let denominator = normal.dot(direction);
+    if denominator.abs() < PLANE_EPSILON {
+        return None;
+    }
+
+    let distance = normal.dot(point - origin) / denominator;
+    if distance >= 0.0 {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+/// Normals are normalized on upload, they just need a direction.
+pub fn validate_normal(normal: Vector3<f32>) -> Result<(), String> {
+    let length = normal.magnitude();
+    if !length.is_finite() || length <= 0.0 {
+        let normal: [f32; 3] = normal.into();
+        return Err(format!("normal {:?} has no direction", normal));
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::acceleration::aabb::Aabb;
+use crate::types::intersectable::{Intersectable, IntersectableType, PrimitiveData};
+use crate::types::light::Light;
+use crate::types::plane::plane_distance;
+
+/// Parallelogram spanned by two edges from a corner, two sided. The uv
+/// coordinates run from 0 to 1 along `edge_u` and `edge_v`.
+pub struct Quad {
+    pub corner: Vector3<f32>,
+    pub edge_u: Vector3<f32>,
+    pub edge_v: Vector3<f32>,
+    pub material_id: u32,
+}
+
+impl Quad {
+    pub fn normal(&self) -> Vector3<f32> {
+        self.edge_u.cross(self.edge_v)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let area = self.normal().magnitude();
+        if !area.is_finite() || area <= 0.0 {
+            return Err("edges are parallel or zero, the quad has no area".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Intersectable for Quad {
+    const TYPE: IntersectableType = IntersectableType::Quad;
+
+    fn bounds(&self) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for corner in [
+            self.corner,
+            self.corner + self.edge_u,
+            self.corner + self.edge_v,
+            self.corner + self.edge_u + self.edge_v,
+        ] {
+            bounds.grow(corner);
+        }
+        bounds
+    }
+
+    fn material_id(&self) -> u32 {
+        self.material_id
+    }
+
+    /// Corner and the two edges.
+    fn write_data(&self, data: &mut PrimitiveData) {
+        data.push_vec3(self.corner);
+        data.push_vec3(self.edge_u);
+        data.push_vec3(self.edge_v);
+    }
+
+    fn intersect(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let normal = self.normal();
+        let distance = plane_distance(self.corner, normal, origin, direction)?;
+
+        // Coordinates of the hit point in the basis of the edges
+        let offset = origin + direction * distance - self.corner;
+        let w = normal / normal.dot(normal);
+        let u = w.dot(offset.cross(self.edge_v));
+        let v = w.dot(self.edge_u.cross(offset));
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    fn light(&self) -> Option<Light> {
+        Some(Light::Quad {
+            corner: self.corner,
+            edge_u: self.edge_u,
+            edge_v: self.edge_v,
+        })
+    }
+}
@@ -1,5 +1,8 @@
-use cgmath::Vector3;
-use wgpu::util::DeviceExt;
+use cgmath::{InnerSpace, Vector3};
+
+use crate::acceleration::aabb::Aabb;
+use crate::types::intersectable::{Intersectable, IntersectableType, PrimitiveData};
+use crate::types::light::Light;
 
 pub struct Sphere {
     pub position: Vector3<f32>,
@@ -7,62 +10,46 @@ pub struct Sphere {
     pub material_id: u32,
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct SphereUniform {
-    // pub position_and_radius: [f32; 4],
-    pub position: [f32; 3],
-    pub radius: f32,
-    pub material_id: u32,
-    pub _padding: [u32; 3],
-}
+impl Intersectable for Sphere {
+    const TYPE: IntersectableType = IntersectableType::Sphere;
 
-pub struct SphereStorage {
-    pub spheres: Vec<SphereUniform>,
-    pub sphere_count: u32,
-}
+    fn bounds(&self) -> Aabb {
+        let radius = Vector3::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.position - radius, self.position + radius)
+    }
 
-pub(crate) struct SphereState {
-    // TODO: add lifetime to objects (Do I need them here?)
-    // pub objects: &Vec<Sphere>,
-    // pub storage: SphereStorage,
-    pub buffer: wgpu::Buffer,
-    pub metadata_buffer: wgpu::Buffer,
-    pub uniforms: Vec<SphereUniform>,
-}
+    fn material_id(&self) -> u32 {
+        self.material_id
+    }
 
-impl SphereState {
-    pub fn new(objects: &Vec<Sphere>, device: &wgpu::Device) -> Self {
-        let mut sphere_uniforms: Vec<SphereUniform> = vec![];
+    /// Position and radius.
+    fn write_data(&self, data: &mut PrimitiveData) {
+        data.push_vec3(self.position);
+        data.push_f32(self.radius);
+    }
 
-        for sphere in objects {
-            sphere_uniforms.push(SphereUniform {
-                // position_and_radius: [sphere.position.x, sphere.position.y, sphere.position.z, sphere.radius],
-                position: sphere.position.into(),
-                radius: sphere.radius,
-                material_id: sphere.material_id,
-                _padding: [0, 0, 0],
-            });
-        }
+    fn intersect(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let offset_origin = origin - self.position;
 
-        println!("Sphere count: {:?}", sphere_uniforms);
+        let a = direction.dot(direction);
+        let b = 2.0 * offset_origin.dot(direction);
+        let c = offset_origin.dot(offset_origin) - self.radius * self.radius;
 
-        let sphere_metadata_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Sphere Metadata Buffer"),
-            contents: bytemuck::cast_slice(&[sphere_uniforms.len() as u32]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
 
-        let storage_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Sphere Storage Buffer"),
-            contents: bytemuck::cast_slice(sphere_uniforms.as_slice()),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        });
+        // Rays starting inside hit the far side
+        let near = (-b - discriminant.sqrt()) / (2.0 * a);
+        let far = (-b + discriminant.sqrt()) / (2.0 * a);
+        [near, far].into_iter().find(|distance| *distance >= 0.0)
+    }
 
-        Self {
-            uniforms: sphere_uniforms,
-            buffer: storage_buffer,
-            metadata_buffer: sphere_metadata_buffer,
-        }
+    fn light(&self) -> Option<Light> {
+        Some(Light::Sphere {
+            position: self.position,
+            radius: self.radius,
+        })
     }
 }
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use cgmath::Vector3;
+use image::imageops::FilterType;
+use image::RgbaImage;
+use serde::Deserialize;
+use wgpu::util::DeviceExt;
+
+use crate::scene::scene_error::SceneError;
+
+/// Texture index of material slots without a texture.
+pub const NO_TEXTURE: u32 = u32::MAX;
+
+/// Largest width and height of the layers of each texture array. Images go
+/// into the first array they fit, bigger ones are scaled down to the last.
+pub const TEXTURE_SIZES: [u32; 4] = [256, 512, 1024, 2048];
+
+/// Texture indices hold the texture array in their top byte and the layer below.
+const LAYER_BITS: u32 = 24;
+
+/// A texture of a material, written as its path in scene files. The scene
+/// loader sets `index` to the texture array and layer holding the image.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "String")]
+pub struct TextureReference {
+    pub path: String,
+    pub index: u32,
+}
+
+impl From<String> for TextureReference {
+    fn from(path: String) -> Self {
+        Self {
+            path,
+            index: NO_TEXTURE,
+        }
+    }
+}
+
+/// Index of an optional texture, `NO_TEXTURE` when there is none.
+pub fn texture_index(texture: &Option<TextureReference>) -> u32 {
+    texture.as_ref().map_or(NO_TEXTURE, |texture| texture.index)
+}
+
+/// Texture array holding images of `width` x `height`.
+pub fn texture_array(width: u32, height: u32) -> usize {
+    let size = width.max(height);
+    TEXTURE_SIZES
+        .iter()
+        .position(|&array_size| size <= array_size)
+        .unwrap_or(TEXTURE_SIZES.len() - 1)
+}
+
+/// Average of the colors of an sRGB encoded image, decoded like the shader
+/// does.
+pub fn average_linear_color(image: &RgbaImage) -> Vector3<f32> {
+    let decode = |value: u8| {
+        let value = value as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    // Decoding a byte is cheaper by table
+    let table: Vec<f32> = (0..=255).map(decode).collect();
+
+    let mut sum = Vector3::new(0.0f64, 0.0, 0.0);
+    for pixel in image.pixels() {
+        sum.x += table[pixel[0] as usize] as f64;
+        sum.y += table[pixel[1] as usize] as f64;
+        sum.z += table[pixel[2] as usize] as f64;
+    }
+    let count = (image.width() * image.height()).max(1) as f64;
+    (sum / count).cast().unwrap()
+}
+
+/// The images of all textures of a scene. Every image is loaded once, no
+/// matter how many materials use it.
+#[derive(Debug, Default)]
+pub struct TextureSet {
+    pub images: Vec<RgbaImage>,
+    indices: HashMap<PathBuf, u32>,
+    /// Indices into `images` of the layers of each texture array.
+    layers: [Vec<usize>; TEXTURE_SIZES.len()],
+}
+
+impl TextureSet {
+    /// Loads the image at `path` unless it already was, returns its index.
+    pub fn load(&mut self, path: &Path) -> Result<u32, image::ImageError> {
+        if let Some(index) = self.indices.get(path) {
+            return Ok(*index);
+        }
+
+        let image = image::open(path)?.to_rgba8();
+        let index = self.push(image);
+        self.indices.insert(path.to_path_buf(), index);
+        Ok(index)
+    }
+
+    pub fn push(&mut self, image: RgbaImage) -> u32 {
+        let array = texture_array(image.width(), image.height());
+        let layer = self.layers[array].len() as u32;
+        self.layers[array].push(self.images.len());
+        self.images.push(image);
+        ((array as u32) << LAYER_BITS) | layer
+    }
+
+    /// Image of texture `index`, `None` for `NO_TEXTURE`.
+    pub fn image(&self, index: u32) -> Option<&RgbaImage> {
+        let array = (index >> LAYER_BITS) as usize;
+        let layer = (index & ((1 << LAYER_BITS) - 1)) as usize;
+        let image = *self.layers.get(array)?.get(layer)?;
+        Some(&self.images[image])
+    }
+
+    /// Images in the layers of texture array `array`.
+    pub fn layers(&self, array: usize) -> impl Iterator<Item = &RgbaImage> {
+        self.layers[array].iter().map(|&index| &self.images[index])
+    }
+
+    /// Width and height of the layers of texture array `array`, large enough
+    /// for its largest image up to the size of the array.
+    pub fn layer_size(&self, array: usize) -> (u32, u32) {
+        self.layers(array).fold((1, 1), |(width, height), image| {
+            (
+                width.max(image.width()).min(TEXTURE_SIZES[array]),
+                height.max(image.height()).min(TEXTURE_SIZES[array]),
+            )
+        })
+    }
+}
+
+/// All textures of a scene as the layers of one texture array per size in
+/// `TEXTURE_SIZES`, images are stretched to the size of their array.
+pub struct TextureState {
+    pub views: Vec<wgpu::TextureView>,
+    pub sampler: wgpu::Sampler,
+}
+
+impl TextureState {
+    pub fn new(
+        textures: &TextureSet,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Self, SceneError> {
+        let max_layers = device.limits().max_texture_array_layers;
+        let mut views = Vec::with_capacity(TEXTURE_SIZES.len());
+        for (array, &size) in TEXTURE_SIZES.iter().enumerate() {
+            let mut images: Vec<&RgbaImage> = textures.layers(array).collect();
+            if images.len() as u32 > max_layers {
+                return Err(SceneError::TooManyTextures {
+                    size,
+                    count: images.len(),
+                    limit: max_layers,
+                });
+            }
+            // Bindings can't be empty and GL makes textures with one layer 2D
+            // textures instead of arrays, arrays get white layers nothing refers to
+            let white = RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+            while images.len() < 2 {
+                images.push(&white);
+            }
+
+            let (width, height) = textures.layer_size(array);
+            let mut data = Vec::with_capacity((width * height * 4) as usize * images.len());
+            for image in images.iter() {
+                if image.dimensions() == (width, height) {
+                    data.extend_from_slice(image.as_raw());
+                } else {
+                    let resized =
+                        image::imageops::resize(*image, width, height, FilterType::Triangle);
+                    data.extend_from_slice(resized.as_raw());
+                }
+            }
+
+            let texture = device.create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some("Material Texture Array"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: images.len() as u32,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    // Color textures are sRGB encoded, they're decoded in the shader
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+                &data,
+            );
+            views.push(texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            }));
+        }
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Material Texture Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self { views, sampler })
+    }
+}
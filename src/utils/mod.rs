@@ -1,17 +1,36 @@
 use gif::{Encoder, Frame, Repeat};
+use image::RgbaImage;
 
-pub(crate) fn save_gif(
+/// Encodes `frames` as an endlessly looping GIF.
+///
+/// Every frame gets its own palette, quantized with NeuQuant. `speed` trades
+/// quantization quality (1) for encoding speed (30), `delay` is the time
+/// between frames in hundredths of a second.
+pub fn save_gif(
     path: &str,
-    frames: &mut Vec<Vec<u8>>,
+    frames: &mut [RgbaImage],
     speed: i32,
-    size: u16,
+    delay: u16,
 ) -> Result<(), failure::Error> {
+    let (width, height) = match frames.first() {
+        Some(frame) => frame.dimensions(),
+        None => return Err(failure::err_msg("cannot save a GIF without frames")),
+    };
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(failure::err_msg("GIF frames are limited to 65535x65535"));
+    }
+
     let mut image = std::fs::File::create(path)?;
-    let mut encoder = Encoder::new(&mut image, size, size, &[])?;
+    let mut encoder = Encoder::new(&mut image, width as u16, height as u16, &[])?;
     encoder.set_repeat(Repeat::Infinite)?;
 
-    for mut frame in frames {
-        encoder.write_frame(&Frame::from_rgba_speed(size, size, &mut frame, speed))?;
+    for frame in frames {
+        if frame.dimensions() != (width, height) {
+            return Err(failure::err_msg("all GIF frames must have the same size"));
+        }
+        let mut frame = Frame::from_rgba_speed(width as u16, height as u16, frame, speed);
+        frame.delay = delay;
+        encoder.write_frame(&frame)?;
     }
 
     Ok(())
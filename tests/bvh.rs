@@ -0,0 +1,121 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3};
+use common::Xorshift;
+use wgpu_raytracer::acceleration::aabb::Aabb;
+use wgpu_raytracer::acceleration::bvh::Bvh;
+use wgpu_raytracer::types::intersectable::Intersectable;
+use wgpu_raytracer::types::sphere::Sphere;
+
+fn random_spheres(rng: &mut Xorshift, count: usize) -> Vec<Sphere> {
+    (0..count)
+        .map(|index| Sphere {
+            position: rng.vector(-50.0, 50.0),
+            radius: rng.range(0.1, 3.0),
+            material_id: index as u32,
+        })
+        .collect()
+}
+
+fn brute_force_hit(
+    spheres: &[Sphere],
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> Option<(u32, f32)> {
+    let mut closest: Option<(u32, f32)> = None;
+    for (index, sphere) in spheres.iter().enumerate() {
+        if let Some(distance) = sphere.intersect(origin, direction) {
+            if closest.is_none_or(|hit| distance < hit.1) {
+                closest = Some((index as u32, distance));
+            }
+        }
+    }
+    closest
+}
+
+#[test]
+fn bvh_matches_brute_force_closest_hit() {
+    let mut rng = Xorshift(0x1234_5678);
+    let spheres = random_spheres(&mut rng, 1000);
+    let bounds: Vec<Aabb> = spheres.iter().map(Sphere::bounds).collect();
+    let bvh = Bvh::build(&bounds);
+
+    let mut hits = 0;
+    for _ in 0..2000 {
+        let origin = rng.vector(-60.0, 60.0);
+        let direction = rng.vector(-1.0, 1.0).normalize();
+
+        let expected = brute_force_hit(&spheres, origin, direction);
+        let actual = bvh.closest_hit(origin, direction, |index| {
+            spheres[index as usize].intersect(origin, direction)
+        });
+
+        assert_eq!(
+            expected, actual,
+            "ray from {:?} along {:?}",
+            origin, direction
+        );
+        if expected.is_some() {
+            hits += 1;
+        }
+    }
+    // Make sure the comparison isn't trivially passing on misses
+    assert!(hits > 100, "only {} rays hit a sphere", hits);
+}
+
+#[test]
+fn bvh_references_every_primitive_once() {
+    let mut rng = Xorshift(0x9e37_79b9);
+    let spheres = random_spheres(&mut rng, 1000);
+    let bounds: Vec<Aabb> = spheres.iter().map(Sphere::bounds).collect();
+    let bvh = Bvh::build(&bounds);
+
+    let mut indices = bvh.primitive_indices.clone();
+    indices.sort_unstable();
+    assert_eq!(indices, (0..spheres.len() as u32).collect::<Vec<_>>());
+
+    let leaf_primitives: u32 = bvh
+        .nodes
+        .iter()
+        .filter(|node| node.is_leaf())
+        .map(|node| node.count)
+        .sum();
+    assert_eq!(leaf_primitives as usize, spheres.len());
+}
+
+#[test]
+fn bvh_of_nothing_is_never_hit() {
+    let bvh = Bvh::build(&[]);
+    let hit = bvh.closest_hit(
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(0.0, 0.0, 1.0),
+        |_| Some(1.0),
+    );
+    assert_eq!(hit, None);
+}
+
+#[test]
+fn rays_in_the_plane_of_a_face_hit_the_box() {
+    let bounds = Aabb {
+        min: Vector3::new(-1.0, -1.0, -1.0),
+        max: Vector3::new(1.0, 1.0, 1.0),
+    };
+    let direction = Vector3::new(0.0, 0.0, 1.0);
+    let inverse_direction = Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+
+    // 0 * inf in the slabs of x and y
+    for origin in [Vector3::new(-1.0, 0.0, -5.0), Vector3::new(1.0, 1.0, -5.0)] {
+        assert_eq!(
+            bounds.intersect(origin, inverse_direction, f32::INFINITY),
+            Some(4.0)
+        );
+    }
+    assert_eq!(
+        bounds.intersect(
+            Vector3::new(1.5, 0.0, -5.0),
+            inverse_direction,
+            f32::INFINITY
+        ),
+        None
+    );
+}
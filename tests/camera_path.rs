@@ -0,0 +1,143 @@
+use image::RgbaImage;
+use wgpu_raytracer::camera::camera_path::CameraPath;
+use wgpu_raytracer::renderer::animation::default_animation;
+use wgpu_raytracer::scene::scene_file::CameraDescription;
+use wgpu_raytracer::utils::save_gif;
+
+fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+    }
+}
+
+fn keyframe(eye: [f32; 3], fovy: f32) -> CameraDescription {
+    CameraDescription {
+        eye,
+        fovy,
+        ..CameraDescription::default()
+    }
+}
+
+#[test]
+fn turntables_loop_and_keyframes_end_on_the_last_key() {
+    let turntable = CameraPath::Turntable {
+        target: [0.0; 3],
+        radius: 1.0,
+        height: 0.0,
+        revolutions: 1.0,
+    };
+    let keyframes = CameraPath::Keyframes(vec![]);
+
+    assert_eq!(turntable.frame_time(0, 4), 0.0);
+    assert_eq!(turntable.frame_time(3, 4), 0.75);
+    assert_eq!(keyframes.frame_time(0, 5), 0.0);
+    assert_eq!(keyframes.frame_time(4, 5), 1.0);
+    // A single frame doesn't divide by zero
+    assert_eq!(turntable.frame_time(0, 0), 0.0);
+    assert_eq!(keyframes.frame_time(0, 1), 0.0);
+}
+
+#[test]
+fn turntables_circle_the_target() {
+    let path = CameraPath::Turntable {
+        target: [1.0, 0.0, 2.0],
+        radius: 3.0,
+        height: 0.5,
+        revolutions: 1.0,
+    };
+    let base = CameraDescription {
+        fovy: 40.0,
+        ..CameraDescription::default()
+    };
+
+    let start = path.camera_at(0.0, &base);
+    // Starts behind the target looking along +z, like the default camera
+    assert_close(start.eye, [1.0, 0.5, -1.0]);
+    assert_close(path.camera_at(0.25, &base).eye, [-2.0, 0.5, 2.0]);
+    assert_close(path.camera_at(0.5, &base).eye, [1.0, 0.5, 5.0]);
+    assert_eq!(start.target, [1.0, 0.0, 2.0]);
+    assert_eq!(start.fovy, 40.0);
+}
+
+#[test]
+fn keyframes_are_interpolated_linearly() {
+    let path = CameraPath::Keyframes(vec![
+        keyframe([0.0, 0.0, 0.0], 40.0),
+        keyframe([2.0, 0.0, 0.0], 60.0),
+        keyframe([2.0, 4.0, 0.0], 80.0),
+    ]);
+    let base = CameraDescription::default();
+
+    assert_close(path.camera_at(0.0, &base).eye, [0.0, 0.0, 0.0]);
+    assert_close(path.camera_at(0.25, &base).eye, [1.0, 0.0, 0.0]);
+    assert_close(path.camera_at(0.75, &base).eye, [2.0, 2.0, 0.0]);
+    assert_close(path.camera_at(1.0, &base).eye, [2.0, 4.0, 0.0]);
+    assert_eq!(path.camera_at(0.25, &base).fovy, 50.0);
+    // Times outside the path stay on its ends
+    assert_close(path.camera_at(1.5, &base).eye, [2.0, 4.0, 0.0]);
+}
+
+#[test]
+fn paths_without_enough_keys_hold_still() {
+    let base = keyframe([1.0, 2.0, 3.0], 45.0);
+
+    let empty = CameraPath::Keyframes(vec![]).camera_at(0.5, &base);
+    assert_eq!(empty.eye, base.eye);
+
+    let single = CameraPath::Keyframes(vec![keyframe([4.0, 0.0, 0.0], 30.0)]).camera_at(0.5, &base);
+    assert_eq!(single.eye, [4.0, 0.0, 0.0]);
+    assert_eq!(single.fovy, 30.0);
+}
+
+#[test]
+fn default_animation_circles_at_the_camera_distance() {
+    let camera = CameraDescription {
+        eye: [3.0, 1.0, 4.0],
+        target: [0.0, 0.0, 0.0],
+        ..CameraDescription::default()
+    };
+    let animation = default_animation(&camera);
+
+    assert_eq!(animation.frames, 36);
+    match animation.path {
+        CameraPath::Turntable { radius, height, .. } => {
+            assert!((radius - 5.0).abs() < 1e-5);
+            assert_eq!(height, 1.0);
+        }
+        CameraPath::Keyframes(_) => panic!("expected a turntable"),
+    }
+}
+
+#[test]
+fn gifs_contain_every_frame() {
+    let path = std::env::temp_dir().join("wgpu_raytracer_frames.gif");
+    let path = path.to_string_lossy();
+    let mut frames: Vec<RgbaImage> = (0..3)
+        .map(|i| RgbaImage::from_pixel(8, 4, image::Rgba([i * 100, 0, 0, 255])))
+        .collect();
+
+    save_gif(&path, &mut frames, 10, 4).unwrap();
+
+    let file = std::fs::File::open(path.as_ref()).unwrap();
+    let mut decoder = gif::DecodeOptions::new().read_info(file).unwrap();
+    assert_eq!((decoder.width(), decoder.height()), (8, 4));
+    let mut count = 0;
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        assert_eq!(frame.delay, 4);
+        count += 1;
+    }
+    assert_eq!(count, 3);
+    std::fs::remove_file(path.as_ref()).unwrap();
+}
+
+#[test]
+fn gifs_need_frames_of_one_size() {
+    let path = std::env::temp_dir().join("wgpu_raytracer_invalid.gif");
+    let path = path.to_string_lossy();
+
+    assert!(save_gif(&path, &mut [], 10, 4).is_err());
+
+    let mut frames = vec![RgbaImage::new(8, 4), RgbaImage::new(4, 8)];
+    assert!(save_gif(&path, &mut frames, 10, 4).is_err());
+    let _ = std::fs::remove_file(path.as_ref());
+}
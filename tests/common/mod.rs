@@ -0,0 +1,44 @@
+// Each test crate uses a different part of these helpers
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+
+use cgmath::{Vector2, Vector3};
+
+/// Deterministic uniform numbers in [0, 1), the tests don't need a good generator.
+pub struct Xorshift(pub u32);
+
+impl Xorshift {
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Uniform number in [min, max).
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next()
+    }
+
+    /// Point in the cube from `min` to `max` on every axis.
+    pub fn vector(&mut self, min: f32, max: f32) -> Vector3<f32> {
+        Vector3::new(
+            self.range(min, max),
+            self.range(min, max),
+            self.range(min, max),
+        )
+    }
+
+    pub fn next_2d(&mut self) -> Vector2<f32> {
+        Vector2::new(self.next(), self.next())
+    }
+
+    /// Uniform direction on the hemisphere above the surface.
+    pub fn hemisphere(&mut self) -> Vector3<f32> {
+        let z = self.next().max(1e-3);
+        let phi = 2.0 * PI * self.next();
+        let r = (1.0 - z * z).sqrt();
+        Vector3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+}
@@ -0,0 +1,172 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
+use image::{Rgb, Rgb32FImage};
+use wgpu_raytracer::sampler::distribution::{Distribution1D, Distribution2D};
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+use wgpu_raytracer::types::environment::{
+    equirectangular_direction, equirectangular_uv, EnvironmentMap,
+};
+
+mod common;
+use common::Xorshift;
+
+#[test]
+fn samples_follow_the_function() {
+    let function = [1.0, 0.0, 3.0, 4.0];
+    let distribution = Distribution1D::new(&function);
+    assert_eq!(distribution.cdf, vec![0.0, 0.125, 0.125, 0.5, 1.0]);
+    assert_eq!(distribution.integral, 2.0);
+
+    let mut rng = Xorshift(7);
+    let mut counts = [0; 4];
+    for _ in 0..80000 {
+        let (x, pdf) = distribution.sample(rng.next());
+        let bin = (x * 4.0) as usize;
+        counts[bin] += 1;
+        assert!((pdf - distribution.pdf(x)).abs() < 1e-5);
+    }
+    // Empty bins are never picked
+    assert_eq!(counts[1], 0);
+    for (count, value) in counts.iter().zip(function) {
+        let expected = 80000.0 * value / 8.0;
+        assert!((*count as f32 - expected).abs() < 0.03 * 80000.0);
+    }
+
+    // Nothing to sample from, every bin is equally likely
+    let uniform = Distribution1D::new(&[0.0, 0.0]);
+    assert_eq!(uniform.cdf, vec![0.0, 0.5, 1.0]);
+    assert_eq!(uniform.pdf(0.7), 1.0);
+}
+
+#[test]
+fn image_distribution_is_normalized_and_consistent() {
+    let (width, height) = (8, 4);
+    let function: Vec<f32> = (0..width * height)
+        .map(|i| ((i * 7) % 5) as f32 + if i == 9 { 40.0 } else { 0.0 })
+        .collect();
+    let distribution = Distribution2D::new(&function, width, height);
+    assert_eq!(
+        (distribution.width(), distribution.height()),
+        (width, height)
+    );
+    assert_eq!(
+        distribution.to_buffer().len(),
+        (height + 1) + height * (width + 1)
+    );
+
+    // The density integrates to 1 and is proportional to the function
+    let total: f32 = function.iter().sum();
+    let mut integral = 0.0;
+    for y in 0..height {
+        for x in 0..width {
+            let point = [
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            ];
+            let pdf = distribution.pdf(point);
+            let expected = function[y * width + x] * (width * height) as f32 / total;
+            assert!((pdf - expected).abs() < 1e-4, "{} != {}", pdf, expected);
+            integral += pdf / (width * height) as f32;
+        }
+    }
+    assert!((integral - 1.0).abs() < 1e-4);
+
+    let mut rng = Xorshift(3);
+    for _ in 0..1000 {
+        let (point, pdf) = distribution.sample([rng.next(), rng.next()]);
+        assert!(pdf > 0.0);
+        assert!((pdf - distribution.pdf(point)).abs() < 1e-3 * pdf);
+    }
+}
+
+#[test]
+fn equirectangular_coordinates_round_trip() {
+    // +z is in the middle of the image and +y at the top
+    let middle = equirectangular_uv(Vector3::new(0.0, 0.0, 1.0), 0.0);
+    assert!((middle[0] - 0.5).abs() < 1e-6 && (middle[1] - 0.5).abs() < 1e-6);
+    assert!(equirectangular_uv(Vector3::new(0.0, 1.0, 0.0), 0.0)[1] < 1e-6);
+    // Turning the map by 90 degrees moves its middle from +z to +x
+    let turned = equirectangular_direction([0.5, 0.5], 90.0);
+    assert!((turned - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+
+    let mut rng = Xorshift(11);
+    for rotation in [0.0, 35.0, -120.0] {
+        for _ in 0..100 {
+            let uv = [rng.next(), 0.02 + 0.96 * rng.next()];
+            let direction = equirectangular_direction(uv, rotation);
+            let back = equirectangular_uv(direction, rotation);
+            let du = (back[0] - uv[0]).abs();
+            assert!(du.min(1.0 - du) < 1e-4 && (back[1] - uv[1]).abs() < 1e-4);
+        }
+    }
+}
+
+#[test]
+fn environment_pdf_integrates_to_one_over_the_sphere() {
+    let image = Rgb32FImage::from_fn(32, 16, |x, y| {
+        if (x, y) == (20, 5) {
+            Rgb([50.0, 40.0, 30.0])
+        } else {
+            Rgb([0.2, 0.3, 0.4 + x as f32 * 0.01])
+        }
+    });
+    let map = EnvironmentMap::new(image);
+
+    // Midpoint rule over the sphere in latitude and longitude
+    let (steps_theta, steps_phi) = (256, 512);
+    let mut integral = 0.0;
+    for i in 0..steps_theta {
+        let theta = PI * (i as f32 + 0.5) / steps_theta as f32;
+        for j in 0..steps_phi {
+            let phi = 2.0 * PI * (j as f32 + 0.5) / steps_phi as f32;
+            let direction = Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            let solid_angle =
+                theta.sin() * (PI / steps_theta as f32) * (2.0 * PI / steps_phi as f32);
+            integral += map.pdf(direction, 30.0) * solid_angle;
+        }
+    }
+    assert!((integral - 1.0).abs() < 0.01, "{}", integral);
+
+    // The bright pixel is the most likely direction
+    let bright = equirectangular_direction([20.5 / 32.0, 5.5 / 16.0], 0.0);
+    let dim = equirectangular_direction([4.5 / 32.0, 5.5 / 16.0], 0.0);
+    assert!(map.pdf(bright, 0.0) > 100.0 * map.pdf(dim, 0.0));
+}
+
+#[test]
+fn environment_maps_are_loaded_in_high_dynamic_range() {
+    let scene = Scene::load("scenes/environment.ron").unwrap();
+    let map = scene.environment_map.unwrap();
+    let brightest = map
+        .image
+        .pixels()
+        .map(|pixel| pixel.0[0])
+        .fold(0.0, f32::max);
+    assert!(brightest > 1.0);
+    assert!(scene.environment.background);
+
+    let scene = Scene::parse("Scene(environment: (background: false))").unwrap();
+    assert!(scene.environment_map.is_none());
+    assert!(!scene.environment.background);
+}
+
+#[test]
+fn rejects_invalid_environment_maps() {
+    let result = Scene::parse(r#"Scene(environment: (map: Some((path: "missing.hdr"))))"#);
+    match result {
+        Err(SceneError::EnvironmentMap { path, .. }) => assert_eq!(path, "missing.hdr"),
+        Err(error) => panic!("expected an environment map error, got {}", error),
+        Ok(_) => panic!("expected an environment map error"),
+    }
+
+    let result = Scene::parse(
+        r#"Scene(environment: (map: Some((path: "scenes/environments/sunset.hdr", intensity: -1.0))))"#,
+    );
+    assert!(matches!(result, Err(SceneError::InvalidEnvironment { .. })));
+}
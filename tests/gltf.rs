@@ -0,0 +1,69 @@
+use wgpu_raytracer::import::gltf::{load_gltf, DEFAULT_MATERIAL_NAME};
+
+fn box_model() -> wgpu_raytracer::import::gltf::GltfModel {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/models/box.gltf");
+    load_gltf(path).unwrap()
+}
+
+#[test]
+fn flattens_nodes_into_world_space() {
+    let model = box_model();
+
+    // Box and ground quad
+    assert_eq!(model.mesh.triangles.len(), 12 + 2);
+    assert!(model.mesh.validate().is_ok());
+    // The box has an inside, the ground doesn't
+    assert_eq!(model.mesh.closed[..12], [true; 12]);
+    assert_eq!(model.mesh.closed[12..], [false; 2]);
+
+    // The box is moved up by its parent node so it rests on the ground
+    let box_positions = &model.mesh.positions[..24];
+    let lowest = box_positions
+        .iter()
+        .map(|p| p.y)
+        .fold(f32::INFINITY, f32::min);
+    let highest = box_positions
+        .iter()
+        .map(|p| p.y)
+        .fold(f32::NEG_INFINITY, f32::max);
+    assert!(lowest.abs() < 1e-5 && (highest - 1.0).abs() < 1e-5);
+
+    // and rotated around y, so no face is axis aligned in x any more
+    assert!(box_positions.iter().all(|p| (p.x.abs() - 0.5).abs() > 0.01));
+    assert!(model.mesh.normals.iter().all(|normal| (normal.x * normal.x
+        + normal.y * normal.y
+        + normal.z * normal.z
+        - 1.0)
+        .abs()
+        < 1e-5));
+}
+
+#[test]
+fn maps_pbr_materials() {
+    let model = box_model();
+
+    assert_eq!(
+        model.material_names,
+        vec!["red metal", DEFAULT_MATERIAL_NAME]
+    );
+    let red_metal = &model.materials[0];
+    assert_eq!(red_metal.color, [0.9, 0.2, 0.1]);
+    assert_eq!(red_metal.metallic, 1.0);
+    assert_eq!(red_metal.roughness, 0.3);
+    assert_eq!(red_metal.emission_strength, 0.0);
+
+    assert!(model.mesh.material_ids[..12].iter().all(|id| *id == 0));
+    assert!(model.mesh.material_ids[12..].iter().all(|id| *id == 1));
+}
+
+#[test]
+fn uses_the_first_camera() {
+    let camera = box_model().camera.expect("the box scene has a camera");
+
+    assert_eq!(camera.eye, [0.0, 1.7, 4.0]);
+    assert!((camera.fovy - 0.8f32.to_degrees()).abs() < 1e-4);
+    // Looking down -Z, tilted towards the ground
+    assert!(camera.target[2] < camera.eye[2]);
+    assert!(camera.target[1] < camera.eye[1]);
+    assert!(camera.up[1] > 0.9);
+}
@@ -0,0 +1,488 @@
+use std::sync::OnceLock;
+
+use wgpu_raytracer::renderer::headless::HeadlessRenderer;
+use wgpu_raytracer::sampler::reservoir::ReservoirUniform;
+use wgpu_raytracer::scene::scene_file::Scene;
+use wgpu_raytracer::types::globals::RenderSettings;
+
+/// Whether there is a fallback adapter to render on. Without one the tests
+/// only print a message, since they can't check anything.
+fn has_adapter() -> bool {
+    static HAS_ADAPTER: OnceLock<bool> = OnceLock::new();
+    *HAS_ADAPTER.get_or_init(|| {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: true,
+        }));
+        if adapter.is_none() {
+            eprintln!("no graphics adapter found, skipping the headless tests");
+        }
+        adapter.is_some()
+    })
+}
+
+/// Renderer on the fallback adapter, `None` if there is no adapter.
+fn headless_renderer(scene: &Scene, width: u32, height: u32) -> Option<HeadlessRenderer> {
+    if !has_adapter() {
+        return None;
+    }
+    Some(
+        pollster::block_on(HeadlessRenderer::new(scene, width, height, true))
+            .unwrap_or_else(|error| panic!("failed to create the headless renderer: {}", error)),
+    )
+}
+
+#[test]
+fn renders_default_scene_on_fallback_adapter() {
+    let scene = Scene::default();
+    let Some(mut renderer) = headless_renderer(&scene, 64, 48) else {
+        return;
+    };
+
+    let image = renderer.render().unwrap();
+
+    assert_eq!(image.dimensions(), (64, 48));
+    assert!(image.pixels().all(|pixel| pixel[3] == 255));
+    assert!(image
+        .pixels()
+        .any(|pixel| pixel[0] > 0 || pixel[1] > 0 || pixel[2] > 0));
+}
+
+#[test]
+fn renders_with_restir() {
+    let scene = Scene::parse(
+        r#"Scene(
+            materials: {
+                "white": (),
+                "light": (emission_color: (1.0, 1.0, 1.0), emission_strength: 5.0),
+            },
+            spheres: [
+                (position: (0.0, 0.0, 0.0), radius: 1.0, material: "white"),
+                (position: (2.0, 2.0, 0.0), radius: 0.5, material: "light"),
+            ],
+            lights: [Point(position: (-2.0, 2.0, 0.0), intensity: 10.0)],
+        )"#,
+    )
+    .unwrap();
+    let Some(mut renderer) = headless_renderer(&scene, 32, 24) else {
+        return;
+    };
+    renderer.raytracer.global_state.set_restir(true);
+
+    // Later frames reuse the reservoirs of the ones before
+    let image = renderer.render_samples(3).unwrap();
+
+    assert_eq!(image.dimensions(), (32, 24));
+    assert!(image
+        .pixels()
+        .any(|pixel| pixel[0] > 0 || pixel[1] > 0 || pixel[2] > 0));
+}
+
+/// Reads back the accumulation texture the last frame wrote to.
+fn read_accumulation(renderer: &HeadlessRenderer) -> Vec<f32> {
+    let raytracer = &renderer.raytracer;
+    // Bind group i reads texture i and writes the other one
+    let texture = &raytracer.accumulation_textures
+        [1 - (raytracer.global_state.uniform.timestamp % 2) as usize];
+    let bytes_per_row = raytracer.width * 16;
+    assert_eq!(bytes_per_row % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT, 0);
+
+    let buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (bytes_per_row * raytracer.height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = renderer
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        texture.size(),
+    );
+    renderer.queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| ());
+    renderer.device.poll(wgpu::Maintain::Wait);
+    let values = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    values
+}
+
+#[test]
+fn resets_discard_nans_in_the_accumulation() {
+    let Some(mut renderer) = headless_renderer(&Scene::default(), 32, 24) else {
+        return;
+    };
+
+    // A NaN sample in the average, e.g. from a degenerate pdf
+    let nans = vec![f32::NAN; 32 * 24 * 4];
+    for texture in &renderer.raytracer.accumulation_textures {
+        renderer.queue.write_texture(
+            texture.as_image_copy(),
+            bytemuck::cast_slice(&nans),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(32 * 16),
+                rows_per_image: None,
+            },
+            texture.size(),
+        );
+    }
+
+    // Starts a new average, which must not mix in the old one
+    renderer.render_samples(1).unwrap();
+    assert!(read_accumulation(&renderer)
+        .iter()
+        .all(|value| value.is_finite()));
+
+    // Later frames average with the cleared one
+    renderer.render_samples(4).unwrap();
+    assert!(read_accumulation(&renderer)
+        .iter()
+        .all(|value| value.is_finite()));
+}
+
+#[test]
+fn open_surfaces_absorb_nothing_from_behind() {
+    // Two emitting quads with an absorbing inside, the right one faces away
+    let scene = Scene::parse(
+        r#"Scene(
+            environment: (
+                ground_color: (0.0, 0.0, 0.0),
+                sky_horizon_color: (0.0, 0.0, 0.0),
+                sky_zenith_color: (0.0, 0.0, 0.0),
+            ),
+            materials: {
+                "murky light": (
+                    color: (0.0, 0.0, 0.0),
+                    emission_color: (1.0, 1.0, 1.0),
+                    emission_strength: 1.0,
+                    attenuation_color: (0.01, 0.01, 0.01),
+                    attenuation_distance: 0.01,
+                ),
+            },
+            quads: [
+                (corner: (-2.0, -0.75, 0.0), edge_u: (0.0, 1.5, 0.0), edge_v: (1.5, 0.0, 0.0), material: "murky light"),
+                (corner: (0.5, -0.75, 0.0), edge_u: (1.5, 0.0, 0.0), edge_v: (0.0, 1.5, 0.0), material: "murky light"),
+            ],
+        )"#,
+    )
+    .unwrap();
+    let Some(mut renderer) = headless_renderer(&scene, 32, 24) else {
+        return;
+    };
+
+    let image = renderer.render_samples(1).unwrap();
+
+    let half_brightness = |columns: std::ops::Range<u32>| -> u32 {
+        columns
+            .flat_map(|x| (0..24).map(move |y| (x, y)))
+            .map(|(x, y)| image.get_pixel(x, y)[0] as u32)
+            .sum()
+    };
+    let (left, right) = (half_brightness(0..16), half_brightness(16..32));
+    assert!(left > 0 && right > 0, "{} {}", left, right);
+    assert!(
+        left.abs_diff(right) * 10 < left.max(right),
+        "front {} and back {} differ",
+        left,
+        right
+    );
+}
+
+#[test]
+fn textures_of_different_sizes_are_sampled_from_their_arrays() {
+    // A small red and a large green emission texture, in different arrays
+    let directory = std::env::temp_dir();
+    let red = directory.join("wgpu_raytracer_red.png");
+    let green = directory.join("wgpu_raytracer_green.png");
+    image::RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255]))
+        .save(&red)
+        .unwrap();
+    image::RgbaImage::from_pixel(600, 300, image::Rgba([0, 255, 0, 255]))
+        .save(&green)
+        .unwrap();
+    let scene = Scene::parse(&format!(
+        r#"Scene(
+            environment: (
+                ground_color: (0.0, 0.0, 0.0),
+                sky_horizon_color: (0.0, 0.0, 0.0),
+                sky_zenith_color: (0.0, 0.0, 0.0),
+            ),
+            materials: {{
+                "red": (color: (0.0, 0.0, 0.0), emission_color: (1.0, 1.0, 1.0), emission_strength: 1.0, emission_texture: Some({:?})),
+                "green": (color: (0.0, 0.0, 0.0), emission_color: (1.0, 1.0, 1.0), emission_strength: 1.0, emission_texture: Some({:?})),
+            }},
+            quads: [
+                (corner: (-2.0, -0.75, 0.0), edge_u: (0.0, 1.5, 0.0), edge_v: (1.5, 0.0, 0.0), material: "red"),
+                (corner: (0.5, -0.75, 0.0), edge_u: (0.0, 1.5, 0.0), edge_v: (1.5, 0.0, 0.0), material: "green"),
+            ],
+        )"#,
+        red.to_string_lossy(),
+        green.to_string_lossy()
+    ))
+    .unwrap();
+    std::fs::remove_file(red).unwrap();
+    std::fs::remove_file(green).unwrap();
+    let Some(mut renderer) = headless_renderer(&scene, 32, 24) else {
+        return;
+    };
+
+    let image = renderer.render_samples(1).unwrap();
+
+    // The red quad is at -x, on the left of the image
+    let left = image.get_pixel(11, 12);
+    let right = image.get_pixel(20, 12);
+    assert!(left[0] > 100 && left[1] == 0, "{:?}", left);
+    assert!(right[1] > 100 && right[0] == 0, "{:?}", right);
+}
+
+#[test]
+fn reports_more_textures_than_an_array_holds() {
+    let count = wgpu::Limits::default().max_texture_array_layers + 1;
+    let mut scene = Scene::default();
+    for _ in 0..count {
+        scene.textures.push(image::RgbaImage::new(1, 1));
+    }
+
+    if !has_adapter() {
+        return;
+    }
+    let error = match pollster::block_on(HeadlessRenderer::new(&scene, 8, 8, true)) {
+        Ok(_) => panic!("expected too many textures"),
+        Err(error) => error.to_string(),
+    };
+    assert!(
+        error.contains(&format!("scene has {} textures", count)),
+        "{}",
+        error
+    );
+}
+
+/// Sum of the red, green and blue of every pixel, averaged over 64 frames
+/// of 2 paths per pixel with ReSTIR on or off.
+fn restir_brightness(renderer: &mut HeadlessRenderer, restir: bool) -> f32 {
+    let global_state = &mut renderer.raytracer.global_state;
+    global_state.set_render_settings(RenderSettings {
+        samples_per_frame: 2,
+        ..RenderSettings::default()
+    });
+    global_state.set_restir(restir);
+    renderer.render_samples(64).unwrap();
+    read_accumulation(renderer)
+        .chunks(4)
+        .map(|pixel| pixel[0] + pixel[1] + pixel[2])
+        .sum()
+}
+
+#[test]
+fn restir_agrees_with_next_event_estimation() {
+    // A floor under one sphere light, lit only by it
+    let scene = Scene::parse(
+        r#"Scene(
+            environment: (
+                ground_color: (0.0, 0.0, 0.0),
+                sky_horizon_color: (0.0, 0.0, 0.0),
+                sky_zenith_color: (0.0, 0.0, 0.0),
+            ),
+            materials: {
+                "white": (),
+                "light": (emission_color: (1.0, 1.0, 1.0), emission_strength: 4.0),
+            },
+            quads: [
+                (corner: (-4.0, -1.0, -2.0), edge_u: (8.0, 0.0, 0.0), edge_v: (0.0, 0.0, 8.0), material: "white"),
+            ],
+            spheres: [(position: (0.5, 1.5, 1.0), radius: 0.5, material: "light")],
+        )"#,
+    )
+    .unwrap();
+    let Some(mut renderer) = headless_renderer(&scene, 32, 24) else {
+        return;
+    };
+    let reservoir_size = std::mem::size_of::<ReservoirUniform>() as u64;
+    assert_eq!(
+        renderer.raytracer.reservoir_state.buffer.size(),
+        2 * 32 * 24 * reservoir_size
+    );
+
+    let (nee, restir) = (
+        restir_brightness(&mut renderer, false),
+        restir_brightness(&mut renderer, true),
+    );
+
+    assert!(nee > 0.0);
+    assert!(
+        (restir - nee).abs() < 0.05 * nee,
+        "ReSTIR {} and next event estimation {} differ",
+        restir,
+        nee
+    );
+}
+
+#[test]
+fn restir_agrees_with_next_event_estimation_for_every_kind_of_light() {
+    // Area, point, spot and directional lights and the sky as candidates
+    let scene = Scene::parse(
+        r#"Scene(
+            materials: {
+                "white": (),
+                "rough": (metallic: 1.0, roughness: 0.4),
+                "light": (emission_color: (1.0, 0.8, 0.6), emission_strength: 4.0),
+            },
+            quads: [
+                (corner: (-4.0, -1.0, -2.0), edge_u: (8.0, 0.0, 0.0), edge_v: (0.0, 0.0, 8.0), material: "white"),
+                (corner: (-2.0, 1.5, 2.0), edge_u: (1.0, 0.0, 0.0), edge_v: (0.0, 0.0, 1.0), material: "light"),
+            ],
+            spheres: [
+                (position: (0.5, 1.5, 1.0), radius: 0.3, material: "light"),
+                (position: (-0.5, -0.5, 1.0), radius: 0.5, material: "rough"),
+            ],
+            lights: [
+                Point(position: (1.5, 1.0, 0.0), color: (0.2, 0.4, 1.0), intensity: 2.0),
+                Spot(position: (-1.0, 2.0, 0.0), direction: (0.0, -1.0, 0.0), intensity: 4.0, outer_angle: 40.0),
+                Directional(direction: (0.3, -1.0, 0.5), intensity: 0.5, angular_radius: 2.0),
+            ],
+        )"#,
+    )
+    .unwrap();
+    let Some(mut renderer) = headless_renderer(&scene, 32, 24) else {
+        return;
+    };
+
+    let (nee, restir) = (
+        restir_brightness(&mut renderer, false),
+        restir_brightness(&mut renderer, true),
+    );
+
+    assert!(nee > 0.0);
+    assert!(
+        (restir - nee).abs() < 0.05 * nee,
+        "ReSTIR {} and next event estimation {} differ",
+        restir,
+        nee
+    );
+}
+
+#[test]
+fn russian_roulette_keeps_the_mean_radiance() {
+    // A bright box around a small light, so light bounces around many times
+    let scene = Scene::parse(
+        r#"Scene(
+            environment: (
+                ground_color: (0.0, 0.0, 0.0),
+                sky_horizon_color: (0.0, 0.0, 0.0),
+                sky_zenith_color: (0.0, 0.0, 0.0),
+            ),
+            materials: {
+                "walls": (color: (0.8, 0.6, 0.4)),
+                "light": (emission_color: (1.0, 1.0, 1.0), emission_strength: 8.0),
+            },
+            boxes: [(min: (-3.0, -3.0, -8.0), max: (3.0, 3.0, 3.0), material: "walls")],
+            spheres: [(position: (0.0, 2.0, 1.0), radius: 0.3, material: "light")],
+        )"#,
+    )
+    .unwrap();
+    let Some(mut renderer) = headless_renderer(&scene, 32, 24) else {
+        return;
+    };
+
+    let mut brightness = |roulette_depth: u32| -> f32 {
+        renderer
+            .raytracer
+            .global_state
+            .set_render_settings(RenderSettings {
+                samples_per_frame: 4,
+                max_bounces: 8,
+                roulette_depth,
+            });
+        renderer.render_samples(16).unwrap();
+        read_accumulation(&renderer)
+            .chunks(4)
+            .map(|pixel| pixel[0] + pixel[1] + pixel[2])
+            .sum()
+    };
+    let (full, roulette) = (brightness(RenderSettings::MAX_BOUNCES), brightness(0));
+
+    assert!(full > 0.0);
+    assert!(
+        (roulette - full).abs() < 0.05 * full,
+        "with roulette {} and without {} differ",
+        roulette,
+        full
+    );
+}
+
+#[test]
+fn open_meshes_absorb_and_refract_nothing_from_behind() {
+    // Two murky glass panes in front of a light, the right one faces away
+    let scene = Scene::parse(
+        r#"Scene(
+            environment: (
+                ground_color: (0.0, 0.0, 0.0),
+                sky_horizon_color: (0.0, 0.0, 0.0),
+                sky_zenith_color: (0.0, 0.0, 0.0),
+            ),
+            materials: {
+                "murky glass": (
+                    transmission: 1.0,
+                    roughness: 0.0,
+                    ior: 1.5,
+                    attenuation_color: (0.01, 0.01, 0.01),
+                    attenuation_distance: 0.01,
+                ),
+                "light": (emission_color: (1.0, 1.0, 1.0), emission_strength: 1.0, two_sided_emission: true),
+            },
+            quads: [
+                (corner: (-6.0, -6.0, 2.0), edge_u: (12.0, 0.0, 0.0), edge_v: (0.0, 12.0, 0.0), material: "light"),
+            ],
+            meshes: [
+                (
+                    positions: [(-2.0, -0.75, 0.0), (-0.5, -0.75, 0.0), (-0.5, 0.75, 0.0), (-2.0, 0.75, 0.0)],
+                    indices: [(0, 2, 1), (0, 3, 2)],
+                    material: "murky glass",
+                ),
+                (
+                    positions: [(0.5, -0.75, 0.0), (2.0, -0.75, 0.0), (2.0, 0.75, 0.0), (0.5, 0.75, 0.0)],
+                    indices: [(0, 1, 2), (0, 2, 3)],
+                    material: "murky glass",
+                ),
+            ],
+        )"#,
+    )
+    .unwrap();
+    let Some(mut renderer) = headless_renderer(&scene, 32, 24) else {
+        return;
+    };
+
+    let image = renderer.render_samples(4).unwrap();
+
+    // The centers of the panes, whose sides differ in nothing but their winding
+    let pane_brightness = |columns: std::ops::Range<u32>| -> u32 {
+        columns
+            .flat_map(|x| (9..15).map(move |y| (x, y)))
+            .map(|(x, y)| image.get_pixel(x, y)[0] as u32)
+            .sum()
+    };
+    let (left, right) = (pane_brightness(9..13), pane_brightness(19..23));
+    assert!(left > 0 && right > 0, "{} {}", left, right);
+    assert!(
+        left.abs_diff(right) * 10 < left.max(right),
+        "front {} and back {} differ",
+        left,
+        right
+    );
+}
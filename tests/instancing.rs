@@ -0,0 +1,200 @@
+use cgmath::{Deg, Matrix4, Vector3};
+use wgpu_raytracer::acceleration::aabb::Aabb;
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+use wgpu_raytracer::types::instance::NO_MATERIAL_OVERRIDE;
+use wgpu_raytracer::types::intersectable::IntersectableLayout;
+
+/// A scene with one sphere in the world and `count` instances of a two triangle mesh.
+fn instanced_scene(count: usize) -> Scene {
+    let instances: Vec<String> = (0..count)
+        .map(|index| {
+            format!(
+                "(object: \"quad\", translation: ({}.0, 0.0, 0.0), rotation: (0.0, {}.0, 0.0))",
+                index * 2,
+                index * 10
+            )
+        })
+        .collect();
+    Scene::parse(&format!(
+        r#"Scene(
+            materials: {{ "white": (), "red": (color: (1.0, 0.0, 0.0)) }},
+            spheres: [(position: (0.0, 5.0, 0.0), radius: 1.0, material: "white")],
+            objects: {{
+                "quad": (
+                    meshes: [(
+                        positions: [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (1.0, 1.0, 0.0), (0.0, 1.0, 0.0)],
+                        indices: [(0, 1, 2), (0, 2, 3)],
+                        material: "white",
+                    )],
+                ),
+            }},
+            instances: [{}],
+        )"#,
+        instances.join(", ")
+    ))
+    .unwrap()
+}
+
+/// Number of nodes in the BVH starting at `root`.
+fn subtree_size(layout: &IntersectableLayout, root: usize) -> usize {
+    let node = &layout.nodes[root];
+    if node.is_leaf() {
+        1
+    } else {
+        let left = node.left_or_first as usize;
+        1 + subtree_size(layout, left) + subtree_size(layout, left + 1)
+    }
+}
+
+#[test]
+fn geometry_is_stored_once_per_object() {
+    let one = instanced_scene(1).intersectables();
+    let many = instanced_scene(500).intersectables();
+
+    // The world is an instance of its own
+    assert_eq!(one.instances.len(), 2);
+    assert_eq!(many.instances.len(), 501);
+
+    assert_eq!(one.primitives.len(), 3);
+    assert_eq!(many.primitives.len(), 3);
+    assert_eq!(one.data.words, many.data.words);
+    assert_eq!(one.vertices.len(), many.vertices.len());
+
+    // Only the top level grows with the instances
+    let top_level = |layout: &IntersectableLayout| subtree_size(layout, 0);
+    assert_eq!(
+        one.nodes.len() - top_level(&one),
+        many.nodes.len() - top_level(&many)
+    );
+}
+
+#[test]
+fn leaves_reference_valid_ranges() {
+    let layout = instanced_scene(64).intersectables();
+    let top_level_size = subtree_size(&layout, 0);
+
+    let mut instances = vec![];
+    let mut stack = vec![0];
+    while let Some(index) = stack.pop() {
+        let node = &layout.nodes[index];
+        let first = node.left_or_first as usize;
+        if node.is_leaf() {
+            instances.extend(first..first + node.count as usize);
+        } else {
+            assert!(first + 1 < top_level_size);
+            stack.extend([first, first + 1]);
+        }
+    }
+    instances.sort_unstable();
+    assert_eq!(instances, (0..layout.instances.len()).collect::<Vec<_>>());
+
+    for instance in &layout.instances {
+        let root = instance.root_node as usize;
+        assert!(root >= top_level_size);
+
+        let mut stack = vec![root];
+        while let Some(index) = stack.pop() {
+            let node = &layout.nodes[index];
+            let first = node.left_or_first as usize;
+            if node.is_leaf() {
+                assert!(first + node.count as usize <= layout.bvh_primitive_count);
+            } else {
+                assert!(first >= top_level_size && first + 1 < layout.nodes.len());
+                stack.extend([first, first + 1]);
+            }
+        }
+    }
+}
+
+#[test]
+fn transformed_bounds_contain_the_rotated_box() {
+    let bounds = Aabb::new(Vector3::new(-1.0, 0.0, -1.0), Vector3::new(1.0, 1.0, 1.0));
+    let transform = Matrix4::from_translation(Vector3::new(10.0, 0.0, 0.0))
+        * Matrix4::from_angle_y(Deg(45.0))
+        * Matrix4::from_scale(2.0);
+    let transformed = bounds.transform(&transform);
+
+    let reach = 2.0 * 2.0f32.sqrt();
+    assert!((transformed.min.x - (10.0 - reach)).abs() < 1e-5);
+    assert!((transformed.max.x - (10.0 + reach)).abs() < 1e-5);
+    assert!((transformed.max.z - reach).abs() < 1e-5);
+    assert!(transformed.min.y.abs() < 1e-5);
+    assert!((transformed.max.y - 2.0).abs() < 1e-5);
+    assert!(Aabb::empty().transform(&transform).is_empty());
+}
+
+#[test]
+fn instances_override_materials() {
+    let scene = Scene::parse(
+        r#"Scene(
+            materials: { "white": (), "red": (color: (1.0, 0.0, 0.0)) },
+            objects: { "ball": (spheres: [(position: (0.0, 0.0, 0.0), radius: 1.0, material: "white")]) },
+            instances: [
+                (object: "ball"),
+                (object: "ball", translation: (3.0, 0.0, 0.0), material: Some("red")),
+            ],
+        )"#,
+    )
+    .unwrap();
+    let red = scene
+        .material_names
+        .iter()
+        .position(|name| name == "red")
+        .unwrap() as u32;
+
+    let mut overrides: Vec<u32> = scene
+        .intersectables()
+        .instances
+        .iter()
+        .map(|instance| instance.material_override)
+        .collect();
+    overrides.sort_unstable();
+    // The world is empty, so only the balls have instances
+    assert_eq!(overrides, vec![red, NO_MATERIAL_OVERRIDE]);
+}
+
+#[test]
+fn reports_invalid_instances() {
+    let parse = |instances: &str| {
+        Scene::parse(&format!(
+            r#"Scene(
+                materials: {{ "white": () }},
+                objects: {{ "ball": (spheres: [(position: (0.0, 0.0, 0.0), radius: 1.0, material: "white")]) }},
+                instances: [{}],
+            )"#,
+            instances
+        ))
+    };
+
+    assert!(matches!(
+        parse(r#"(object: "ball"), (object: "cube")"#),
+        Err(SceneError::UnknownObject { instance: 1, .. })
+    ));
+    assert!(matches!(
+        parse(r#"(object: "ball", scale: (1.0, 0.0, 1.0))"#),
+        Err(SceneError::InvalidInstance { instance: 0, .. })
+    ));
+    assert!(matches!(
+        parse(r#"(object: "ball", material: Some("gold"))"#),
+        Err(SceneError::UnknownMaterial { .. })
+    ));
+
+    let error = Scene::parse(
+        r#"Scene(
+            materials: { "white": () },
+            objects: { "ball": (spheres: [(position: (0.0, 0.0, 0.0), radius: 0.0, material: "white")]) },
+        )"#,
+    );
+    match error {
+        Err(SceneError::InObject { object, error }) => {
+            assert_eq!(object, "ball");
+            assert!(matches!(
+                *error,
+                SceneError::InvalidRadius { sphere: 0, .. }
+            ));
+        }
+        Err(error) => panic!("expected an error in the object, got {}", error),
+        Ok(_) => panic!("expected an error in the object"),
+    }
+}
@@ -0,0 +1,362 @@
+use cgmath::{Angle, Deg, InnerSpace, Vector3};
+use std::f32::consts::PI;
+use wgpu_raytracer::sampler::alias::AliasTable;
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+use wgpu_raytracer::types::intersectable::IntersectableLayout;
+
+use wgpu_raytracer::types::light::{Light, LightUniform, LIGHT_DISC, LIGHT_QUAD, NO_LIGHT};
+
+mod common;
+use common::Xorshift;
+
+fn layout(source: &str) -> IntersectableLayout {
+    Scene::parse(source).unwrap().intersectables()
+}
+
+fn sphere_light(position: [f32; 3], radius: f32) -> LightUniform {
+    Light::Sphere {
+        position: position.into(),
+        radius,
+    }
+    .uniform()
+}
+
+#[test]
+fn emissive_spheres_become_lights() {
+    let layout = layout(
+        r#"Scene(
+            materials: {
+                "white": (),
+                "light": (emission_color: (1.0, 1.0, 1.0), emission_strength: 5.0),
+                "dark": (emission_color: (1.0, 1.0, 1.0), emission_strength: 0.0),
+            },
+            spheres: [
+                (position: (0.0, 0.0, 0.0), radius: 1.0, material: "white"),
+                (position: (0.0, 3.0, 0.0), radius: 0.5, material: "light"),
+                (position: (2.0, 0.0, 0.0), radius: 1.0, material: "dark"),
+            ],
+            // Unbounded primitives can't be sampled
+            planes: [(point: (0.0, -1.0, 0.0), normal: (0.0, 1.0, 0.0), material: "light")],
+        )"#,
+    );
+
+    // Materials are numbered by name, "light" comes second
+    let light = LightUniform {
+        material: 1,
+        ..sphere_light([0.0, 3.0, 0.0], 0.5)
+    };
+    assert_eq!(layout.lights, vec![light]);
+    let light_indices: Vec<u32> = layout
+        .primitives
+        .iter()
+        .map(|primitive| primitive.light_index)
+        .filter(|index| *index != NO_LIGHT)
+        .collect();
+    assert_eq!(light_indices, vec![0]);
+    assert_eq!(layout.instances[0].light_offset, 0);
+}
+
+#[test]
+fn instances_place_their_lights() {
+    let layout = layout(
+        r#"Scene(
+            materials: {
+                "white": (),
+                "light": (emission_color: (1.0, 1.0, 1.0), emission_strength: 5.0),
+            },
+            objects: {
+                "lamp": (spheres: [
+                    (position: (0.0, 1.0, 0.0), radius: 0.5, material: "light"),
+                    (position: (0.0, 0.0, 0.0), radius: 0.5, material: "white"),
+                ]),
+            },
+            instances: [
+                (object: "lamp", translation: (5.0, 0.0, 0.0), scale: (2.0, 2.0, 2.0)),
+                // Neither an ellipsoid nor a replaced material is sampled
+                (object: "lamp", scale: (1.0, 3.0, 1.0)),
+                (object: "lamp", translation: (0.0, 0.0, 5.0), material: Some("white")),
+            ],
+        )"#,
+    );
+
+    assert_eq!(layout.lights, vec![sphere_light([5.0, 2.0, 0.0], 1.0)]);
+    let offsets: Vec<u32> = layout
+        .instances
+        .iter()
+        .map(|instance| instance.light_offset)
+        .collect();
+    assert_eq!(offsets.iter().filter(|offset| **offset == 0).count(), 1);
+    assert_eq!(
+        offsets.iter().filter(|offset| **offset == NO_LIGHT).count(),
+        2
+    );
+}
+
+#[test]
+fn analytic_lights_are_parsed() {
+    let scene = Scene::parse(
+        r#"Scene(
+            lights: [
+                Point(position: (0.0, 2.0, 0.0), color: (1.0, 0.5, 0.0), intensity: 4.0),
+                Spot(position: (0.0, 3.0, 0.0), direction: (0.0, -2.0, 0.0), inner_angle: 10.0, outer_angle: 20.0),
+                Directional(direction: (0.0, -1.0, 1.0), intensity: 2.0, angular_radius: 0.5),
+            ],
+        )"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        scene.lights[0],
+        Light::Point {
+            position: Vector3::new(0.0, 2.0, 0.0),
+            intensity: Vector3::new(4.0, 2.0, 0.0),
+        }
+    );
+    assert_eq!(
+        scene.lights[1],
+        Light::Spot {
+            position: Vector3::new(0.0, 3.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+            intensity: Vector3::new(1.0, 1.0, 1.0),
+            cos_inner: Deg(10.0).cos(),
+            cos_outer: Deg(20.0).cos(),
+        }
+    );
+    assert_eq!(
+        scene.lights[2],
+        Light::Directional {
+            direction: Vector3::new(0.0, -1.0, 1.0).normalize(),
+            irradiance: Vector3::new(2.0, 2.0, 2.0),
+            cos_angular_radius: Deg(0.5).cos(),
+        }
+    );
+
+    // Sampled after the emissive primitives, which there are none of
+    let layout = scene.intersectables();
+    let lights: Vec<LightUniform> = scene.lights.iter().map(Light::uniform).collect();
+    assert_eq!(layout.lights, lights);
+}
+
+#[test]
+fn rejects_invalid_lights() {
+    let invalid_light = |light: &str| {
+        let source = format!(
+            "Scene(lights: [Point(position: (0.0, 0.0, 0.0)), {}])",
+            light
+        );
+        match Scene::parse(&source) {
+            Err(SceneError::InvalidLight { light, .. }) => light,
+            Err(error) => panic!("expected an invalid light, got {}", error),
+            Ok(_) => panic!("expected an invalid light"),
+        }
+    };
+
+    assert_eq!(
+        invalid_light("Spot(position: (0.0, 0.0, 0.0), direction: (0.0, -1.0, 0.0), inner_angle: 30.0, outer_angle: 20.0)"),
+        1
+    );
+    assert_eq!(invalid_light("Directional(direction: (0.0, 0.0, 0.0))"), 1);
+    assert_eq!(
+        invalid_light("Point(position: (0.0, 0.0, 0.0), intensity: -1.0)"),
+        1
+    );
+}
+
+#[test]
+fn quads_and_discs_become_area_lights() {
+    let scene = Scene::parse(
+        r#"Scene(
+            materials: {
+                "softbox": (emission_color: (1.0, 1.0, 1.0), emission_strength: 4.0, two_sided_emission: false),
+                "light": (emission_color: (1.0, 1.0, 1.0), emission_strength: 4.0),
+            },
+            quads: [(corner: (0.0, 2.0, 0.0), edge_u: (2.0, 0.0, 0.0), edge_v: (0.0, 0.0, 0.5), material: "softbox")],
+            discs: [(center: (0.0, 3.0, 0.0), normal: (0.0, -2.0, 0.0), radius: 0.5, material: "light")],
+            objects: {
+                "panel": (quads: [(corner: (0.0, 0.0, 0.0), edge_u: (1.0, 0.0, 0.0), edge_v: (0.0, 1.0, 0.0), material: "light")]),
+                "lamp": (discs: [(center: (0.0, 0.0, 0.0), normal: (0.0, 1.0, 0.0), radius: 1.0, material: "light")]),
+            },
+            instances: [
+                // Quads stay parallelograms under any transform, discs only under uniform scales
+                (object: "panel", translation: (5.0, 0.0, 0.0), scale: (3.0, 1.0, 1.0)),
+                (object: "lamp", scale: (2.0, 2.0, 2.0)),
+                (object: "lamp", scale: (1.0, 1.0, 3.0)),
+            ],
+        )"#,
+    )
+    .unwrap();
+    let two_sided = |name: &str| {
+        let index = scene.material_names.iter().position(|n| n == name).unwrap();
+        scene.materials[index].two_sided_emission
+    };
+    assert!(!two_sided("softbox"));
+    assert!(two_sided("light"));
+
+    let layout = scene.intersectables();
+    let mut kinds: Vec<u32> = layout.lights.iter().map(|light| light.kind).collect();
+    kinds.sort();
+    assert_eq!(kinds, vec![LIGHT_QUAD, LIGHT_QUAD, LIGHT_DISC, LIGHT_DISC]);
+    let mut areas: Vec<f32> = layout.lights.iter().map(|light| light.area).collect();
+    areas.sort_by(f32::total_cmp);
+    let expected = [0.25 * PI, 1.0, 3.0, 4.0 * PI];
+    for (area, expected) in areas.iter().zip(expected) {
+        assert!((area - expected).abs() < 1e-5, "{} != {}", area, expected);
+    }
+
+    // The edges of a disc are radius long tangents spanning it
+    let disc = layout
+        .lights
+        .iter()
+        .find(|light| light.kind == LIGHT_DISC && light.position == [0.0, 3.0, 0.0])
+        .unwrap();
+    let edge_u = Vector3::from(disc.edge_u);
+    let edge_v = Vector3::from(disc.edge_v);
+    assert!((edge_u.magnitude() - 0.5).abs() < 1e-5 && (edge_v.magnitude() - 0.5).abs() < 1e-5);
+    assert!(edge_u.dot(edge_v).abs() < 1e-5);
+    assert!((edge_u.cross(edge_v).normalize() - Vector3::new(0.0, -1.0, 0.0)).magnitude() < 1e-5);
+}
+
+#[test]
+fn instanced_quads_keep_their_shape() {
+    let light = Light::Quad {
+        corner: Vector3::new(0.0, 0.0, 0.0),
+        edge_u: Vector3::new(1.0, 0.0, 0.0),
+        edge_v: Vector3::new(0.0, 1.0, 0.0),
+    };
+    let transform = cgmath::Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0))
+        * cgmath::Matrix4::from_angle_y(Deg(90.0))
+        * cgmath::Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0);
+    let Some(Light::Quad {
+        corner,
+        edge_u,
+        edge_v,
+    }) = light.transformed(&transform)
+    else {
+        panic!("a transformed quad is a quad");
+    };
+    assert!((corner - Vector3::new(1.0, 2.0, 3.0)).magnitude() < 1e-5);
+    assert!((edge_u - Vector3::new(0.0, 0.0, -2.0)).magnitude() < 1e-5);
+    assert!((edge_v - Vector3::new(0.0, 1.0, 0.0)).magnitude() < 1e-5);
+
+    let disc = Light::Disc {
+        center: Vector3::new(0.0, 0.0, 0.0),
+        normal: Vector3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+    };
+    assert_eq!(disc.transformed(&transform), None);
+}
+
+#[test]
+fn alias_table_picks_in_proportion_to_the_weights() {
+    let weights = [1.0, 0.0, 6.0, 2.0, 0.5, 0.5];
+    let table = AliasTable::new(&weights);
+    let total: f32 = weights.iter().sum();
+
+    // Every slot is picked with 1/n and hands the rest of it to its alias
+    let count = weights.len() as f32;
+    let mut picked = vec![0.0; weights.len()];
+    for entry in &table.entries {
+        assert!((0.0..=1.0).contains(&entry.probability));
+        picked[entry.alias as usize] += (1.0 - entry.probability) / count;
+    }
+    for (index, weight) in weights.iter().enumerate() {
+        picked[index] += table.entries[index].probability / count;
+        assert!((picked[index] - weight / total).abs() < 1e-6);
+        assert!((table.pmf(index) - weight / total).abs() < 1e-6);
+    }
+
+    let mut rng = Xorshift(5);
+    let mut counts = vec![0; weights.len()];
+    for _ in 0..60000 {
+        counts[table.sample(rng.next())] += 1;
+    }
+    assert_eq!(counts[1], 0);
+    for (count, weight) in counts.iter().zip(weights) {
+        let expected = 60000.0 * weight / total;
+        assert!((*count as f32 - expected).abs() < 0.02 * 60000.0);
+    }
+
+    // Nothing to go by, every item is equally likely
+    let uniform = AliasTable::new(&[0.0, 0.0, 0.0, 0.0]);
+    assert!(uniform.entries.iter().all(|entry| entry.pmf == 0.25));
+    assert!(AliasTable::new(&[]).is_empty());
+}
+
+#[test]
+fn lights_are_picked_by_power() {
+    let layout = layout(
+        r#"Scene(
+            materials: {
+                "bright": (emission_color: (1.0, 1.0, 1.0), emission_strength: 8.0),
+                "dim": (emission_color: (1.0, 1.0, 1.0), emission_strength: 1.0),
+            },
+            spheres: [
+                (position: (0.0, 3.0, 0.0), radius: 0.5, material: "bright"),
+                (position: (2.0, 3.0, 0.0), radius: 0.5, material: "dim"),
+                (position: (4.0, 3.0, 0.0), radius: 1.0, material: "dim"),
+            ],
+            lights: [Point(position: (0.0, 2.0, 0.0), intensity: 0.0)],
+            environment: (sky: Some(())),
+        )"#,
+    );
+
+    // One more entry for the sky, after the lights
+    assert_eq!(layout.lights.len(), 4);
+    assert_eq!(layout.light_table.len(), 5);
+    let pmf = |index: usize| layout.light_table.pmf(index);
+    let total: f32 = (0..5).map(pmf).sum();
+    assert!((total - 1.0).abs() < 1e-5);
+
+    // Power grows with the emission and the surface
+    let order: Vec<usize> = {
+        let mut order: Vec<usize> = (0..3).collect();
+        order.sort_by_key(|index| layout.lights[*index].position[0] as i32);
+        order
+    };
+    let (bright, dim, large) = (pmf(order[0]), pmf(order[1]), pmf(order[2]));
+    assert!((bright / dim - 8.0).abs() < 1e-3);
+    assert!((large / dim - 4.0).abs() < 1e-3);
+    // A light without intensity is never picked
+    assert_eq!(pmf(3), 0.0);
+    assert!(pmf(4) > 0.0);
+}
+
+#[test]
+fn area_lights_are_weighed_by_their_sides_and_textures() {
+    // Half the emission texture is black, in linear color as well as sRGB
+    let path = std::env::temp_dir().join("wgpu_raytracer_half_black.png");
+    let mut image = image::RgbaImage::from_pixel(2, 1, image::Rgba([255, 255, 255, 255]));
+    image.put_pixel(1, 0, image::Rgba([0, 0, 0, 255]));
+    image.save(&path).unwrap();
+    let scene = Scene::parse(&format!(
+        r#"Scene(
+            materials: {{
+                "a two sided": (emission_color: (1.0, 1.0, 1.0), emission_strength: 1.0),
+                "b one sided": (emission_color: (1.0, 1.0, 1.0), emission_strength: 1.0, two_sided_emission: false),
+                "c textured": (emission_color: (1.0, 1.0, 1.0), emission_strength: 1.0, two_sided_emission: false, emission_texture: Some({:?})),
+            }},
+            quads: [
+                (corner: (0.0, 3.0, 0.0), edge_u: (1.0, 0.0, 0.0), edge_v: (0.0, 0.0, 1.0), material: "a two sided"),
+                (corner: (2.0, 3.0, 0.0), edge_u: (1.0, 0.0, 0.0), edge_v: (0.0, 0.0, 1.0), material: "b one sided"),
+                (corner: (4.0, 3.0, 0.0), edge_u: (1.0, 0.0, 0.0), edge_v: (0.0, 0.0, 1.0), material: "c textured"),
+            ],
+        )"#,
+        path.to_string_lossy()
+    ))
+    .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let layout = scene.intersectables();
+
+    let power = |x: f32| {
+        let index = layout
+            .lights
+            .iter()
+            .position(|light| light.position[0] == x)
+            .unwrap();
+        layout.light_powers[index]
+    };
+    let (two_sided, one_sided, textured) = (power(0.0), power(2.0), power(4.0));
+    assert!((two_sided / one_sided - 2.0).abs() < 1e-4);
+    assert!((textured / one_sided - 0.5).abs() < 1e-4);
+}
@@ -0,0 +1,296 @@
+use cgmath::{InnerSpace, Vector3};
+use wgpu_raytracer::bsdf::BsdfLobes;
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+use wgpu_raytracer::types::material::Material;
+use wgpu_raytracer::types::texture::{texture_index, TextureSet, NO_TEXTURE};
+
+mod common;
+use common::Xorshift;
+
+#[test]
+fn transmissive_materials_are_parsed() {
+    let scene = Scene::parse(
+        r#"Scene(
+            materials: {
+                "water": (transmission: 1.0, ior: 1.33, attenuation_color: (0.3, 0.7, 0.9), attenuation_distance: 0.5),
+                "white": (),
+            },
+        )"#,
+    )
+    .unwrap();
+
+    let water = &scene.materials[0];
+    assert_eq!(water.transmission, 1.0);
+    assert_eq!(water.ior, 1.33);
+    assert_eq!(water.attenuation_color, [0.3, 0.7, 0.9]);
+    assert_eq!(water.attenuation_distance, 0.5);
+
+    let white = &scene.materials[1];
+    assert_eq!(white.transmission, 0.0);
+    assert_eq!(white.attenuation_color, [1.0, 1.0, 1.0]);
+}
+
+#[test]
+fn rejects_invalid_materials() {
+    let invalid_material = |material: &str| {
+        let source = format!("Scene(materials: {{ \"glass\": {} }})", material);
+        match Scene::parse(&source) {
+            Err(SceneError::InvalidMaterial { material, .. }) => material,
+            Err(error) => panic!("expected an invalid material, got {}", error),
+            Ok(_) => panic!("expected an invalid material"),
+        }
+    };
+
+    assert_eq!(invalid_material("(transmission: 1.0, ior: 0.0)"), "glass");
+    assert_eq!(
+        invalid_material("(transmission: 1.0, attenuation_distance: -1.0)"),
+        "glass"
+    );
+}
+
+#[test]
+fn textures_are_loaded_once_per_image() {
+    let scene = Scene::load("scenes/textured.ron").unwrap();
+    assert_eq!(scene.textures.images.len(), 4);
+
+    let material = |name: &str| {
+        let index = scene.material_names.iter().position(|n| n == name).unwrap();
+        &scene.materials[index]
+    };
+    let floor = material("floor");
+    let ball = material("checkered ball");
+    assert_eq!(
+        texture_index(&floor.base_color_texture),
+        texture_index(&ball.base_color_texture)
+    );
+    assert_ne!(texture_index(&floor.roughness_texture), NO_TEXTURE);
+    assert_eq!(texture_index(&ball.normal_texture), NO_TEXTURE);
+}
+
+#[test]
+fn missing_textures_are_reported() {
+    let result = Scene::parse(
+        r#"Scene(materials: { "wood": (base_color_texture: Some("textures/missing.png")) })"#,
+    );
+    match result {
+        Err(SceneError::Texture { path, .. }) => assert_eq!(path, "textures/missing.png"),
+        Err(error) => panic!("expected a texture error, got {}", error),
+        Ok(_) => panic!("expected a texture error"),
+    }
+}
+
+#[test]
+fn textures_are_grouped_by_size() {
+    let mut textures = TextureSet::default();
+    let mut push = |width, height| textures.push(image::RgbaImage::new(width, height));
+
+    // The top byte picks the texture array, the rest is the layer in it
+    assert_eq!(push(16, 16), 0);
+    assert_eq!(push(300, 100), 1 << 24);
+    assert_eq!(push(4096, 1024), 3 << 24);
+    assert_eq!(push(200, 64), 1);
+    assert_eq!(push(512, 512), (1 << 24) | 1);
+
+    // Layers are as large as the largest image in the array, not the scene
+    assert_eq!(textures.layer_size(0), (200, 64));
+    assert_eq!(textures.layer_size(1), (512, 512));
+    assert_eq!(textures.layer_size(2), (1, 1));
+    assert_eq!(textures.layer_size(3), (2048, 1024));
+    assert_eq!(textures.layers(0).count(), 2);
+}
+
+fn direction(theta_degrees: f32) -> Vector3<f32> {
+    let theta = theta_degrees.to_radians();
+    Vector3::new(theta.sin(), 0.0, theta.cos())
+}
+
+/// Materials exercising every lobe of the principled BSDF, all with a white base.
+fn white_materials() -> Vec<(&'static str, Material)> {
+    let material = |f: fn(&mut Material)| {
+        let mut material = Material::default();
+        f(&mut material);
+        material
+    };
+    vec![
+        ("diffuse", Material::default()),
+        ("plastic", material(|m| m.roughness = 0.3)),
+        (
+            "mirror",
+            material(|m| (m.metallic, m.roughness) = (1.0, 0.0)),
+        ),
+        (
+            "rough metal",
+            material(|m| (m.metallic, m.roughness) = (1.0, 0.6)),
+        ),
+        (
+            "brushed metal",
+            material(|m| (m.metallic, m.roughness, m.anisotropic) = (1.0, 0.4, 0.8)),
+        ),
+        ("velvet", material(|m| (m.sheen, m.sheen_tint) = (1.0, 0.0))),
+        (
+            "car paint",
+            material(|m| (m.roughness, m.clearcoat, m.clearcoat_roughness) = (0.5, 1.0, 0.1)),
+        ),
+        (
+            "frosted glass",
+            material(|m| (m.roughness, m.transmission) = (0.4, 1.0)),
+        ),
+    ]
+}
+
+/// Mean sample weight, the fraction of the light from wo that's scattered.
+/// Refracted light is scaled back from the radiance of the other side.
+fn albedo(lobes: &BsdfLobes, wo: Vector3<f32>, samples: u32, rng: &mut Xorshift) -> Vector3<f32> {
+    let mut total = Vector3::new(0.0, 0.0, 0.0);
+    for _ in 0..samples {
+        if let Some(sample) = lobes.sample(wo, rng.next(), rng.next_2d()) {
+            let radiance_scale = if sample.direction.z < 0.0 {
+                lobes.eta * lobes.eta
+            } else {
+                1.0
+            };
+            total += sample.weight * radiance_scale;
+        }
+    }
+    total / samples as f32
+}
+
+#[test]
+fn bsdf_does_not_create_energy() {
+    let mut rng = Xorshift(0x9e3779b9);
+    for (name, material) in white_materials() {
+        for front_face in [true, false] {
+            let lobes = BsdfLobes::new(&material, front_face);
+            for theta in [0.0, 30.0, 60.0, 85.0] {
+                let scattered = albedo(&lobes, direction(theta), 20000, &mut rng);
+                for channel in [scattered.x, scattered.y, scattered.z] {
+                    assert!(
+                        channel <= 1.02,
+                        "{} at {} degrees scatters {}",
+                        name,
+                        theta,
+                        channel
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn white_mirrors_and_clear_glass_lose_no_energy() {
+    let mut rng = Xorshift(0x2545f491);
+    let mirror = Material {
+        metallic: 1.0,
+        roughness: 0.0,
+        ..Default::default()
+    };
+    let glass = Material {
+        roughness: 0.0,
+        transmission: 1.0,
+        ..Default::default()
+    };
+
+    for theta in [0.0, 45.0, 80.0] {
+        let scattered = albedo(
+            &BsdfLobes::new(&mirror, true),
+            direction(theta),
+            2000,
+            &mut rng,
+        );
+        assert!(
+            (scattered.x - 1.0).abs() < 0.01,
+            "mirror reflects {}",
+            scattered.x
+        );
+
+        // Leaving the glass includes total internal reflection
+        for front_face in [true, false] {
+            let lobes = BsdfLobes::new(&glass, front_face);
+            let scattered = albedo(&lobes, direction(theta), 20000, &mut rng);
+            assert!(
+                (scattered.x - 1.0).abs() < 0.01,
+                "glass scatters {}",
+                scattered.x
+            );
+        }
+    }
+}
+
+#[test]
+fn bsdf_is_reciprocal() {
+    let mut rng = Xorshift(0x6c8e9cf5);
+    for (name, mut material) in white_materials() {
+        material.color = [0.8, 0.5, 0.2];
+        let lobes = BsdfLobes::new(&material, true);
+        for _ in 0..200 {
+            let wo = rng.hemisphere();
+            let wi = rng.hemisphere();
+            // The BSDF itself, without the cosine of eval
+            let forward = lobes.eval(wo, wi).value / wi.z;
+            let backward = lobes.eval(wi, wo).value / wo.z;
+            assert!(
+                (forward - backward).magnitude() <= 1e-3 * forward.magnitude().max(1.0),
+                "{}: {:?} != {:?}",
+                name,
+                forward,
+                backward
+            );
+        }
+    }
+}
+
+#[test]
+fn refraction_is_reciprocal_up_to_the_radiance_scale() {
+    let mut rng = Xorshift(0x3c6ef372);
+    let glass = Material {
+        roughness: 0.5,
+        transmission: 1.0,
+        ..Default::default()
+    };
+    let outside = BsdfLobes::new(&glass, true);
+    let inside = BsdfLobes::new(&glass, false);
+
+    for _ in 0..200 {
+        let wo = rng.hemisphere();
+        let wi = -rng.hemisphere();
+        // Seen from the inside the frame is turned over, both directions flip
+        let forward = outside.eval(wo, wi).value / -wi.z;
+        let backward = inside.eval(-wi, -wo).value / wo.z;
+        let scale = outside.eta * outside.eta;
+        assert!(
+            (forward * scale - backward).magnitude() <= 1e-3 * backward.magnitude().max(1.0),
+            "{:?} * {} != {:?}",
+            forward,
+            scale,
+            backward
+        );
+    }
+}
+
+#[test]
+fn sample_pdf_matches_eval() {
+    let mut rng = Xorshift(0xa54ff53a);
+    for (name, material) in white_materials() {
+        let lobes = BsdfLobes::new(&material, true);
+        let wo = direction(40.0);
+        for _ in 0..200 {
+            let Some(sample) = lobes.sample(wo, rng.next(), rng.next_2d()) else {
+                continue;
+            };
+            let evaluation = lobes.eval(wo, sample.direction);
+            assert!(
+                (sample.pdf - evaluation.pdf).abs() <= 1e-4 * evaluation.pdf,
+                "{}",
+                name
+            );
+            assert!(
+                (sample.weight * sample.pdf - evaluation.value).magnitude()
+                    <= 1e-4 * evaluation.value.magnitude().max(1.0),
+                "{}",
+                name
+            );
+        }
+    }
+}
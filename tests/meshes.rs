@@ -0,0 +1,291 @@
+use cgmath::{InnerSpace, Vector3};
+use wgpu_raytracer::acceleration::aabb::Aabb;
+use wgpu_raytracer::acceleration::bvh::Bvh;
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+use wgpu_raytracer::types::intersectable::Intersectable;
+use wgpu_raytracer::types::mesh::{Mesh, Triangle};
+
+fn triangle(positions: [[f32; 3]; 3]) -> Triangle {
+    Triangle {
+        positions: positions.map(Vector3::from),
+        vertices: [0, 1, 2],
+        material_id: 0,
+        closed: false,
+    }
+}
+
+/// Right triangle in the z = 0 plane with its legs along x and y.
+fn unit_triangle() -> Triangle {
+    triangle([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+}
+
+fn forward() -> Vector3<f32> {
+    Vector3::new(0.0, 0.0, 1.0)
+}
+
+#[test]
+fn triangles_are_hit_inside_their_edges() {
+    let triangle = unit_triangle();
+    let hit = |x: f32, y: f32| triangle.intersect(Vector3::new(x, y, -2.0), forward());
+
+    assert_eq!(hit(0.25, 0.25), Some(2.0));
+    assert_eq!(hit(0.01, 0.98), Some(2.0));
+    assert_eq!(hit(0.6, 0.6), None);
+    assert_eq!(hit(-0.1, 0.5), None);
+    assert_eq!(hit(0.5, -0.1), None);
+}
+
+#[test]
+fn triangles_are_hit_from_both_sides() {
+    let triangle = unit_triangle();
+
+    assert_eq!(
+        triangle.intersect(Vector3::new(0.2, 0.2, 3.0), -forward()),
+        Some(3.0)
+    );
+    // Slanted rays travel further
+    let direction = Vector3::new(0.0, 0.6, 0.8);
+    let distance = triangle
+        .intersect(Vector3::new(0.2, -0.4, -0.8), direction)
+        .unwrap();
+    assert!((distance - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn triangles_behind_or_parallel_to_the_ray_are_missed() {
+    let triangle = unit_triangle();
+
+    assert_eq!(
+        triangle.intersect(Vector3::new(0.2, 0.2, 1.0), forward()),
+        None
+    );
+    assert_eq!(
+        triangle.intersect(Vector3::new(-1.0, 0.2, 0.0), Vector3::unit_x()),
+        None
+    );
+    assert_eq!(
+        triangle.intersect(Vector3::new(-1.0, 0.2, 1.0), Vector3::unit_x()),
+        None
+    );
+}
+
+#[test]
+fn triangles_are_bounded_by_their_vertices() {
+    let bounds = triangle([[1.0, -2.0, 0.5], [3.0, 0.0, -1.0], [0.0, 4.0, 2.0]]).bounds();
+
+    assert_eq!(bounds.min, Vector3::new(0.0, -2.0, -1.0));
+    assert_eq!(bounds.max, Vector3::new(3.0, 4.0, 2.0));
+}
+
+/// Grid of `size` x `size` quads in the y = 0 plane, bent up into a bowl.
+fn bowl(size: u32) -> Mesh {
+    let mut mesh = Mesh::default();
+    for z in 0..=size {
+        for x in 0..=size {
+            let (u, v) = (x as f32 - size as f32 / 2.0, z as f32 - size as f32 / 2.0);
+            mesh.positions
+                .push(Vector3::new(u, 0.1 * (u * u + v * v), v));
+        }
+    }
+    let row = size + 1;
+    for z in 0..size {
+        for x in 0..size {
+            let corner = z * row + x;
+            mesh.triangles.push([corner, corner + row, corner + 1]);
+            mesh.triangles
+                .push([corner + 1, corner + row, corner + row + 1]);
+        }
+    }
+    mesh.material_ids = vec![0; mesh.triangles.len()];
+    mesh
+}
+
+#[test]
+fn mesh_bvh_matches_brute_force_closest_hit() {
+    let mesh = bowl(12);
+    let triangles: Vec<Triangle> = (0..mesh.triangles.len())
+        .map(|index| Triangle::from_mesh(&mesh, index, 0))
+        .collect();
+    let bounds: Vec<Aabb> = triangles.iter().map(Triangle::bounds).collect();
+    let bvh = Bvh::build(&bounds);
+
+    let mut hits = 0;
+    for i in 0..400 {
+        // Rays from above the bowl towards points spread over it
+        let origin = Vector3::new(
+            (i % 20) as f32 * 0.3 - 3.0,
+            20.0,
+            (i / 20) as f32 * 0.3 - 3.0,
+        );
+        let target = Vector3::new((i % 7) as f32 - 3.0, 0.0, (i % 11) as f32 * 0.5 - 2.5);
+        let direction = (target - origin).normalize();
+
+        let expected = triangles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, triangle)| {
+                triangle
+                    .intersect(origin, direction)
+                    .map(|distance| (index as u32, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let actual = bvh.closest_hit(origin, direction, |index| {
+            triangles[index as usize].intersect(origin, direction)
+        });
+
+        // Rays through shared edges hit both triangles within rounding
+        match (expected, actual) {
+            (Some(expected), Some(actual)) => assert!(
+                (expected.1 - actual.1).abs() < 1e-4,
+                "ray from {:?} along {:?}",
+                origin,
+                direction
+            ),
+            (None, None) => {}
+            _ => panic!(
+                "ray from {:?} along {:?}: {:?} != {:?}",
+                origin, direction, expected, actual
+            ),
+        }
+        if expected.is_some() {
+            hits += 1;
+        }
+    }
+    assert!(hits > 300, "only {} rays hit the mesh", hits);
+}
+
+#[test]
+fn computed_normals_follow_the_surface() {
+    let mut mesh = bowl(2);
+    mesh.compute_normals();
+
+    // The bottom of the bowl faces straight up, the rim leans inwards
+    let center = mesh.normals[4];
+    assert!(
+        (center - Vector3::unit_y()).magnitude() < 1e-5,
+        "{:?}",
+        center
+    );
+    let rim = mesh.normals[3];
+    assert!(rim.x > 0.0 && rim.y > 0.0, "{:?}", rim);
+    assert!(mesh
+        .normals
+        .iter()
+        .all(|normal| (normal.magnitude() - 1.0).abs() < 1e-5));
+}
+
+#[test]
+fn reports_meshes_with_invalid_indices() {
+    let result = Scene::parse(
+        r#"Scene(
+            materials: { "white": () },
+            meshes: [(
+                positions: [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)],
+                indices: [(0, 1, 3)],
+                material: "white",
+            )],
+        )"#,
+    );
+
+    assert!(matches!(
+        result,
+        Err(SceneError::InvalidMesh { mesh: 0, .. })
+    ));
+}
+
+/// Unit cube with every face split off at its own four vertices, as
+/// importers do for flat normals.
+fn split_cube() -> Mesh {
+    let mut mesh = Mesh::default();
+    for axis in 0..3 {
+        for side in [0.0, 1.0] {
+            let first = mesh.positions.len() as u32;
+            for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                let mut position = Vector3::new(0.0, 0.0, 0.0);
+                position[axis] = side;
+                position[(axis + 1) % 3] = u;
+                position[(axis + 2) % 3] = v;
+                mesh.positions.push(position);
+            }
+            mesh.triangles.push([first, first + 1, first + 2]);
+            mesh.triangles.push([first, first + 2, first + 3]);
+        }
+    }
+    mesh.material_ids = vec![0; mesh.triangles.len()];
+    mesh
+}
+
+/// Closed flags `find_closed` gives the triangles of `mesh`.
+fn find_closed(mut mesh: Mesh) -> Vec<bool> {
+    mesh.find_closed();
+    mesh.closed
+}
+
+#[test]
+fn parts_without_a_boundary_are_closed() {
+    assert_eq!(find_closed(split_cube()), vec![true; 12]);
+    assert_eq!(find_closed(bowl(3)), vec![false; 18]);
+    assert!(find_closed(Mesh::default()).is_empty());
+
+    // A cube without its lid is open
+    let mut open_cube = split_cube();
+    open_cube.triangles.truncate(10);
+    open_cube.material_ids.truncate(10);
+    assert_eq!(find_closed(open_cube), vec![false; 10]);
+
+    // A cube next to a bowl in the same mesh keeps its inside
+    let mut mesh = split_cube();
+    let bowl = bowl(1);
+    let first_vertex = mesh.positions.len() as u32;
+    mesh.positions.extend(
+        bowl.positions
+            .iter()
+            .map(|p| p + Vector3::new(5.0, 0.0, 0.0)),
+    );
+    mesh.triangles.extend(
+        bowl.triangles
+            .iter()
+            .map(|triangle| triangle.map(|index| index + first_vertex)),
+    );
+    mesh.material_ids.extend(bowl.material_ids);
+    let closed = find_closed(mesh);
+    assert_eq!(closed[..12], [true; 12]);
+    assert_eq!(closed[12..], [false; 2]);
+}
+
+#[test]
+fn scene_meshes_are_closed_unless_told_otherwise() {
+    let scene = Scene::parse(
+        r#"Scene(
+            materials: { "white": () },
+            meshes: [
+                (
+                    positions: [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0)],
+                    indices: [(0, 2, 1), (0, 1, 3), (0, 3, 2), (1, 2, 3)],
+                    material: "white",
+                ),
+                (
+                    positions: [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)],
+                    indices: [(0, 1, 2)],
+                    material: "white",
+                ),
+                (
+                    positions: [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)],
+                    indices: [(0, 1, 2)],
+                    material: "white",
+                    closed: Some(true),
+                ),
+            ],
+        )"#,
+    )
+    .unwrap();
+
+    let closed: Vec<&[bool]> = scene
+        .world
+        .meshes
+        .iter()
+        .map(|mesh| mesh.closed.as_slice())
+        .collect();
+    assert_eq!(closed, vec![&[true; 4][..], &[false], &[true]]);
+}
@@ -0,0 +1,167 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector2, Vector3};
+use wgpu_raytracer::bsdf::microfacet::{ggx_d, ggx_g1, sample_ggx_visible_normal};
+use wgpu_raytracer::bsdf::BsdfLobes;
+use wgpu_raytracer::types::material::Material;
+
+/// Midpoint rule over the hemisphere around +z, fine enough for alphas down
+/// to 0.1.
+fn integrate_hemisphere<F: Fn(Vector3<f32>) -> f32>(f: F) -> f32 {
+    let (theta_steps, phi_steps) = (1024, 256);
+    let (d_theta, d_phi) = (0.5 * PI / theta_steps as f32, 2.0 * PI / phi_steps as f32);
+
+    let mut total = 0.0f64;
+    for i in 0..theta_steps {
+        let theta = (i as f32 + 0.5) * d_theta;
+        let mut ring = 0.0f64;
+        for j in 0..phi_steps {
+            let phi = (j as f32 + 0.5) * d_phi;
+            let w = Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            );
+            ring += f(w) as f64;
+        }
+        total += ring * (theta.sin() * d_theta * d_phi) as f64;
+    }
+    total as f32
+}
+
+fn direction(theta_degrees: f32, phi_degrees: f32) -> Vector3<f32> {
+    let (theta, phi) = (theta_degrees.to_radians(), phi_degrees.to_radians());
+    Vector3::new(
+        theta.sin() * phi.cos(),
+        theta.sin() * phi.sin(),
+        theta.cos(),
+    )
+}
+
+fn alphas() -> [Vector2<f32>; 5] {
+    [
+        Vector2::new(0.1, 0.1),
+        Vector2::new(0.3, 0.3),
+        Vector2::new(0.6, 0.6),
+        Vector2::new(1.0, 1.0),
+        Vector2::new(0.2, 0.6),
+    ]
+}
+
+#[test]
+fn ggx_normals_cover_the_surface_once() {
+    for alpha in alphas() {
+        // The microfacets projected onto the macro surface add up to its area
+        let projected = integrate_hemisphere(|h| ggx_d(alpha, h) * h.z);
+        assert!(
+            (projected - 1.0).abs() < 1e-2,
+            "alpha {:?}: {}",
+            alpha,
+            projected
+        );
+    }
+}
+
+#[test]
+fn visible_normals_are_a_distribution() {
+    for alpha in alphas() {
+        for wo in [
+            direction(0.0, 0.0),
+            direction(45.0, 30.0),
+            direction(80.0, 90.0),
+        ] {
+            // Microfacets seen from wo add up to the projected area of the surface
+            let visible =
+                integrate_hemisphere(|h| ggx_g1(alpha, wo) * wo.dot(h).max(0.0) * ggx_d(alpha, h));
+            assert!(
+                (visible - wo.z).abs() < 1e-2 * wo.z.max(0.1),
+                "alpha {:?}, wo {:?}: {} != {}",
+                alpha,
+                wo,
+                visible,
+                wo.z
+            );
+        }
+    }
+}
+
+#[test]
+fn sampled_visible_normals_follow_their_density() {
+    for alpha in alphas() {
+        for wo in [direction(0.0, 0.0), direction(60.0, 30.0)] {
+            let density =
+                |h: Vector3<f32>| ggx_g1(alpha, wo) * wo.dot(h).max(0.0) * ggx_d(alpha, h) / wo.z;
+            let expected = Vector3::new(
+                integrate_hemisphere(|h| h.x * density(h)),
+                integrate_hemisphere(|h| h.y * density(h)),
+                integrate_hemisphere(|h| h.z * density(h)),
+            );
+
+            // Stratified samples, so the mean converges quickly
+            let strata = 256;
+            let mut mean = Vector3::new(0.0, 0.0, 0.0);
+            for i in 0..strata {
+                for j in 0..strata {
+                    let u = Vector2::new(
+                        (i as f32 + 0.5) / strata as f32,
+                        (j as f32 + 0.5) / strata as f32,
+                    );
+                    let h = sample_ggx_visible_normal(alpha, wo, u);
+                    assert!((h.magnitude() - 1.0).abs() < 1e-4);
+                    assert!(
+                        wo.dot(h) >= -1e-4,
+                        "back facing normal {:?} for wo {:?}",
+                        h,
+                        wo
+                    );
+                    mean += h;
+                }
+            }
+            mean /= (strata * strata) as f32;
+
+            assert!(
+                (mean - expected).magnitude() < 1e-2,
+                "alpha {:?}, wo {:?}: mean {:?} != {:?}",
+                alpha,
+                wo,
+                mean,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn grazing_directions_evaluate_to_finite_values() {
+    let materials = [
+        Material::default(),
+        Material {
+            metallic: 1.0,
+            roughness: 0.2,
+            ..Material::default()
+        },
+        Material {
+            sheen: 1.0,
+            clearcoat: 1.0,
+            ..Material::default()
+        },
+    ];
+    // Nearly the same direction just above the horizon, where wi · h rounds to above 1
+    let wo = Vector3::new(0.28474376, -0.95860374, 0.00011894107);
+    let wi = Vector3::new(0.28463897, -0.9586348, 0.00020589287);
+
+    for material in &materials {
+        let lobes = BsdfLobes::new(material, true);
+        for (a, b) in [(wo, wi), (wi, wo), (wo, wo)] {
+            let eval = lobes.eval(a, b);
+            assert!(eval.pdf.is_finite() && eval.pdf >= 0.0, "{:?}", eval);
+            assert!(
+                [eval.value.x, eval.value.y, eval.value.z]
+                    .iter()
+                    .all(|value| value.is_finite() && *value >= 0.0),
+                "{:?}",
+                eval
+            );
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use wgpu_raytracer::import::import_error::ImportError;
+use wgpu_raytracer::import::obj::{load_obj, parse_mtl, parse_obj, DEFAULT_MATERIAL_NAME};
+use wgpu_raytracer::types::material::Material;
+
+fn no_library(name: &str) -> Result<Vec<(String, Material)>, ImportError> {
+    panic!("unexpected material library '{}'", name)
+}
+
+fn parse_error_line(result: Result<impl Sized, ImportError>) -> usize {
+    match result {
+        Err(ImportError::Parse { line, .. }) => line,
+        Err(error) => panic!("expected a parse error, got {}", error),
+        Ok(_) => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+fn triangulates_polygons_and_shares_vertices() {
+    let source = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0.5 1.5 0
+vn 0 0 1
+vt 0 0
+vt 1 1
+# quad and pentagon sharing an edge
+f 1/1/1 2/1/1 3/2/1 4/2/1
+f 4/2/1 3/2/1 5/1/1 -1/1/1 1/1/1
+";
+    let model = parse_obj(source, "test.obj", no_library).unwrap();
+
+    assert_eq!(model.mesh.triangles.len(), 2 + 3);
+    assert_eq!(model.mesh.positions.len(), 5);
+    assert_eq!(model.mesh.normals.len(), 5);
+    assert_eq!(model.mesh.uvs.len(), 5);
+    assert_eq!(model.mesh.triangles[0], [0, 1, 2]);
+    assert_eq!(model.mesh.triangles[1], [0, 2, 3]);
+    assert_eq!(model.material_names, vec![DEFAULT_MATERIAL_NAME]);
+    assert!(model.mesh.material_ids.iter().all(|id| *id == 0));
+    assert!(model.mesh.validate().is_ok());
+    assert_eq!(model.mesh.closed, vec![false; 5]);
+}
+
+#[test]
+fn closed_models_have_an_inside() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/models/cube.obj");
+    let model = load_obj(path).unwrap();
+
+    // The cube has an inside, the lamp quad above it doesn't
+    assert_eq!(model.mesh.closed[..12], [true; 12]);
+    assert_eq!(model.mesh.closed[12..], [false; 2]);
+}
+
+#[test]
+fn missing_normals_fall_back_to_computed_ones() {
+    let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3\n";
+    let model = parse_obj(source, "test.obj", no_library).unwrap();
+
+    assert!(model.mesh.normals.is_empty());
+    assert!(model.mesh.uvs.is_empty());
+}
+
+#[test]
+fn maps_mtl_colors_to_materials() {
+    let source = "\
+newmtl red
+Kd 0.8 0.1 0.1
+Ks 1 1 1
+newmtl glow
+Kd 0.5
+Ke 2 2 1
+";
+    let materials = parse_mtl(source, "test.mtl").unwrap();
+
+    assert_eq!(materials.len(), 2);
+    assert_eq!(materials[0].0, "red");
+    assert_eq!(materials[0].1.color, [0.8, 0.1, 0.1]);
+    assert_eq!(materials[0].1.emission_strength, 0.0);
+    assert_eq!(materials[1].1.color, [0.5, 0.5, 0.5]);
+    assert_eq!(materials[1].1.emission_color, [2.0, 2.0, 1.0]);
+    assert_eq!(materials[1].1.emission_strength, 1.0);
+}
+
+#[test]
+fn uses_materials_from_libraries() {
+    let source = "\
+mtllib colors.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl blue
+f 1 2 3
+usemtl green
+f 3 2 1
+usemtl blue
+f 1 3 2
+";
+    let model = parse_obj(source, "test.obj", |name| {
+        assert_eq!(name, "colors.mtl");
+        parse_mtl(
+            "newmtl green\nKd 0 1 0\nnewmtl blue\nKd 0 0 1\n",
+            "colors.mtl",
+        )
+    })
+    .unwrap();
+
+    assert_eq!(model.material_names, vec!["blue", "green"]);
+    assert_eq!(model.mesh.material_ids, vec![0, 1, 0]);
+    assert_eq!(model.materials[0].color, [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn reports_malformed_lines() {
+    let parse = |source: &str| parse_obj(source, "test.obj", no_library);
+
+    assert_eq!(parse_error_line(parse("v 0 0 0\nv 1 zero 0\n")), 2);
+    assert_eq!(parse_error_line(parse("v 0 0 0\n\nv 1 0\n")), 3);
+    assert_eq!(parse_error_line(parse("v 0 0 0\nv 1 0 0\nf 1 2\n")), 3);
+    assert_eq!(parse_error_line(parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n")), 3);
+    assert_eq!(parse_error_line(parse("v 0 0 0\nf 0 1 1\n")), 2);
+    assert_eq!(parse_error_line(parse("v 0 0 0\nf 1/1 1 1\n")), 2);
+    assert_eq!(parse_error_line(parse("# comment\nusemtl nothing\n")), 2);
+    assert_eq!(parse_error_line(parse_mtl("Kd 1 1 1\n", "test.mtl")), 1);
+}
+
+#[test]
+fn reports_missing_files() {
+    let directory = std::env::temp_dir().join(format!("obj-import-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("missing_library.obj");
+    std::fs::write(&path, "v 0 0 0\n\nmtllib does_not_exist.mtl\n").unwrap();
+
+    let result = load_obj(&path);
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(parse_error_line(result), 3);
+
+    assert!(matches!(
+        load_obj(directory.join("does_not_exist.obj")),
+        Err(ImportError::Io { .. })
+    ));
+}
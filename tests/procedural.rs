@@ -0,0 +1,128 @@
+use cgmath::{Vector2, Vector3};
+use wgpu_raytracer::procedural::noise::{fbm, perlin, turbulence};
+use wgpu_raytracer::procedural::{Pattern, ProceduralTexture};
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+
+fn texture(pattern: Pattern) -> ProceduralTexture {
+    ProceduralTexture {
+        pattern,
+        ..Default::default()
+    }
+}
+
+/// Points spread over a few lattice cells, on both sides of the origin.
+fn points() -> impl Iterator<Item = Vector3<f32>> {
+    (0..1000).map(|i| {
+        let i = i as f32;
+        Vector3::new(
+            (i * 0.618).sin() * 7.3,
+            (i * 0.377).cos() * 5.1,
+            i * 0.013 - 6.0,
+        )
+    })
+}
+
+#[test]
+fn checkers_alternate() {
+    let checker = texture(Pattern::Checker);
+    let origin = Vector3::new(0.0, 0.0, 0.0);
+    assert_eq!(checker.value(origin, Vector2::new(0.5, 0.5)), 0.0);
+    assert_eq!(checker.value(origin, Vector2::new(1.5, 0.5)), 1.0);
+    assert_eq!(checker.value(origin, Vector2::new(-0.5, 0.5)), 1.0);
+    assert_eq!(checker.value(origin, Vector2::new(1.5, 1.5)), 0.0);
+
+    let solid = texture(Pattern::SolidChecker);
+    let uv = Vector2::new(0.0, 0.0);
+    assert_eq!(solid.value(Vector3::new(0.5, 0.5, 0.5), uv), 0.0);
+    assert_eq!(solid.value(Vector3::new(0.5, 0.5, -0.5), uv), 1.0);
+    assert_eq!(solid.value(Vector3::new(1.5, -0.5, 0.5), uv), 0.0);
+}
+
+#[test]
+fn noise_vanishes_on_the_lattice_and_stays_bounded() {
+    for x in -3..3 {
+        for y in -3..3 {
+            let p = Vector3::new(x as f32, y as f32, 2.0);
+            assert_eq!(perlin(p), 0.0);
+        }
+    }
+
+    let mut smallest = f32::MAX;
+    let mut largest = f32::MIN;
+    for p in points() {
+        let noise = perlin(p);
+        smallest = smallest.min(noise);
+        largest = largest.max(noise);
+        assert!(fbm(p, 6).abs() <= 1.1);
+        assert!((0.0..=1.1).contains(&turbulence(p, 6)));
+    }
+    assert!(smallest >= -1.1 && largest <= 1.1);
+    // Not a constant
+    assert!(largest - smallest > 0.5);
+}
+
+#[test]
+fn noise_is_continuous() {
+    let step = Vector3::new(1e-3, 1e-3, 1e-3);
+    for p in points() {
+        assert!((perlin(p + step) - perlin(p)).abs() < 0.02);
+    }
+}
+
+#[test]
+fn patterns_stay_between_0_and_1() {
+    let patterns = [
+        Pattern::Checker,
+        Pattern::SolidChecker,
+        Pattern::Gradient,
+        Pattern::Noise,
+        Pattern::Marble,
+        Pattern::Wood,
+    ];
+    for pattern in patterns {
+        let texture = ProceduralTexture {
+            pattern,
+            scale: 3.0,
+            ..Default::default()
+        };
+        for p in points() {
+            let value = texture.value(p, Vector2::new(p.x, p.y));
+            assert!((0.0..=1.0).contains(&value), "{:?} gave {}", pattern, value);
+        }
+    }
+}
+
+#[test]
+fn previews_blend_both_colors() {
+    let checker = ProceduralTexture {
+        pattern: Pattern::Checker,
+        color: [0.0, 0.0, 0.0],
+        scale: 2.0,
+        ..Default::default()
+    };
+    let image = checker.preview([1.0, 1.0, 1.0], 64, 64);
+    assert_eq!(image.dimensions(), (64, 64));
+    assert_eq!(image.get_pixel(0, 0).0, [255, 255, 255, 255]);
+    assert_eq!(image.get_pixel(63, 0).0, [0, 0, 0, 255]);
+    assert_eq!(image.get_pixel(63, 63).0, [255, 255, 255, 255]);
+}
+
+#[test]
+fn procedural_textures_are_parsed_and_validated() {
+    let scene = Scene::parse(
+        r#"Scene(materials: {
+            "marble": (procedural_texture: Some((pattern: Marble, color: (0.1, 0.1, 0.1), scale: 4.0, octaves: 6))),
+        })"#,
+    )
+    .unwrap();
+    let marble = scene.materials[0].procedural_texture.unwrap();
+    assert_eq!(marble.pattern, Pattern::Marble);
+    assert_eq!(marble.octaves, 6);
+    assert_eq!(marble.distortion, 1.0);
+
+    let result = Scene::parse(
+        r#"Scene(materials: { "wood": (procedural_texture: Some((pattern: Wood, octaves: 20))) })"#,
+    );
+    assert!(matches!(result, Err(SceneError::InvalidMaterial { .. })));
+}
@@ -0,0 +1,101 @@
+use wgpu_raytracer::cli::args::Args;
+use wgpu_raytracer::sampler::sampler_kind::SamplerKind;
+use wgpu_raytracer::types::globals::RenderSettings;
+
+fn parse(args: &[&str]) -> Result<Args, String> {
+    Args::parse(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn settings_are_clamped_to_their_ranges() {
+    let settings = RenderSettings {
+        samples_per_frame: 0,
+        max_bounces: 0,
+        roulette_depth: 0,
+    };
+    assert_eq!(
+        settings.clamped(),
+        RenderSettings {
+            samples_per_frame: 1,
+            max_bounces: 1,
+            roulette_depth: 0,
+        }
+    );
+
+    let settings = RenderSettings {
+        samples_per_frame: u32::MAX,
+        max_bounces: u32::MAX,
+        roulette_depth: u32::MAX,
+    };
+    assert_eq!(
+        settings.clamped(),
+        RenderSettings {
+            samples_per_frame: RenderSettings::MAX_SAMPLES_PER_FRAME,
+            max_bounces: RenderSettings::MAX_BOUNCES,
+            roulette_depth: RenderSettings::MAX_BOUNCES,
+        }
+    );
+
+    assert_eq!(
+        RenderSettings::default().clamped(),
+        RenderSettings::default()
+    );
+}
+
+#[test]
+fn render_settings_are_parsed() {
+    let args = parse(&[
+        "--spp",
+        "4",
+        "--depth",
+        "12",
+        "--roulette",
+        "0",
+        "--sampler",
+        "sobol",
+    ])
+    .unwrap();
+    assert_eq!(
+        args.render_settings,
+        RenderSettings {
+            samples_per_frame: 4,
+            max_bounces: 12,
+            roulette_depth: 0,
+        }
+    );
+    assert_eq!(args.sampler_kind, SamplerKind::Sobol);
+
+    assert_eq!(
+        parse(&[]).unwrap().render_settings,
+        RenderSettings::default()
+    );
+    let args = parse(&["--spp", "100000", "--depth", "1000"]).unwrap();
+    assert_eq!(
+        args.render_settings.samples_per_frame,
+        RenderSettings::MAX_SAMPLES_PER_FRAME
+    );
+    assert_eq!(
+        args.render_settings.max_bounces,
+        RenderSettings::MAX_BOUNCES
+    );
+}
+
+#[test]
+fn invalid_arguments_are_reported() {
+    assert_eq!(
+        parse(&["--spp", "0"]).unwrap_err(),
+        "--spp must be greater than 0"
+    );
+    assert_eq!(
+        parse(&["--depth", "deep"]).unwrap_err(),
+        "--depth expects a number, got 'deep'"
+    );
+    assert_eq!(
+        parse(&["--roulette"]).unwrap_err(),
+        "--roulette requires a value"
+    );
+    assert_eq!(
+        parse(&["--bounces", "3"]).unwrap_err(),
+        "unknown argument '--bounces'"
+    );
+}
@@ -0,0 +1,92 @@
+use wgpu_raytracer::sampler::blue_noise::{blue_noise_tile, generate_blue_noise, BLUE_NOISE_SIZE};
+use wgpu_raytracer::sampler::reservoir::ReservoirUniform;
+use wgpu_raytracer::sampler::sampler_kind::SamplerKind;
+
+#[test]
+fn sampler_names_round_trip() {
+    for kind in SamplerKind::ALL {
+        assert_eq!(kind.to_string().parse::<SamplerKind>(), Ok(kind));
+    }
+    assert_eq!(
+        "blue-noise".parse::<SamplerKind>(),
+        Ok(SamplerKind::BlueNoise)
+    );
+    assert!("halton".parse::<SamplerKind>().is_err());
+    assert!("Sobol".parse::<SamplerKind>().is_err());
+}
+
+#[test]
+fn samplers_cycle_through_all_kinds() {
+    assert_eq!(SamplerKind::default(), SamplerKind::Random);
+    assert_eq!(SamplerKind::Random.next(), SamplerKind::Sobol);
+    assert_eq!(SamplerKind::Sobol.next(), SamplerKind::BlueNoise);
+    assert_eq!(SamplerKind::BlueNoise.next(), SamplerKind::Random);
+}
+
+#[test]
+fn blue_noise_ranks_every_pixel_once() {
+    let size = 16;
+    let mut values = generate_blue_noise(size, 7);
+
+    values.sort_by(f32::total_cmp);
+    let count = size * size;
+    for (rank, value) in values.iter().enumerate() {
+        assert_eq!(*value, (rank as f32 + 0.5) / count as f32);
+    }
+}
+
+#[test]
+fn blue_noise_spreads_out_similar_values() {
+    let size = 32;
+    let values = generate_blue_noise(size, 7);
+    let at = |x: usize, y: usize| values[(y % size) * size + x % size];
+
+    // White noise has a mean difference of 1/3 between neighbours, blue noise
+    // lacks the low frequencies that make neighbours alike
+    let mut difference = 0.0;
+    for y in 0..size {
+        for x in 0..size {
+            difference += (at(x, y) - at(x + 1, y)).abs() + (at(x, y) - at(x, y + 1)).abs();
+        }
+    }
+    let mean_difference = difference / (2 * size * size) as f32;
+    assert!(mean_difference > 0.4, "mean difference {}", mean_difference);
+
+    // The darkest tenth of the pixels are no direct neighbours of each other,
+    // with the tile wrapping around
+    for y in 0..size {
+        for x in 0..size {
+            if at(x, y) < 0.1 {
+                assert!(at(x + 1, y) >= 0.1 && at(x, y + 1) >= 0.1);
+                assert!(at(x + 1, y + 1) >= 0.1 && at(x + size - 1, y + 1) >= 0.1);
+            }
+        }
+    }
+}
+
+#[test]
+fn small_blue_noise_tiles_rank_every_pixel() {
+    assert!(generate_blue_noise(0, 7).is_empty());
+    for size in 1..4 {
+        let mut values = generate_blue_noise(size, 7);
+
+        values.sort_by(f32::total_cmp);
+        let count = size * size;
+        for (rank, value) in values.iter().enumerate() {
+            assert_eq!(*value, (rank as f32 + 0.5) / count as f32);
+        }
+    }
+}
+
+#[test]
+fn blue_noise_tile_is_generated_once() {
+    let tile = blue_noise_tile();
+
+    assert_eq!(tile.len(), BLUE_NOISE_SIZE * BLUE_NOISE_SIZE);
+    assert!(std::ptr::eq(tile, blue_noise_tile()));
+}
+
+#[test]
+fn reservoirs_match_the_shader_layout() {
+    assert_eq!(std::mem::size_of::<ReservoirUniform>(), 48);
+}
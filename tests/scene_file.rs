@@ -0,0 +1,79 @@
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+
+fn parse_spheres(spheres: &str) -> Result<Scene, SceneError> {
+    Scene::parse(&format!(
+        r#"Scene(
+            materials: {{ "white": (), "light": (emission_strength: 1.0) }},
+            spheres: [{}],
+        )"#,
+        spheres
+    ))
+}
+
+#[test]
+fn resolves_materials_by_name() {
+    let scene = parse_spheres(
+        r#"(position: (0.0, 0.0, 0.0), radius: 1.0, material: "light"),
+           (position: (2.0, 0.0, 0.0), radius: 0.5, material: "white")"#,
+    )
+    .unwrap();
+
+    let material_ids: Vec<u32> = scene.world.spheres.iter().map(|s| s.material_id).collect();
+    // Materials are numbered by name, "light" comes before "white"
+    assert_eq!(material_ids, vec![0, 1]);
+    assert_eq!(scene.world.spheres[1].radius, 0.5);
+}
+
+#[test]
+fn reports_unknown_materials() {
+    let result = parse_spheres(
+        r#"(position: (0.0, 0.0, 0.0), radius: 1.0, material: "white"),
+           (position: (2.0, 0.0, 0.0), radius: 1.0, material: "gold")"#,
+    );
+
+    match result {
+        Err(SceneError::UnknownMaterial { object, material }) => {
+            assert_eq!(object, "sphere 1");
+            assert_eq!(material, "gold");
+        }
+        Err(error) => panic!("expected an unknown material, got {}", error),
+        Ok(_) => panic!("expected an unknown material"),
+    }
+}
+
+#[test]
+fn reports_invalid_radii() {
+    for radius in ["0.0", "-1.0", "NaN", "inf"] {
+        let result = parse_spheres(&format!(
+            r#"(position: (0.0, 0.0, 0.0), radius: 1.0, material: "white"),
+               (position: (2.0, 0.0, 0.0), radius: {}, material: "white")"#,
+            radius
+        ));
+
+        assert!(
+            matches!(result, Err(SceneError::InvalidRadius { sphere: 1, .. })),
+            "radius {} was accepted",
+            radius
+        );
+    }
+}
+
+#[test]
+fn errors_name_what_is_wrong() {
+    let error = parse_spheres(r#"(position: (0.0, 0.0, 0.0), radius: 1.0, material: "gold")"#)
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "sphere 0 references unknown material 'gold'"
+    );
+
+    let error = parse_spheres(r#"(position: (0.0, 0.0, 0.0), radius: -2.0, material: "white")"#)
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "sphere 0 has invalid radius -2 (must be finite and greater than 0)"
+    );
+}
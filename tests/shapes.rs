@@ -0,0 +1,188 @@
+use cgmath::{InnerSpace, Vector3};
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+use wgpu_raytracer::types::axis_aligned_box::AxisAlignedBox;
+use wgpu_raytracer::types::disc::Disc;
+use wgpu_raytracer::types::intersectable::{Intersectable, IntersectableType};
+use wgpu_raytracer::types::plane::Plane;
+use wgpu_raytracer::types::quad::Quad;
+use wgpu_raytracer::types::sphere::Sphere;
+
+fn down() -> Vector3<f32> {
+    Vector3::new(0.0, -1.0, 0.0)
+}
+
+#[test]
+fn planes_are_hit_from_both_sides() {
+    let plane = Plane {
+        point: Vector3::new(0.0, 1.0, 0.0),
+        normal: Vector3::new(0.0, 2.0, 0.0),
+        material_id: 0,
+    };
+
+    assert_eq!(
+        plane.intersect(Vector3::new(5.0, 3.0, -7.0), down()),
+        Some(2.0)
+    );
+    assert_eq!(
+        plane.intersect(Vector3::new(0.0, -1.0, 0.0), -down()),
+        Some(2.0)
+    );
+    assert_eq!(plane.intersect(Vector3::new(0.0, 3.0, 0.0), -down()), None);
+    assert_eq!(
+        plane.intersect(Vector3::new(0.0, 3.0, 0.0), Vector3::unit_x()),
+        None
+    );
+}
+
+#[test]
+fn quads_are_bounded_by_their_edges() {
+    // Skewed parallelogram in the y = 0 plane
+    let quad = Quad {
+        corner: Vector3::new(0.0, 0.0, 0.0),
+        edge_u: Vector3::new(2.0, 0.0, 0.0),
+        edge_v: Vector3::new(1.0, 0.0, 1.0),
+        material_id: 0,
+    };
+    let hit = |x: f32, z: f32| quad.intersect(Vector3::new(x, 1.0, z), down());
+
+    assert_eq!(hit(1.0, 0.5), Some(1.0));
+    assert_eq!(hit(2.9, 0.95), Some(1.0));
+    assert_eq!(hit(0.1, 0.5), None);
+    assert_eq!(hit(1.0, 1.1), None);
+    assert_eq!(hit(2.5, 0.1), None);
+
+    let bounds = quad.bounds();
+    assert_eq!(bounds.min, Vector3::new(0.0, 0.0, 0.0));
+    assert_eq!(bounds.max, Vector3::new(3.0, 0.0, 1.0));
+}
+
+#[test]
+fn discs_are_bounded_by_their_radius() {
+    let disc = Disc {
+        center: Vector3::new(0.0, 0.0, 0.0),
+        normal: Vector3::new(1.0, 1.0, 0.0),
+        radius: 1.0,
+        material_id: 0,
+    };
+
+    let direction = Vector3::new(-1.0, -1.0, 0.0).normalize();
+    let distance = disc.intersect(Vector3::new(1.0, 1.0, 0.9), direction);
+    assert!((distance.unwrap() - 2.0f32.sqrt()).abs() < 1e-5);
+    assert_eq!(disc.intersect(Vector3::new(1.0, 1.0, 1.1), direction), None);
+
+    // Tilted 45 degrees around z, so it reaches sin(45°) along x and y
+    let bounds = disc.bounds();
+    let half_diagonal = 0.5f32.sqrt();
+    assert!((bounds.max.x - half_diagonal).abs() < 1e-5);
+    assert!((bounds.max.y - half_diagonal).abs() < 1e-5);
+    assert!((bounds.max.z - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn boxes_are_hit_on_entry_or_from_inside_on_exit() {
+    let axis_aligned_box = AxisAlignedBox {
+        min: Vector3::new(-1.0, -1.0, -1.0),
+        max: Vector3::new(1.0, 2.0, 1.0),
+        material_id: 0,
+    };
+
+    assert_eq!(
+        axis_aligned_box.intersect(Vector3::new(0.0, 5.0, 0.0), down()),
+        Some(3.0)
+    );
+    assert_eq!(
+        axis_aligned_box.intersect(Vector3::new(0.0, 0.0, 0.0), down()),
+        Some(1.0)
+    );
+    assert_eq!(
+        axis_aligned_box.intersect(Vector3::new(0.0, -2.0, 0.0), down()),
+        None
+    );
+    assert_eq!(
+        axis_aligned_box.intersect(Vector3::new(1.5, 5.0, 0.0), down()),
+        None
+    );
+}
+
+#[test]
+fn spheres_are_hit_on_entry_or_from_inside_on_exit() {
+    let sphere = Sphere {
+        position: Vector3::new(0.0, 0.0, 0.0),
+        radius: 1.0,
+        material_id: 0,
+    };
+
+    assert_eq!(
+        sphere.intersect(Vector3::new(0.0, 3.0, 0.0), down()),
+        Some(2.0)
+    );
+    assert_eq!(
+        sphere.intersect(Vector3::new(0.0, 0.5, 0.0), down()),
+        Some(1.5)
+    );
+    assert_eq!(sphere.intersect(Vector3::new(0.0, -2.0, 0.0), down()), None);
+}
+
+#[test]
+fn planes_stay_out_of_the_bvh() {
+    let scene = Scene::parse(
+        r#"Scene(
+            materials: { "white": () },
+            spheres: [(position: (0.0, 0.0, 0.0), radius: 1.0, material: "white")],
+            planes: [(point: (0.0, -1.0, 0.0), normal: (0.0, 1.0, 0.0), material: "white")],
+            quads: [(corner: (0.0, 0.0, 0.0), edge_u: (1.0, 0.0, 0.0), edge_v: (0.0, 1.0, 0.0), material: "white")],
+            discs: [(center: (0.0, 0.0, 0.0), normal: (0.0, 0.0, 1.0), radius: 1.0, material: "white")],
+            boxes: [(min: (0.0, 0.0, 0.0), max: (1.0, 1.0, 1.0), material: "white")],
+        )"#,
+    )
+    .unwrap();
+
+    let layout = scene.intersectables();
+    assert_eq!(layout.primitives.len(), 5);
+    assert_eq!(layout.bvh_primitive_count, 4);
+    assert_eq!(
+        layout.primitives[4].type_tag,
+        IntersectableType::Plane as u32
+    );
+}
+
+#[test]
+fn rejects_degenerate_shapes() {
+    let invalid_object = |shapes: &str| {
+        let source = format!("Scene(materials: {{ \"white\": () }}, {})", shapes);
+        match Scene::parse(&source) {
+            Err(SceneError::InvalidShape { object, .. }) => object,
+            Err(error) => panic!("expected an invalid shape, got {}", error),
+            Ok(_) => panic!("expected an invalid shape"),
+        }
+    };
+
+    assert_eq!(
+        invalid_object(
+            r#"planes: [(point: (0.0, 0.0, 0.0), normal: (0.0, 0.0, 0.0), material: "white")]"#
+        ),
+        "plane 0"
+    );
+    assert_eq!(
+        invalid_object(
+            r#"quads: [(corner: (0.0, 0.0, 0.0), edge_u: (1.0, 0.0, 0.0), edge_v: (2.0, 0.0, 0.0), material: "white")]"#
+        ),
+        "quad 0"
+    );
+    assert_eq!(
+        invalid_object(
+            r#"discs: [
+                (center: (0.0, 0.0, 0.0), normal: (0.0, 1.0, 0.0), radius: 1.0, material: "white"),
+                (center: (0.0, 0.0, 0.0), normal: (0.0, 1.0, 0.0), radius: -1.0, material: "white"),
+            ]"#
+        ),
+        "disc 1"
+    );
+    assert_eq!(
+        invalid_object(
+            r#"boxes: [(min: (0.0, 0.0, 0.0), max: (1.0, 0.0, 1.0), material: "white")]"#
+        ),
+        "box 0"
+    );
+}
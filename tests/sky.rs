@@ -0,0 +1,172 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
+use wgpu_raytracer::scene::scene_error::SceneError;
+use wgpu_raytracer::scene::scene_file::Scene;
+use wgpu_raytracer::sky::{
+    sky_values, sun_path, sun_transmittance, zenith_values, Sky, SunPosition, SUN_IRRADIANCE,
+};
+use wgpu_raytracer::types::intersectable::IntersectableLayout;
+
+fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+    assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn sun_follows_its_path_over_the_day() {
+    let latitude = 40f32.to_radians();
+    for hours in [0.0, 7.0, 12.0, 15.5] {
+        assert!((sun_path(hours, latitude, 0.0).magnitude() - 1.0).abs() < 1e-5);
+    }
+
+    // Highest at noon towards the azimuth, 90 degrees minus the latitude up
+    let noon = sun_path(12.0, latitude, 0.0);
+    assert_close(
+        noon,
+        Vector3::new(0.0, 50f32.to_radians().sin(), 50f32.to_radians().cos()),
+    );
+    // Rises on the -x side and sets on the +x side at an equinox
+    assert_close(sun_path(6.0, latitude, 0.0), Vector3::new(-1.0, 0.0, 0.0));
+    assert_close(sun_path(18.0, latitude, 0.0), Vector3::new(1.0, 0.0, 0.0));
+    assert!(sun_path(0.0, latitude, 0.0).y < 0.0);
+    // The azimuth turns the whole path
+    assert_close(
+        sun_path(18.0, latitude, 90f32.to_radians()),
+        Vector3::new(0.0, 0.0, -1.0),
+    );
+
+    let sky = Sky {
+        sun: SunPosition::Fixed {
+            elevation: 30.0,
+            azimuth: 90.0,
+        },
+        ..Default::default()
+    };
+    assert_close(
+        sky.sun_direction(8.0),
+        Vector3::new(30f32.to_radians().cos(), 0.5, 0.0),
+    );
+    assert_eq!(sky.hours(), 12.0);
+}
+
+#[test]
+fn sky_is_brightest_around_the_sun() {
+    let sun = Vector3::new(0.0, 0.5, 0.75f32.sqrt());
+    let theta_sun = sun.y.acos();
+
+    // The Perez distribution is normalized to the zenith
+    let zenith = sky_values(Vector3::new(0.0, 1.0, 0.0), sun, 3.0);
+    let expected = zenith_values(theta_sun, 3.0);
+    for i in 0..3 {
+        assert!((zenith[i] - expected[i]).abs() < 1e-4 * expected[i].abs().max(1.0));
+    }
+
+    let near_sun = Vector3::new(0.0, 0.45, 0.8).normalize();
+    let away = Vector3::new(0.0, 0.45, -0.8).normalize();
+    assert!(sky_values(near_sun, sun, 3.0)[0] > 2.0 * sky_values(away, sun, 3.0)[0]);
+
+    // Hazy skies are less blue at the zenith
+    let sky = |turbidity| Sky {
+        turbidity,
+        ..Default::default()
+    };
+    let clear = sky(2.0).radiance(Vector3::new(0.0, 1.0, 0.0), sun);
+    let hazy = sky(8.0).radiance(Vector3::new(0.0, 1.0, 0.0), sun);
+    assert!(clear[2] / clear[0] > hazy[2] / hazy[0]);
+    assert!(clear.iter().all(|value| value.is_finite() && *value >= 0.0));
+
+    // Dark once the sun is well below the horizon
+    let night = Vector3::new(0.0, -0.3, 0.95f32.sqrt());
+    assert_eq!(
+        sky(3.0).radiance(Vector3::new(0.0, 1.0, 0.0), night),
+        [0.0; 3]
+    );
+}
+
+#[test]
+fn low_sun_is_dimmer_and_redder() {
+    let high = sun_transmittance(Vector3::new(0.0, 1.0, 0.0), 3.0);
+    let low = sun_transmittance(Vector3::new(0.0, 0.05, 1.0).normalize(), 3.0);
+    for i in 0..3 {
+        assert!(low[i] < high[i] && high[i] < 1.0);
+    }
+    assert!(high[0] > high[2]);
+    assert!(low[0] / low[2] > high[0] / high[2]);
+
+    // Over its disc the sun brings its irradiance through the atmosphere
+    let sky = Sky::default();
+    let sun = Vector3::new(0.0, 1.0, 0.0);
+    let radius = sky.sun_angular_radius.to_radians();
+    let solid_angle = 2.0 * PI * (1.0 - radius.cos());
+    let radiance = sky.sun_radiance(sun);
+    for i in 0..3 {
+        let irradiance = radiance[i] * solid_angle;
+        assert!((irradiance - SUN_IRRADIANCE * high[i]).abs() < 1e-3 * SUN_IRRADIANCE);
+    }
+}
+
+#[test]
+fn skies_are_parsed_and_validated() {
+    let scene = Scene::parse(
+        r#"Scene(
+            environment: (sky: Some((turbidity: 4.0, sun: TimeOfDay(hours: 17.5, latitude: 50.0)))),
+            animation: Some((path: Keyframes([]), time_of_day: Some((6.0, 20.0)))),
+        )"#,
+    )
+    .unwrap();
+    let sky = scene.environment.sky.unwrap();
+    assert_eq!(sky.turbidity, 4.0);
+    assert_eq!(
+        sky.sun,
+        SunPosition::TimeOfDay {
+            hours: 17.5,
+            latitude: 50.0,
+            azimuth: 0.0,
+        }
+    );
+    assert_eq!(sky.hours(), 17.5);
+    assert_eq!(scene.animation.unwrap().time_of_day, Some((6.0, 20.0)));
+
+    let invalid_environment = |environment: &str| {
+        let source = format!("Scene(environment: {})", environment);
+        matches!(
+            Scene::parse(&source),
+            Err(SceneError::InvalidEnvironment { .. })
+        )
+    };
+    assert!(invalid_environment("(sky: Some((turbidity: 1.0)))"));
+    assert!(invalid_environment(
+        "(sky: Some((sun: TimeOfDay(hours: 12.0, latitude: 100.0))))"
+    ));
+    assert!(invalid_environment(
+        r#"(sky: Some(()), map: Some((path: "scenes/environments/sunset.hdr")))"#
+    ));
+}
+
+#[test]
+fn sky_lights_are_weighed_at_the_time_of_day() {
+    let scene = Scene::parse(
+        r#"Scene(
+            materials: { "white": () },
+            spheres: [(position: (0.0, 0.0, 0.0), radius: 1.0, material: "white")],
+            lights: [Point(position: (0.0, 3.0, 0.0), intensity: 1.0)],
+            environment: (sky: Some((sun: TimeOfDay(hours: 0.0)))),
+        )"#,
+    )
+    .unwrap();
+    let layout = scene.intersectables();
+    let environment = &scene.environment;
+
+    // The sky comes after the point light, a night sky is hardly picked
+    let night = environment.power(None, layout.scene_radius, 0.0);
+    let noon = environment.power(None, layout.scene_radius, 12.0);
+    assert!(noon > 100.0 * night.max(1e-6), "{} {}", night, noon);
+    assert!(layout.light_table.pmf(1) < 0.01);
+
+    let table = IntersectableLayout::light_table(&layout.light_powers, Some(noon));
+    assert!(table.pmf(1) > 0.5, "{:?}", table);
+    assert_eq!(
+        IntersectableLayout::light_table(&layout.light_powers, Some(night)).entries,
+        layout.light_table.entries
+    );
+}